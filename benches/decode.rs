@@ -0,0 +1,45 @@
+//! Throughput of `decode_into` on a multi-megabyte rom.
+//!
+//! Run with `cargo bench`.
+
+use std::fmt::{self, Write};
+use std::time::Instant;
+
+use intel_8080_disassembler::decode::decode_into;
+
+/// Size of the decoded rom.
+const ROM_SIZE: usize = 8 * 1024 * 1024;
+
+/// Output discarding the text, counting its length.
+struct Counter(usize);
+
+impl Write for Counter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+fn main() {
+    // Pseudo-random bytes from a linear congruential generator, to exercise every opcode.
+    let mut state: u32 = 1;
+    let rom: Vec<u8> = (0..ROM_SIZE)
+        .map(|_| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            state.to_be_bytes()[0]
+        })
+        .collect();
+
+    let mut output = Counter(0);
+    let start = Instant::now();
+    decode_into(&rom, &mut output).expect("counting never fails");
+    let elapsed = start.elapsed();
+
+    #[allow(clippy::cast_precision_loss)]
+    let megabytes = ROM_SIZE as f64 / 1_000_000.0;
+    println!(
+        "decoded {megabytes:.1} MB into {} bytes of text in {elapsed:.2?}: {:.1} MB/s",
+        output.0,
+        megabytes / elapsed.as_secs_f64()
+    );
+}
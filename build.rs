@@ -0,0 +1,118 @@
+//! Generate the opcode table of `src/opcodes.rs` from `data/opcodes.csv`.
+
+use std::fmt::Write;
+
+const TABLE: &str = "data/opcodes.csv";
+
+fn main() {
+    println!("cargo:rerun-if-changed={TABLE}");
+    let text = std::fs::read_to_string(TABLE).unwrap_or_else(|e| panic!("reading {TABLE}: {e}"));
+
+    let mut entries: Vec<Option<String>> = vec![None; 256];
+    for (index, line) in text.lines().enumerate().skip(1) {
+        if line.is_empty() {
+            continue;
+        }
+        let (opcode, entry) =
+            parse_line(line).unwrap_or_else(|e| panic!("{TABLE}:{}: {e}: \"{line}\"", index + 1));
+        if entries[usize::from(opcode)].replace(entry).is_some() {
+            panic!("{TABLE}:{}: opcode {opcode:02X} given twice", index + 1);
+        }
+    }
+
+    let mut table = String::from("[\n");
+    for (opcode, entry) in entries.iter().enumerate() {
+        let entry = entry
+            .as_deref()
+            .unwrap_or_else(|| panic!("{TABLE}: opcode {opcode:02X} is missing"));
+        // Writing to a string never fails.
+        let _ = writeln!(table, "    // {opcode:02X}\n    {entry},");
+    }
+    table.push(']');
+
+    let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("opcodes.rs");
+    std::fs::write(&out, table).unwrap_or_else(|e| panic!("writing {}: {e}", out.display()));
+}
+
+/// Parse a line of the table into its opcode and the expression building its `Opcode`.
+fn parse_line(line: &str) -> Result<(u8, String), String> {
+    let fields = split_fields(line)?;
+    let [opcode, mnemonic, registers, immediate, cycles, flags] = fields.as_slice() else {
+        return Err(format!("expected 6 fields, found {}", fields.len()));
+    };
+
+    let opcode = u8::from_str_radix(opcode, 16).map_err(|_| "invalid opcode")?;
+    if mnemonic.is_empty() || !mnemonic.bytes().all(|b| b.is_ascii_uppercase()) {
+        return Err("invalid mnemonic".to_owned());
+    }
+    let immediate = match immediate.as_str() {
+        "" => "Empty",
+        "d8" => "Byte",
+        "d16" => "Word",
+        "a16" => "Address",
+        "p8" => "Port",
+        _ => return Err("expected an immediate of d8, d16, a16, p8 or nothing".to_owned()),
+    };
+    let cycles = match cycles.split_once('/') {
+        Some((taken, not_taken)) => format!(
+            "Cycles::conditional({}, {})",
+            parse_cycles(taken)?,
+            parse_cycles(not_taken)?
+        ),
+        None => format!("Cycles::fixed({})", parse_cycles(cycles)?),
+    };
+    let flags = parse_flags(flags)?;
+
+    Ok((
+        opcode,
+        format!(
+            "op({mnemonic:?}, {registers:?}, Immediate::{immediate}, {cycles}, Flags(0x{flags:02X}))"
+        ),
+    ))
+}
+
+fn parse_cycles(text: &str) -> Result<u8, String> {
+    text.parse().map_err(|_| "invalid clock states".to_owned())
+}
+
+/// Parse flags written as `SZAPC`, with a dash in place of the unaffected ones.
+fn parse_flags(text: &str) -> Result<u8, String> {
+    // Bits of S, Z, AC, P and CY in the PSW register.
+    const FLAGS: [(char, u8); 5] = [
+        ('S', 0x80),
+        ('Z', 0x40),
+        ('A', 0x10),
+        ('P', 0x04),
+        ('C', 0x01),
+    ];
+
+    if text.chars().count() != FLAGS.len() {
+        return Err("expected flags like SZAPC or -----".to_owned());
+    }
+    let mut bits = 0;
+    for (c, (name, bit)) in text.chars().zip(FLAGS) {
+        match c {
+            '-' => {}
+            c if c == name => bits |= bit,
+            _ => return Err("expected flags like SZAPC or -----".to_owned()),
+        }
+    }
+    Ok(bits)
+}
+
+/// Split a line of comma separated fields, some quoted to hold commas.
+fn split_fields(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quote".to_owned());
+    }
+    Ok(fields)
+}
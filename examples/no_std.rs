@@ -0,0 +1,28 @@
+//! Decoder used from a `no_std` crate with an allocator, such as the firmware of a monitor.
+//!
+//! Built as a library, as a `no_std` binary would need a target of its own:
+//!
+//! ```text
+//! cargo build --no-default-features --features alloc --example no_std
+//! ```
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use intel_8080_disassembler::decode::{self, DecodeError, Disassembler};
+use intel_8080_disassembler::instruction::{Bytes, Instruction};
+
+/// Instructions of a rom with their address, and the error ending the rom if any.
+pub fn instructions(rom: &[u8]) -> (Vec<(u16, Instruction)>, Option<DecodeError>) {
+    let lines = Disassembler::new(rom).collect();
+    let error = decode::decode(rom).find_map(Result::err);
+    (lines, error)
+}
+
+/// Bytes of the instruction the program counter of an emulator points to.
+pub fn next_instruction(memory: &[u8], pc: u16) -> Bytes {
+    decode::decode_at(memory, pc)
+}
@@ -0,0 +1,69 @@
+/* C interface to the Intel 8080 decoder of intel-8080-disassembler.
+ *
+ * Build the library with the ffi feature, as a shared or static library:
+ *
+ *     cargo rustc --release --lib --features ffi --crate-type cdylib
+ *     cargo rustc --release --lib --features ffi --crate-type staticlib
+ *
+ * Keep in sync with src/ffi.rs.
+ */
+
+#ifndef I8080_H
+#define I8080_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Kinds of immediates following the opcode. */
+#define I8080_IMMEDIATE_NONE 0
+#define I8080_IMMEDIATE_BYTE 1
+#define I8080_IMMEDIATE_WORD 2
+#define I8080_IMMEDIATE_ADDRESS 3
+#define I8080_IMMEDIATE_PORT 4
+
+/* Flags modified, in their PSW bits. */
+#define I8080_FLAG_S 0x80
+#define I8080_FLAG_Z 0x40
+#define I8080_FLAG_AC 0x10
+#define I8080_FLAG_P 0x04
+#define I8080_FLAG_CY 0x01
+
+typedef struct i8080_insn {
+    /* Bytes of the instruction, followed by zeros. */
+    uint8_t bytes[3];
+    /* Number of bytes of the instruction, from 1 to 3. */
+    uint8_t length;
+    /* Mnemonic, like "MVI". */
+    char mnemonic[5];
+    /* Registers or vector encoded in the opcode, like "A" or "B,C". */
+    char registers[4];
+    /* One of the I8080_IMMEDIATE_ constants. */
+    uint8_t immediate_kind;
+    /* Value of the byte or word following the opcode, 0 without one. */
+    uint16_t immediate;
+    /* Clock states taken when the condition of a conditional call or return is met, and
+     * by any other instruction. */
+    uint8_t cycles_taken;
+    /* Clock states taken when the condition of a conditional call or return is not met. */
+    uint8_t cycles_not_taken;
+    /* Flags modified, as I8080_FLAG_ bits. */
+    uint8_t flags;
+    /* Whether the opcode is undocumented, executed as another instruction. */
+    uint8_t undocumented;
+} i8080_insn;
+
+/* Decode the instruction at the start of `length` bytes into `insn`.
+ *
+ * Returns the number of bytes of the instruction, or 0 when the bytes end before the
+ * instruction does, leaving `insn` untouched. Null pointers decode nothing. */
+size_t i8080_decode(const uint8_t *bytes, size_t length, i8080_insn *insn);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
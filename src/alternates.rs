@@ -0,0 +1,74 @@
+//! Decodings starting at each of a few offsets, to find where a misaligned sweep went wrong.
+
+use intel_8080_disassembler::decode::{self, Decoded};
+
+/// Offsets decoded from when no other number is given.
+pub const OFFSETS: usize = 4;
+
+/// Bytes decoded from each offset.
+const SPAN: usize = 16;
+
+/// Print the instructions decoded from `address` and from each of the `offsets - 1` bytes
+/// after it.
+///
+/// The decoding from `address` covers the whole span, the others stop where they meet it
+/// again, since they decode the same instructions from there.
+pub fn print(
+    out: &mut dyn std::io::Write,
+    rom: &[u8],
+    address: usize,
+    offsets: usize,
+) -> std::io::Result<()> {
+    let end = (address + SPAN).min(rom.len());
+    let decode = |start| -> Vec<Decoded> {
+        decode::decode_from(rom, start)
+            .map_while(Result::ok)
+            .take_while(|d| d.address < end)
+            .collect()
+    };
+    let reference = decode(address);
+
+    writeln!(out, "; from 0x{address:04x} (+0)")?;
+    print_instructions(out, &reference)?;
+    for offset in 1..offsets {
+        let start = address + offset;
+        let decoding = decode(start);
+        let meets = decoding
+            .iter()
+            .position(|d| reference.iter().any(|r| r.address == d.address));
+        writeln!(out)?;
+        match meets {
+            Some(index) => writeln!(
+                out,
+                "; from 0x{start:04x} (+{offset}), meets +0 at 0x{:04x}",
+                decoding[index].address
+            )?,
+            None => writeln!(out, "; from 0x{start:04x} (+{offset}), never meets +0")?,
+        }
+        print_instructions(out, &decoding[..meets.unwrap_or(decoding.len())])?;
+    }
+    Ok(())
+}
+
+fn print_instructions(
+    out: &mut dyn std::io::Write,
+    instructions: &[Decoded],
+) -> std::io::Result<()> {
+    for instruction in instructions {
+        let mut text = String::new();
+        // Writing to a string never fails.
+        let _ = decode::write_instruction(instruction.bytes, &mut text);
+        let bytes: Vec<String> = instruction
+            .bytes
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        writeln!(
+            out,
+            "{:04x}  {:<9} {text}",
+            instruction.address,
+            bytes.join(" ")
+        )?;
+    }
+    Ok(())
+}
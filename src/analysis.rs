@@ -0,0 +1,721 @@
+//! Flow analysis separating code from data and naming the addresses it refers to.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use crate::input::Image;
+use crate::memory::MemoryView;
+use crate::opcodes::{self, Immediate, Opcode};
+use crate::padding;
+
+/// Disassembly of a whole image, with labels on the referenced addresses.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Listing {
+    /// Lines in address order, covering every byte of the image.
+    pub lines: Vec<Line>,
+    /// Labels by address.
+    pub labels: BTreeMap<u16, Label>,
+    /// Addresses of the subroutines, in increasing order.
+    pub functions: Vec<u16>,
+    /// Byte ranges not reached by the flow of execution, excluding padding.
+    pub data_regions: Vec<Range<usize>>,
+    /// Signs of a damaged or truncated image met by the flow of execution, in address order.
+    pub problems: Vec<Problem>,
+    /// Comments on the lines starting at an address.
+    pub comments: BTreeMap<u16, String>,
+    /// Names of addresses outside the lines of the listing, such as system entry points.
+    pub symbols: BTreeMap<u16, String>,
+}
+
+impl Listing {
+    /// Name of an address referred to by an instruction, from its label or symbol.
+    #[must_use]
+    pub fn name(&self, address: u16) -> Option<&str> {
+        self.labels
+            .get(&address)
+            .map(|label| label.name.as_str())
+            .or_else(|| self.symbols.get(&address).map(String::as_str))
+    }
+
+    /// Name of the 16-bit immediate of an instruction.
+    ///
+    /// Constants, as loaded by LXI, are only named after symbols and data labels, so that
+    /// small numbers do not take the name of the code at that address.
+    #[must_use]
+    pub fn operand_name(&self, opcode: &Opcode, value: u16) -> Option<&str> {
+        match opcode.immediate {
+            Immediate::Address => self.name(value),
+            Immediate::Word => match self.labels.get(&value) {
+                Some(label) if label.kind == LabelKind::Data => Some(&label.name),
+                Some(_) => None,
+                None => self.symbols.get(&value).map(String::as_str),
+            },
+            Immediate::Empty | Immediate::Byte | Immediate::Port => None,
+        }
+    }
+
+    /// Addresses of the instructions referring to each address, by jump, call, restart or
+    /// direct memory access, in increasing order.
+    #[must_use]
+    pub fn references(&self) -> BTreeMap<u16, Vec<u16>> {
+        let mut references: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+        for line in &self.lines {
+            let Line::Instruction { address, bytes } = line else {
+                continue;
+            };
+            let target = match *bytes.as_slice() {
+                [_, low, high] => Some(u16::from_le_bytes([low, high])),
+                _ => None,
+            };
+            if let (_, Some((target, _))) = flow(&opcodes::lookup(bytes[0]), target) {
+                references.entry(target).or_default().push(*address);
+            }
+        }
+        references
+    }
+
+    /// Bytes of the function starting at `address`: the instructions reached from it
+    /// without following calls or entering other functions, up to the last of them.
+    #[must_use]
+    pub fn function_range(&self, address: u16) -> Option<Range<usize>> {
+        if !self.functions.contains(&address) {
+            return None;
+        }
+        let mut end = usize::from(address);
+        let mut visited = BTreeSet::new();
+        let mut pending = vec![address];
+        while let Some(current) = pending.pop() {
+            if current < address || !visited.insert(current) {
+                continue;
+            }
+            let Ok(index) = self.lines.binary_search_by_key(&current, Line::address) else {
+                continue;
+            };
+            let Line::Instruction { bytes, .. } = &self.lines[index] else {
+                continue;
+            };
+            let next = usize::from(current) + bytes.len();
+            end = end.max(next);
+
+            let target = match *bytes.as_slice() {
+                [_, low, high] => Some(u16::from_le_bytes([low, high])),
+                _ => None,
+            };
+            let (falls_through, reference) = flow(&opcodes::lookup(bytes[0]), target);
+            if let Some((target, LabelKind::Jump)) = reference {
+                if !self.functions.contains(&target) {
+                    pending.push(target);
+                }
+            }
+            if falls_through {
+                pending.extend(u16::try_from(next));
+            }
+        }
+        Some(usize::from(address)..end)
+    }
+
+    /// Part of the listing covering a range of addresses, such as a function.
+    ///
+    /// Labels outside the range are kept as symbols, so that the part still names the
+    /// addresses it refers to.
+    #[must_use]
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        let contains = |address: &u16| range.contains(&usize::from(*address));
+        let mut symbols = self.symbols.clone();
+        for (address, label) in &self.labels {
+            if !contains(address) {
+                symbols.insert(*address, label.name.clone());
+            }
+        }
+        Self {
+            lines: self
+                .lines
+                .iter()
+                .filter(|line| contains(&line.address()))
+                .cloned()
+                .collect(),
+            labels: self
+                .labels
+                .iter()
+                .filter(|(address, _)| contains(address))
+                .map(|(address, label)| (*address, label.clone()))
+                .collect(),
+            functions: self.functions.iter().copied().filter(contains).collect(),
+            data_regions: self
+                .data_regions
+                .iter()
+                .filter(|region| range.contains(&region.start))
+                .map(|region| region.start..region.end.min(range.end))
+                .collect(),
+            problems: self
+                .problems
+                .iter()
+                .copied()
+                .filter(|p| contains(&p.address()))
+                .collect(),
+            comments: self
+                .comments
+                .iter()
+                .filter(|(address, _)| contains(address))
+                .map(|(address, comment)| (*address, comment.clone()))
+                .collect(),
+            symbols,
+        }
+    }
+
+    /// Comment the line at `address`, after the comment it already has.
+    pub fn add_comment(&mut self, address: u16, comment: String) {
+        self.comments
+            .entry(address)
+            .and_modify(|c| *c = format!("{c}; {comment}"))
+            .or_insert(comment);
+    }
+
+    /// Rename every label and symbol with `rename`, given the address and name of each, such
+    /// as to prefix names with their bank or follow a naming convention when rendering.
+    pub fn rename_all(&mut self, mut rename: impl FnMut(u16, &str) -> String) {
+        for (&address, label) in &mut self.labels {
+            label.name = rename(address, &label.name);
+        }
+        for (&address, name) in &mut self.symbols {
+            *name = rename(address, name);
+        }
+    }
+
+    /// Drop the lines below `address`, such as memory not loaded from a file, keeping the
+    /// names of their labels as symbols.
+    pub fn start_at(&mut self, address: u16) {
+        self.lines.retain(|line| line.address() >= address);
+        let below: Vec<u16> = self.labels.range(..address).map(|(a, _)| *a).collect();
+        for label_address in below {
+            if let Some(label) = self.labels.remove(&label_address) {
+                self.symbols.entry(label_address).or_insert(label.name);
+            }
+        }
+        self.functions.retain(|&function| function >= address);
+        self.data_regions
+            .retain(|region| region.start >= usize::from(address));
+        self.comments = self.comments.split_off(&address);
+    }
+
+    /// Split the data at `address` into fields of the given names and sizes, commented with
+    /// their name.
+    ///
+    /// Returns whether the fields were laid, which they are not when they would cover
+    /// anything but data or padding.
+    pub fn overlay(&mut self, address: u16, fields: &[(&str, usize)]) -> bool {
+        let start = usize::from(address);
+        let end = start + fields.iter().map(|(_, size)| size).sum::<usize>();
+        let first = self
+            .lines
+            .partition_point(|line| usize::from(line.address()) + line.length() <= start);
+        let last = self
+            .lines
+            .partition_point(|line| usize::from(line.address()) < end);
+        let covered = &self.lines[first..last];
+        let is_data = covered
+            .iter()
+            .all(|line| matches!(line, Line::Data { .. } | Line::Padding { .. }));
+        let covered_end = covered
+            .last()
+            .map_or(0, |line| usize::from(line.address()) + line.length());
+        if covered.is_empty() || !is_data || covered_end < end {
+            return false;
+        }
+
+        let covered_start = usize::from(covered[0].address());
+        let bytes: Vec<u8> = covered
+            .iter()
+            .flat_map(|line| match line {
+                Line::Data { bytes, .. } => bytes.clone(),
+                Line::Padding { length, byte, .. } => vec![*byte; *length],
+                Line::Instruction { .. } => Vec::new(),
+            })
+            .collect();
+        let mut lines = Vec::new();
+        let mut split = |from: usize, to: usize| {
+            if from < to {
+                lines.push(Line::Data {
+                    // Addresses of the image always fit in 16 bits.
+                    address: u16::try_from(from).unwrap_or(u16::MAX),
+                    bytes: bytes[from - covered_start..to - covered_start].to_vec(),
+                });
+            }
+        };
+
+        split(covered_start, start);
+        let mut field_start = start;
+        for (name, size) in fields {
+            split(field_start, field_start + size);
+            if *size > 0 {
+                let field_address = u16::try_from(field_start).unwrap_or(u16::MAX);
+                self.comments.insert(field_address, (*name).to_owned());
+            }
+            field_start += size;
+        }
+        split(end, covered_end);
+
+        self.lines.splice(first..last, lines);
+        true
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Line {
+    Instruction {
+        address: u16,
+        bytes: Vec<u8>,
+    },
+    /// Bytes not reached by the flow of execution.
+    Data {
+        address: u16,
+        bytes: Vec<u8>,
+    },
+    /// Block of filler bytes.
+    Padding {
+        address: u16,
+        length: usize,
+        byte: u8,
+    },
+}
+
+impl Line {
+    #[must_use]
+    pub const fn address(&self) -> u16 {
+        match self {
+            Self::Instruction { address, .. }
+            | Self::Data { address, .. }
+            | Self::Padding { address, .. } => *address,
+        }
+    }
+
+    /// Bytes covered by the line.
+    #[must_use]
+    pub const fn length(&self) -> usize {
+        match self {
+            Self::Instruction { bytes, .. } | Self::Data { bytes, .. } => bytes.len(),
+            Self::Padding { length, .. } => *length,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Label {
+    pub name: String,
+    pub kind: LabelKind,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LabelKind {
+    /// Target of a call or restart, or an entry point.
+    Function,
+    /// Target of a jump.
+    Jump,
+    /// Memory read or written by LDA, STA, LHLD or SHLD.
+    Data,
+}
+
+impl LabelKind {
+    const fn prefix(self) -> &'static str {
+        match self {
+            Self::Function => "sub",
+            Self::Jump => "loc",
+            Self::Data => "dat",
+        }
+    }
+}
+
+/// Inconsistency found while following the flow of execution.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Problem {
+    /// Instruction cut off by the end of the image.
+    Truncated { address: u16 },
+    /// Jump or call to an address outside the image.
+    OutOfImage { address: u16, target: u16 },
+    /// Execution reaching the middle of an instruction.
+    Overlap { address: u16 },
+}
+
+impl Problem {
+    /// Address of the instruction showing the problem.
+    #[must_use]
+    pub const fn address(&self) -> u16 {
+        match self {
+            Self::Truncated { address }
+            | Self::OutOfImage { address, .. }
+            | Self::Overlap { address } => *address,
+        }
+    }
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncated { address } => {
+                write!(
+                    f,
+                    "0x{address:04x}: instruction cut off by the end of the image"
+                )
+            }
+            Self::OutOfImage { address, target } => {
+                write!(
+                    f,
+                    "0x{address:04x}: target 0x{target:04x} is outside the image"
+                )
+            }
+            Self::Overlap { address } => {
+                write!(
+                    f,
+                    "0x{address:04x}: execution reaches the middle of an instruction"
+                )
+            }
+        }
+    }
+}
+
+/// Bytes per line of data.
+const DATA_LINE_LENGTH: usize = 8;
+
+/// Bytes the 8080 can address, past which an image is not analyzed.
+pub const MEMORY_SIZE: usize = 0x10000;
+
+/// Parameters following a call or restart inline, which the callee reads through the return
+/// address before returning past them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Inline {
+    /// A fixed number of bytes.
+    Bytes(usize),
+    /// Bytes up to and including a terminator, such as a string ending with `$`.
+    Until(u8),
+}
+
+impl Inline {
+    /// Parse a number of bytes, or a terminator after `..`, like `2` or `..0x24`.
+    #[must_use]
+    pub fn from_text(text: &str) -> Option<Self> {
+        match text.strip_prefix("..") {
+            Some(terminator) => {
+                let terminator = crate::encode::parse_number(terminator).ok()?;
+                u8::try_from(terminator).ok().map(Self::Until)
+            }
+            None => text.parse().ok().map(Self::Bytes),
+        }
+    }
+}
+
+/// Restarts and functions followed by inline parameters, by vector and address.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Parameters {
+    pub restarts: BTreeMap<u8, Inline>,
+    pub calls: BTreeMap<u16, Inline>,
+}
+
+impl Parameters {
+    /// Parameters following an instruction referring to an address, with the comment
+    /// naming what they belong to.
+    fn after(
+        &self,
+        opcode: &Opcode,
+        reference: Option<(u16, LabelKind)>,
+    ) -> Option<(Inline, String)> {
+        match (reference, opcode.mnemonic) {
+            (_, "RST") => {
+                let vector = opcode.registers.parse().unwrap_or_default();
+                let comment = format!("parameter of RST {vector}");
+                self.restarts.get(&vector).map(|inline| (*inline, comment))
+            }
+            (Some((target, LabelKind::Function)), _) => {
+                let comment = format!("parameter of CALL 0x{target:04x}");
+                self.calls.get(&target).map(|inline| (*inline, comment))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Disassemble a rom loaded at address 0, following the flow of execution from there.
+///
+/// Bytes never reached are reported as data. Called addresses are labeled `sub_XXXX`,
+/// jump targets `loc_XXXX` and memory accessed directly `dat_XXXX`.
+#[must_use]
+pub fn disassemble_with_labels(rom: &[u8]) -> Listing {
+    analyze(&Image::from_file("", rom.to_vec()), &[0])
+}
+
+/// Disassemble an image, following the flow of execution from each entry point.
+#[must_use]
+pub fn analyze(image: &Image, entry_points: &[u16]) -> Listing {
+    analyze_with_parameters(image, entry_points, &Parameters::default())
+}
+
+/// Disassemble an image like [`analyze`], skipping the parameters following restarts and
+/// calls inline.
+///
+/// Such parameters are used by the system calls of some monitors and by print routines
+/// taking the string after the call, whose callee returns past them. The parameters are
+/// listed as data commented with the instruction they belong to.
+///
+/// Only the first [`MEMORY_SIZE`] bytes of a larger image are analyzed, as the addresses
+/// of the rest do not fit in 16 bits.
+#[must_use]
+pub fn analyze_with_parameters(
+    image: &Image,
+    entry_points: &[u16],
+    parameters: &Parameters,
+) -> Listing {
+    let size = image.bytes.len().min(MEMORY_SIZE);
+    let padding: Vec<padding::Padding> = padding::find(image)
+        .into_iter()
+        .filter(|padding| padding.range.start < size)
+        .map(|padding| padding::Padding {
+            range: padding.range.start..padding.range.end.min(size),
+            ..padding
+        })
+        .collect();
+    follow(
+        image.bytes.as_slice(),
+        size,
+        &padding,
+        entry_points,
+        parameters,
+    )
+}
+
+/// Disassemble the first `size` bytes of memory as the CPU sees it, such as the live
+/// memory of an emulator, following the flow of execution from each entry point.
+///
+/// Bytes are read through the view as the analysis needs them, and the lines of the
+/// listing keep the bytes they list. No padding is summarized, as no file tells where it
+/// lies.
+#[must_use]
+pub fn analyze_view(
+    memory: &(impl MemoryView + ?Sized),
+    size: usize,
+    entry_points: &[u16],
+) -> Listing {
+    follow(
+        memory,
+        size.min(MEMORY_SIZE),
+        &[],
+        entry_points,
+        &Parameters::default(),
+    )
+}
+
+/// Disassemble the first `size` bytes of memory, following the flow of execution from each
+/// entry point and skipping the parameters following restarts and calls.
+fn follow(
+    memory: &(impl MemoryView + ?Sized),
+    size: usize,
+    padding: &[padding::Padding],
+    entry_points: &[u16],
+    parameters: &Parameters,
+) -> Listing {
+    let is_padding = |address: usize| padding.iter().any(|p| p.range.contains(&address));
+    // Addresses below the size always fit in 16 bits.
+    let read = |address: usize| memory.read(u16::try_from(address).unwrap_or(u16::MAX));
+
+    let mut is_code = vec![false; size];
+    let mut instructions = BTreeMap::new();
+    let mut labels = BTreeMap::new();
+    let mut problems = Vec::new();
+    let mut comments = BTreeMap::new();
+    for &entry in entry_points {
+        labels.insert(entry, LabelKind::Function);
+    }
+
+    let mut pending: Vec<u16> = entry_points.to_vec();
+    while let Some(address) = pending.pop() {
+        let start = usize::from(address);
+        if start < size && is_code[start] && !instructions.contains_key(&address) {
+            problems.push(Problem::Overlap { address });
+        }
+        if start >= size || is_code[start] || is_padding(start) {
+            continue;
+        }
+
+        let opcode = opcodes::lookup(read(start));
+        let end = start + opcode.length();
+        if end > size {
+            problems.push(Problem::Truncated { address });
+            continue;
+        }
+        if (start..end).any(|a| is_code[a]) {
+            problems.push(Problem::Overlap { address });
+            continue;
+        }
+        is_code[start..end].fill(true);
+        instructions.insert(address, opcode);
+
+        let target =
+            (opcode.length() == 3).then(|| u16::from_le_bytes([read(start + 1), read(start + 2)]));
+        let (falls_through, reference) = flow(&opcode, target);
+        if let Some((target, kind)) = reference {
+            if usize::from(target) < size {
+                labels
+                    .entry(target)
+                    .and_modify(|k: &mut LabelKind| *k = strongest(*k, kind))
+                    .or_insert(kind);
+                if kind != LabelKind::Data {
+                    pending.push(target);
+                }
+            } else if kind != LabelKind::Data {
+                // Data outside the image is usually RAM.
+                problems.push(Problem::OutOfImage { address, target });
+            }
+        }
+        let mut next = end;
+        if let Some((inline, comment)) = parameters.after(&opcode, reference) {
+            next = match inline {
+                Inline::Bytes(length) => end + length,
+                Inline::Until(terminator) => (end..size)
+                    .find(|&a| read(a) == terminator)
+                    .map_or(size, |a| a + 1),
+            };
+            if next > end && end < size {
+                // The end of an instruction of the image always fits in 16 bits.
+                comments.insert(u16::try_from(end).unwrap_or(u16::MAX), comment);
+            }
+        }
+        if falls_through {
+            if let Ok(next) = u16::try_from(next) {
+                pending.push(next);
+            }
+        }
+    }
+
+    let (lines, data_regions) = split_lines(&read, padding, &instructions, &is_code, &labels);
+
+    // Labels in the middle of an instruction cannot be placed in the listing.
+    labels.retain(|address, _| lines.binary_search_by_key(address, Line::address).is_ok());
+
+    let functions = labels
+        .iter()
+        .filter(|(_, kind)| **kind == LabelKind::Function)
+        .map(|(address, _)| *address)
+        .collect();
+    let labels = labels
+        .into_iter()
+        .map(|(address, kind)| {
+            let name = format!("{}_{address:04x}", kind.prefix());
+            (address, Label { name, kind })
+        })
+        .collect();
+
+    problems.sort_unstable_by_key(|p| (p.address(), *p));
+    problems.dedup();
+
+    Listing {
+        lines,
+        labels,
+        functions,
+        data_regions,
+        problems,
+        comments,
+        symbols: BTreeMap::new(),
+    }
+}
+
+/// Lines covering the bytes read from the whole memory analyzed, with the data regions
+/// between the instructions.
+fn split_lines(
+    read: &impl Fn(usize) -> u8,
+    padding: &[padding::Padding],
+    instructions: &BTreeMap<u16, Opcode>,
+    is_code: &[bool],
+    labels: &BTreeMap<u16, LabelKind>,
+) -> (Vec<Line>, Vec<Range<usize>>) {
+    let mut lines = Vec::new();
+    let mut data_regions: Vec<Range<usize>> = Vec::new();
+    let mut address = 0;
+    while address < is_code.len() {
+        // Addresses of the image always fit in 16 bits.
+        let address16 = u16::try_from(address).unwrap_or(u16::MAX);
+        if let Some(block) = padding.iter().find(|p| p.range.start == address) {
+            lines.push(Line::Padding {
+                address: address16,
+                length: block.range.len(),
+                byte: block.byte,
+            });
+            address = block.range.end;
+        } else if let Some(opcode) = instructions.get(&address16) {
+            let end = address + opcode.length();
+            lines.push(Line::Instruction {
+                address: address16,
+                bytes: (address..end).map(read).collect(),
+            });
+            address = end;
+        } else {
+            // Data stops at code, padding, labels and after a full line.
+            let end = (address + 1..is_code.len())
+                .find(|&a| {
+                    is_code[a]
+                        || a - address == DATA_LINE_LENGTH
+                        || padding.iter().any(|p| p.range.start == a)
+                        || u16::try_from(a).is_ok_and(|a| labels.contains_key(&a))
+                })
+                .unwrap_or(is_code.len());
+            lines.push(Line::Data {
+                address: address16,
+                bytes: (address..end).map(read).collect(),
+            });
+            match data_regions.last_mut() {
+                Some(region) if region.end == address => region.end = end,
+                _ => data_regions.push(address..end),
+            }
+            address = end;
+        }
+    }
+    (lines, data_regions)
+}
+
+/// Addresses execution may go to after an instruction, without following calls: the next
+/// instruction unless it never continues there, and the target of a jump.
+#[must_use]
+pub fn successors(address: u16, bytes: &[u8]) -> Vec<u16> {
+    let target = match *bytes {
+        [_, low, high] => Some(u16::from_le_bytes([low, high])),
+        _ => None,
+    };
+    let (falls_through, reference) = flow(&opcodes::lookup(bytes[0]), target);
+    let mut successors = Vec::new();
+    if let Some((target, LabelKind::Jump)) = reference {
+        successors.push(target);
+    }
+    if falls_through {
+        successors.extend(u16::try_from(usize::from(address) + bytes.len()));
+    }
+    successors
+}
+
+/// Whether execution continues after an instruction, and the address it refers to.
+fn flow(opcode: &Opcode, target: Option<u16>) -> (bool, Option<(u16, LabelKind)>) {
+    let mnemonic = opcode.mnemonic;
+    match (mnemonic, target) {
+        ("JMP", Some(target)) => (false, Some((target, LabelKind::Jump))),
+        ("RET" | "PCHL", _) => (false, None),
+        ("RST", _) => {
+            let vector = opcode.registers.parse::<u16>().unwrap_or_default() * 8;
+            (true, Some((vector, LabelKind::Function)))
+        }
+        (_, Some(target)) if opcode.immediate == Immediate::Address => {
+            let kind = match &mnemonic[..1] {
+                "J" => LabelKind::Jump,
+                "C" => LabelKind::Function,
+                // LDA, STA, LHLD and SHLD.
+                _ => LabelKind::Data,
+            };
+            (true, Some((target, kind)))
+        }
+        _ => (true, None),
+    }
+}
+
+/// Kind of a label referenced in different ways, preferring functions over jumps over data.
+const fn strongest(a: LabelKind, b: LabelKind) -> LabelKind {
+    match (a, b) {
+        (LabelKind::Function, _) | (_, LabelKind::Function) => LabelKind::Function,
+        (LabelKind::Jump, _) | (_, LabelKind::Jump) => LabelKind::Jump,
+        _ => LabelKind::Data,
+    }
+}
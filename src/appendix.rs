@@ -0,0 +1,143 @@
+//! Tables printed after the listing, like the symbol table ending classic assembler listings.
+
+use intel_8080_disassembler::analysis::{LabelKind, Line, Listing};
+
+/// Tables to print after the listing.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Sections {
+    /// Names sorted alphabetically, with their address and first reference.
+    pub symbols: bool,
+    /// Every reference to each name.
+    pub xrefs: bool,
+    /// Counts of instructions, bytes and labels.
+    pub stats: bool,
+}
+
+impl Sections {
+    /// Parse a list of section names separated by commas.
+    pub fn from_names(names: &str) -> Option<Self> {
+        let mut sections = Self::default();
+        for name in names.split(',') {
+            match name {
+                "symbols" => sections.symbols = true,
+                "xrefs" => sections.xrefs = true,
+                "stats" => sections.stats = true,
+                _ => return None,
+            }
+        }
+        Some(sections)
+    }
+}
+
+/// Print the selected tables, with their titles between `bold` and `reset`.
+pub fn print(
+    out: &mut dyn std::io::Write,
+    listing: &Listing,
+    sections: Sections,
+    bold: &str,
+    reset: &str,
+) -> std::io::Result<()> {
+    let references = listing.references();
+    let mut names: Vec<(&str, u16, &str)> = listing
+        .labels
+        .iter()
+        .map(|(&address, label)| {
+            let kind = match label.kind {
+                LabelKind::Function => "function",
+                LabelKind::Jump => "jump",
+                LabelKind::Data => "data",
+            };
+            (label.name.as_str(), address, kind)
+        })
+        .chain(
+            listing
+                .symbols
+                .iter()
+                .filter(|(address, _)| !listing.labels.contains_key(address))
+                .map(|(&address, name)| (name.as_str(), address, "symbol")),
+        )
+        .collect();
+    names.sort_unstable();
+
+    if sections.symbols {
+        writeln!(out, "\n{bold}Symbols:{reset}")?;
+        for &(name, address, kind) in &names {
+            let first = references
+                .get(&address)
+                .and_then(|r| r.first())
+                .map_or_else(|| "-".to_owned(), |first| format!("{first:04x}"));
+            writeln!(out, "  {name:<16} {address:04x}  {kind:<8}  {first}")?;
+        }
+    }
+
+    if sections.xrefs {
+        writeln!(out, "\n{bold}Cross references:{reset}")?;
+        for &(name, address, _) in &names {
+            let from: Vec<String> = references
+                .get(&address)
+                .into_iter()
+                .flatten()
+                .map(|a| format!("{a:04x}"))
+                .collect();
+            let line = format!("  {name:<16} {address:04x}  {}", from.join(" "));
+            writeln!(out, "{}", line.trim_end())?;
+        }
+    }
+
+    if sections.stats {
+        print_stats(out, listing, bold, reset)?;
+    }
+    Ok(())
+}
+
+/// Bytes of each kind in a listing.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Statistics {
+    pub instructions: usize,
+    pub code: usize,
+    pub data: usize,
+    pub padding: usize,
+}
+
+impl Statistics {
+    pub fn of(listing: &Listing) -> Self {
+        let mut statistics = Self::default();
+        for line in &listing.lines {
+            match line {
+                Line::Instruction { bytes, .. } => {
+                    statistics.instructions += 1;
+                    statistics.code += bytes.len();
+                }
+                Line::Data { bytes, .. } => statistics.data += bytes.len(),
+                Line::Padding { length, .. } => statistics.padding += length,
+            }
+        }
+        statistics
+    }
+}
+
+fn print_stats(
+    out: &mut dyn std::io::Write,
+    listing: &Listing,
+    bold: &str,
+    reset: &str,
+) -> std::io::Result<()> {
+    let Statistics {
+        instructions,
+        code,
+        data,
+        padding,
+    } = Statistics::of(listing);
+    let count = |kind| listing.labels.values().filter(|l| l.kind == kind).count();
+
+    writeln!(out, "\n{bold}Statistics:{reset}")?;
+    writeln!(out, "  instructions  {instructions}")?;
+    writeln!(out, "  code bytes    {code}")?;
+    writeln!(out, "  data bytes    {data}")?;
+    writeln!(out, "  padding bytes {padding}")?;
+    writeln!(out, "  functions     {}", count(LabelKind::Function))?;
+    writeln!(out, "  jump targets  {}", count(LabelKind::Jump))?;
+    writeln!(out, "  data labels   {}", count(LabelKind::Data))?;
+    writeln!(out, "  problems      {}", listing.problems.len())
+}
@@ -0,0 +1,450 @@
+//! Rendering of a listing as source code for an assembler.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::analysis::{Line, Listing};
+use crate::layout::Layout;
+use crate::opcodes;
+pub use crate::syntax::HexStyle;
+
+/// Way a macro is defined.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MacroStyle {
+    /// "NAME MACRO PARAMETERS", the parameters spelled as where they are used.
+    NameFirst,
+    /// ".macro NAME PARAMETERS", the parameters spelled without their prefix.
+    DirectiveFirst,
+}
+
+/// Syntax accepted by a family of assemblers.
+///
+/// Each field records a quirk in which assemblers differ from each other.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Dialect {
+    pub name: &'static str,
+    /// Statements at the top of the source, before the origin.
+    pub header: &'static [&'static str],
+    /// Statements at the bottom of the source.
+    pub footer: &'static [&'static str],
+    pub hex: HexStyle,
+    pub origin_directive: &'static str,
+    pub equate_directive: &'static str,
+    pub byte_directive: &'static str,
+    pub macro_directive: &'static str,
+    pub macro_style: MacroStyle,
+    pub end_macro_directive: &'static str,
+    /// Written before the parameters of a macro wherever they are used.
+    pub parameter_prefix: &'static str,
+}
+
+/// Intel syntax, accepted by asm80 and most 8080 assemblers.
+pub const INTEL: Dialect = Dialect {
+    name: "intel",
+    header: &[],
+    footer: &["END"],
+    hex: HexStyle::Suffix,
+    origin_directive: "ORG",
+    equate_directive: "EQU",
+    byte_directive: "DB",
+    macro_directive: "MACRO",
+    macro_style: MacroStyle::NameFirst,
+    end_macro_directive: "ENDM",
+    parameter_prefix: "",
+};
+
+/// zasm, which needs to be told to expect 8080 mnemonics.
+pub const ZASM: Dialect = Dialect {
+    name: "zasm",
+    header: &[".8080"],
+    footer: &[],
+    hex: HexStyle::Dollar,
+    origin_directive: "org",
+    equate_directive: "equ",
+    byte_directive: "defb",
+    macro_directive: "macro",
+    macro_style: MacroStyle::NameFirst,
+    end_macro_directive: "endm",
+    parameter_prefix: "&",
+};
+
+/// GNU as and the assemblers following its syntax, with dotted directives.
+pub const GAS: Dialect = Dialect {
+    name: "gas",
+    header: &[],
+    footer: &[],
+    hex: HexStyle::Prefix,
+    origin_directive: ".org",
+    equate_directive: "=",
+    byte_directive: ".byte",
+    macro_directive: ".macro",
+    macro_style: MacroStyle::DirectiveFirst,
+    end_macro_directive: ".endm",
+    parameter_prefix: "\\",
+};
+
+pub const DIALECTS: [Dialect; 3] = [INTEL, ZASM, GAS];
+
+impl Dialect {
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        DIALECTS.into_iter().find(|d| d.name == name)
+    }
+
+    /// Write a number in hexadecimal, using at least `digits` digits.
+    #[must_use]
+    pub fn hex(&self, value: u16, digits: usize) -> String {
+        match self.hex {
+            HexStyle::Suffix => {
+                let hex = format!("{value:0digits$X}H");
+                if hex.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    format!("0{hex}")
+                } else {
+                    hex
+                }
+            }
+            HexStyle::Dollar => format!("${value:0digits$X}"),
+            HexStyle::Prefix => format!("0x{value:0digits$X}"),
+        }
+    }
+}
+
+/// Shortest sequence of instructions rolled into a macro.
+const MIN_MACRO_LENGTH: usize = 3;
+
+/// Longest sequence of instructions rolled into a macro.
+const MAX_MACRO_LENGTH: usize = 16;
+
+/// Sequence of instructions found several times, differing only by their immediates.
+struct Macro {
+    /// Index of the first line of each use.
+    uses: Vec<usize>,
+    length: usize,
+    /// Indices in the sequence of the instructions whose immediate is a parameter.
+    parameters: Vec<usize>,
+}
+
+/// Render a listing as source code that assembles back to the same bytes.
+///
+/// Undocumented opcodes are written as bytes, since assemblers only produce the documented
+/// encoding of each instruction.
+#[must_use]
+pub fn render(listing: &Listing, dialect: &Dialect, layout: &Layout) -> String {
+    render_rolled(listing, dialect, layout, &[])
+}
+
+/// Render a listing like [`render`], rolling sequences of instructions repeated with
+/// different immediates into macros.
+///
+/// Sequences of 3 to 16 instructions are rolled, longest first, when they are found at
+/// least twice with no label inside them. The immediates that differ between
+/// the uses become the parameters of the macro, and the comments of the instructions of a
+/// use are joined on the line using the macro.
+#[must_use]
+pub fn render_with_macros(listing: &Listing, dialect: &Dialect, layout: &Layout) -> String {
+    render_rolled(listing, dialect, layout, &find_macros(listing, dialect))
+}
+
+fn find_macros(listing: &Listing, dialect: &Dialect) -> Vec<Macro> {
+    let lines = &listing.lines;
+    let opcode = |index: usize| match &lines[index] {
+        Line::Instruction { bytes, .. } if !opcodes::is_undocumented(bytes[0]) => Some(bytes[0]),
+        _ => None,
+    };
+    let is_inner = |index: usize| !listing.labels.contains_key(&lines[index].address());
+    let immediate = |index: usize| match &lines[index] {
+        Line::Instruction { bytes, .. } => instruction(bytes, listing, dialect).2,
+        _ => String::new(),
+    };
+
+    let mut claimed = vec![false; lines.len()];
+    let mut macros: Vec<Macro> = Vec::new();
+    for length in (MIN_MACRO_LENGTH..=MAX_MACRO_LENGTH).rev() {
+        // Ordered, so that the same listing always gives the same macros.
+        let mut sequences: BTreeMap<Vec<u8>, Vec<usize>> = BTreeMap::new();
+        for start in 0..lines.len().saturating_sub(length - 1) {
+            let rollable = (start..start + length).all(|i| !claimed[i])
+                && (start + 1..start + length).all(is_inner);
+            let opcodes: Option<Vec<u8>> = (start..start + length).map(opcode).collect();
+            if let (true, Some(opcodes)) = (rollable, opcodes) {
+                sequences.entry(opcodes).or_default().push(start);
+            }
+        }
+
+        // Sequences found more often are rolled first.
+        let mut sequences: Vec<Vec<usize>> = sequences.into_values().collect();
+        sequences.sort_by_key(|starts| std::cmp::Reverse(starts.len()));
+        for starts in sequences {
+            let mut uses: Vec<usize> = Vec::new();
+            for start in starts {
+                let overlaps = uses.last().is_some_and(|&last| start < last + length)
+                    || claimed[start..start + length].contains(&true);
+                if !overlaps {
+                    uses.push(start);
+                }
+            }
+            if uses.len() < 2 {
+                continue;
+            }
+            let parameters: Vec<usize> = (0..length)
+                .filter(|&offset| {
+                    let first = immediate(uses[0] + offset);
+                    uses[1..]
+                        .iter()
+                        .any(|&start| immediate(start + offset) != first)
+                })
+                .collect();
+            // Identical sequences are better left to subroutines.
+            if parameters.is_empty() {
+                continue;
+            }
+            for &start in &uses {
+                claimed[start..start + length].fill(true);
+            }
+            macros.push(Macro {
+                uses,
+                length,
+                parameters,
+            });
+        }
+    }
+    macros.sort_by_key(|m| m.uses[0]);
+    macros
+}
+
+fn render_rolled(
+    listing: &Listing,
+    dialect: &Dialect,
+    layout: &Layout,
+    macros: &[Macro],
+) -> String {
+    let mut source = String::new();
+    // Writing to a string never fails.
+    let mut line = |text: &str| {
+        let _ = writeln!(source, "{text}");
+    };
+    let statement = |mnemonic: &str, operands: &str, comment: Option<&str>| {
+        write_statement(layout, mnemonic, operands, comment)
+    };
+    let data = |bytes: &[u8], comment: Option<&str>| {
+        let values: Vec<String> = bytes
+            .iter()
+            .map(|&b| dialect.hex(u16::from(b), 2))
+            .collect();
+        statement(dialect.byte_directive, &values.join(","), comment)
+    };
+
+    for header in dialect.header {
+        line(&statement(header, "", None));
+    }
+    for (address, name) in &listing.symbols {
+        if !listing.labels.contains_key(address) {
+            let mut text = name.clone();
+            layout.pad(&mut text, layout.mnemonic_column);
+            text.push_str(dialect.equate_directive);
+            layout.pad(&mut text, layout.operand_column);
+            text.push_str(&dialect.hex(*address, 4));
+            line(&text);
+        }
+    }
+    let (definitions, uses) = define_macros(listing, dialect, layout, macros);
+    for definition in &definitions {
+        line(definition);
+    }
+
+    let origin = listing.lines.first().map_or(0, Line::address);
+    line(&statement(
+        dialect.origin_directive,
+        &dialect.hex(origin, 4),
+        None,
+    ));
+
+    let mut skipped = 0;
+    for (index, listing_line) in listing.lines.iter().enumerate() {
+        if skipped > 0 {
+            skipped -= 1;
+            continue;
+        }
+        if let Some(label) = listing.labels.get(&listing_line.address()) {
+            line(&format!("{}:", label.name));
+        }
+
+        let comment = listing
+            .comments
+            .get(&listing_line.address())
+            .map(String::as_str);
+        if let Some((name, arguments, length)) = uses.get(&index) {
+            let comments: Vec<&str> = listing.lines[index..index + length]
+                .iter()
+                .filter_map(|l| listing.comments.get(&l.address()).map(String::as_str))
+                .collect();
+            let comment = (!comments.is_empty()).then(|| comments.join("; "));
+            line(&statement(name, arguments, comment.as_deref()));
+            skipped = length - 1;
+            continue;
+        }
+        match listing_line {
+            Line::Instruction { bytes, .. } => {
+                let (mnemonic, registers, immediate, value) = instruction(bytes, listing, dialect);
+                let operands = operands(registers, &immediate);
+                let comments: Vec<&str> = comment.into_iter().chain(value.as_deref()).collect();
+                let comment = (!comments.is_empty()).then(|| comments.join("; "));
+                let comment = comment.as_deref();
+                if opcodes::is_undocumented(bytes[0]) {
+                    let text = format!("{mnemonic} {operands}");
+                    let comment = comment.map_or_else(|| text.clone(), |c| format!("{text}; {c}"));
+                    line(&data(bytes, Some(&comment)));
+                } else {
+                    line(&statement(mnemonic, &operands, comment));
+                }
+            }
+            Line::Data { bytes, .. } => line(&data(bytes, comment)),
+            Line::Padding { length, byte, .. } => {
+                for chunk in 0..length.div_ceil(16) {
+                    let count = (length - chunk * 16).min(16);
+                    line(&data(&vec![*byte; count], None));
+                }
+            }
+        }
+    }
+
+    for footer in dialect.footer {
+        line(&statement(footer, "", None));
+    }
+    source
+}
+
+/// Name, arguments and length of the macro used at each line.
+type Uses = BTreeMap<usize, (String, String, usize)>;
+
+/// Lines defining the macros, and their uses.
+fn define_macros(
+    listing: &Listing,
+    dialect: &Dialect,
+    layout: &Layout,
+    macros: &[Macro],
+) -> (Vec<String>, Uses) {
+    let parameter = |index: usize| format!("{}arg{}", dialect.parameter_prefix, index + 1);
+    let mut definitions = Vec::new();
+    let mut uses = BTreeMap::new();
+    for (index, definition) in macros.iter().enumerate() {
+        let name = format!("macro{}", index + 1);
+        let count = definition.parameters.len();
+        let text = match dialect.macro_style {
+            MacroStyle::NameFirst => {
+                let parameters: Vec<String> = (0..count).map(parameter).collect();
+                let mut text = name.clone();
+                layout.pad(&mut text, layout.mnemonic_column);
+                text.push_str(dialect.macro_directive);
+                layout.pad(&mut text, layout.operand_column);
+                text.push_str(&parameters.join(","));
+                text
+            }
+            MacroStyle::DirectiveFirst => {
+                let parameters: Vec<String> = (1..=count).map(|n| format!("arg{n}")).collect();
+                let operands = format!("{name} {}", parameters.join(","));
+                write_statement(layout, dialect.macro_directive, operands.trim_end(), None)
+            }
+        };
+        definitions.push(text);
+        let first = definition.uses[0];
+        for offset in 0..definition.length {
+            let Line::Instruction { bytes, .. } = &listing.lines[first + offset] else {
+                continue;
+            };
+            let (mnemonic, registers, immediate, _) = instruction(bytes, listing, dialect);
+            let immediate = definition
+                .parameters
+                .iter()
+                .position(|&p| p == offset)
+                .map_or(immediate, parameter);
+            definitions.push(write_statement(
+                layout,
+                mnemonic,
+                &operands(registers, &immediate),
+                None,
+            ));
+        }
+        definitions.push(write_statement(
+            layout,
+            dialect.end_macro_directive,
+            "",
+            None,
+        ));
+
+        for &start in &definition.uses {
+            let arguments: Vec<String> = definition
+                .parameters
+                .iter()
+                .map(|&offset| match &listing.lines[start + offset] {
+                    Line::Instruction { bytes, .. } => instruction(bytes, listing, dialect).2,
+                    _ => String::new(),
+                })
+                .collect();
+            uses.insert(
+                start,
+                (name.clone(), arguments.join(","), definition.length),
+            );
+        }
+    }
+
+    (definitions, uses)
+}
+
+fn write_statement(
+    layout: &Layout,
+    mnemonic: &str,
+    operands: &str,
+    comment: Option<&str>,
+) -> String {
+    let mut text = String::new();
+    layout.pad(&mut text, layout.mnemonic_column);
+    text.push_str(mnemonic);
+    if !operands.is_empty() {
+        layout.pad(&mut text, layout.operand_column);
+        text.push_str(operands);
+    }
+    if let Some(comment) = comment {
+        layout.pad(&mut text, layout.comment_column);
+        // Writing to a string never fails.
+        let _ = write!(text, "; {comment}");
+    }
+    text
+}
+
+/// Mnemonic, registers and immediate of an instruction, referring to named addresses by
+/// name.
+///
+/// The value of a named address that is not the target of a jump or call is given back to
+/// be kept in a comment.
+fn instruction(
+    bytes: &[u8],
+    listing: &Listing,
+    dialect: &Dialect,
+) -> (&'static str, &'static str, String, Option<String>) {
+    let opcode = opcodes::lookup(bytes[0]);
+    let mut value_comment = None;
+    let immediate = match *bytes {
+        [_, low, high] => {
+            let value = u16::from_le_bytes([low, high]);
+            let name = listing.operand_name(&opcode, value);
+            if name.is_some() && opcode.has_data_immediate() {
+                value_comment = Some(dialect.hex(value, 4));
+            }
+            name.map_or_else(|| dialect.hex(value, 4), str::to_owned)
+        }
+        [_, value] => dialect.hex(u16::from(value), 2),
+        _ => String::new(),
+    };
+
+    (opcode.mnemonic, opcode.registers, immediate, value_comment)
+}
+
+fn operands(registers: &str, immediate: &str) -> String {
+    let comma = if registers.is_empty() || immediate.is_empty() {
+        ""
+    } else {
+        ","
+    };
+    format!("{registers}{comma}{immediate}")
+}
@@ -0,0 +1,139 @@
+//! Summary of a corpus of roms disassembled together.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+use intel_8080_disassembler::analysis::{Line, Listing};
+use intel_8080_disassembler::{hash, opcodes};
+
+use crate::appendix::Statistics;
+
+/// Fewest instructions of a function compared between roms, as tiny helpers look alike in
+/// every rom.
+const MIN_INSTRUCTIONS: usize = 4;
+
+/// What is summarized of a rom.
+pub struct Rom {
+    pub name: String,
+    size: usize,
+    crc32: u32,
+    sha256: String,
+    statistics: Statistics,
+    functions: usize,
+    problems: usize,
+    /// Signature, address and name of each function long enough to be compared.
+    signatures: Vec<(u64, u16, String)>,
+}
+
+impl Rom {
+    pub fn new(name: String, bytes: &[u8], listing: &Listing) -> Self {
+        Self {
+            name,
+            size: bytes.len(),
+            crc32: hash::crc32(bytes),
+            sha256: hash::sha256(bytes),
+            statistics: Statistics::of(listing),
+            functions: listing.functions.len(),
+            problems: listing.problems.len(),
+            signatures: signatures(listing),
+        }
+    }
+}
+
+/// Print the checksums and statistics of each rom, then the routines found in several.
+pub fn print(out: &mut dyn std::io::Write, roms: &[Rom]) -> std::io::Result<()> {
+    let width = roms
+        .iter()
+        .map(|rom| rom.name.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+
+    writeln!(out, "Checksums:")?;
+    writeln!(out, "  {:<width$}  size   crc32     sha256", "name")?;
+    for rom in roms {
+        writeln!(
+            out,
+            "  {:<width$}  {:<6} {:08x}  {}",
+            rom.name, rom.size, rom.crc32, rom.sha256
+        )?;
+    }
+
+    writeln!(out, "\nStatistics:")?;
+    writeln!(
+        out,
+        "  {:<width$}  instr  code   data   padding  functions  problems",
+        "name"
+    )?;
+    for rom in roms {
+        let statistics = rom.statistics;
+        writeln!(
+            out,
+            "  {:<width$}  {:<6} {:<6} {:<6} {:<8} {:<10} {}",
+            rom.name,
+            statistics.instructions,
+            statistics.code,
+            statistics.data,
+            statistics.padding,
+            rom.functions,
+            rom.problems
+        )?;
+    }
+
+    // Where each signature is found, in the order of the roms.
+    let mut shared: BTreeMap<u64, Vec<(&str, u16, &str)>> = BTreeMap::new();
+    for rom in roms {
+        for (signature, address, name) in &rom.signatures {
+            shared
+                .entry(*signature)
+                .or_default()
+                .push((&rom.name, *address, name));
+        }
+    }
+    shared.retain(|_, found| found.iter().any(|&(rom, ..)| rom != found[0].0));
+    writeln!(out, "\nShared routines:")?;
+    if shared.is_empty() {
+        writeln!(out, "  none")?;
+    }
+    for (signature, found) in &shared {
+        writeln!(out, "  {signature:016x}")?;
+        for &(rom, address, name) in found {
+            writeln!(out, "    {rom:<width$}  {address:04x}  {name}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Signature of each function of at least `MIN_INSTRUCTIONS` instructions.
+///
+/// Functions are compared by their opcodes, leaving the immediates out, since addresses
+/// move from one build to another.
+fn signatures(listing: &Listing) -> Vec<(u64, u16, String)> {
+    let mut signatures = Vec::new();
+    for &address in &listing.functions {
+        let Some(range) = listing.function_range(address) else {
+            continue;
+        };
+        let opcodes: Vec<u8> = listing
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                Line::Instruction { address, bytes } if range.contains(&usize::from(*address)) => {
+                    Some(opcodes::canonical_opcode(bytes[0]))
+                }
+                _ => None,
+            })
+            .collect();
+        if opcodes.len() < MIN_INSTRUCTIONS {
+            continue;
+        }
+        let mut hasher = DefaultHasher::new();
+        opcodes.hash(&mut hasher);
+        let name = listing
+            .name(address)
+            .map_or_else(|| format!("0x{address:04x}"), str::to_owned);
+        signatures.push((hasher.finish(), address, name));
+    }
+    signatures
+}
@@ -0,0 +1,270 @@
+//! Control flow graph of a function, with its dominators and post-dominators.
+//!
+//! An instruction dominates another when every path from the entry of the function to the
+//! other goes through it, and post-dominates it when every path from the other out of the
+//! function does. Single-entry regions, loops and the two branches of an if/else are found
+//! from them.
+
+use crate::analysis::{self, Line, Listing};
+use crate::opcodes;
+
+/// Instructions of a function and the paths of execution between them.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Graph {
+    /// Address of each instruction reached from the entry, in increasing order.
+    pub nodes: Vec<u16>,
+    /// Indices in `nodes` of the instructions each one may continue at.
+    pub successors: Vec<Vec<usize>>,
+    /// Indices in `nodes` of the instructions that may continue at each one.
+    pub predecessors: Vec<Vec<usize>>,
+    /// Whether execution may leave the function after each instruction, by returning or
+    /// jumping out of it.
+    pub exits: Vec<bool>,
+    /// Index of the entry in `nodes`.
+    pub entry: usize,
+}
+
+impl Graph {
+    /// Graph of the function starting at `address`, following jumps but not calls, within
+    /// [`Listing::function_range`]. Going to another function leaves the function.
+    #[must_use]
+    pub fn of_function(listing: &Listing, address: u16) -> Option<Self> {
+        let range = listing.function_range(address)?;
+        let instructions: Vec<(u16, &[u8])> = listing
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                Line::Instruction { address, bytes } if range.contains(&usize::from(*address)) => {
+                    Some((*address, bytes.as_slice()))
+                }
+                _ => None,
+            })
+            .collect();
+        let index = |address: u16| instructions.binary_search_by_key(&address, |i| i.0).ok();
+
+        let mut reached = vec![false; instructions.len()];
+        let mut pending = vec![index(address)?];
+        while let Some(current) = pending.pop() {
+            if std::mem::replace(&mut reached[current], true) {
+                continue;
+            }
+            let (at, bytes) = instructions[current];
+            pending.extend(
+                analysis::successors(at, bytes)
+                    .into_iter()
+                    .filter(|&t| t == address || !listing.functions.contains(&t))
+                    .filter_map(index),
+            );
+        }
+
+        let nodes: Vec<u16> = instructions
+            .iter()
+            .zip(&reached)
+            .filter_map(|(&(at, _), &reached)| reached.then_some(at))
+            .collect();
+        let node = |address: u16| nodes.binary_search(&address).ok();
+        let mut successors = Vec::with_capacity(nodes.len());
+        let mut exits = Vec::with_capacity(nodes.len());
+        for &at in &nodes {
+            let bytes = instructions[index(at).unwrap_or_default()].1;
+            let opcode = opcodes::lookup(bytes[0]);
+            let targets = analysis::successors(at, bytes);
+            let inside: Vec<usize> = targets
+                .iter()
+                .filter(|&&t| t == address || !listing.functions.contains(&t))
+                .filter_map(|&t| node(t))
+                .collect();
+            let returns = matches!(opcode.mnemonic, "RET" | "PCHL")
+                || (opcode.mnemonic.starts_with('R') && opcode.is_conditional());
+            exits.push(returns || inside.len() < targets.len());
+            successors.push(inside);
+        }
+        let mut predecessors = vec![Vec::new(); nodes.len()];
+        for (from, targets) in successors.iter().enumerate() {
+            for &to in targets {
+                predecessors[to].push(from);
+            }
+        }
+
+        Some(Self {
+            entry: node(address)?,
+            nodes,
+            successors,
+            predecessors,
+            exits,
+        })
+    }
+
+    /// Index of the instruction at an address.
+    #[must_use]
+    pub fn index(&self, address: u16) -> Option<usize> {
+        self.nodes.binary_search(&address).ok()
+    }
+
+    /// Immediate dominator of each instruction, `None` for the entry.
+    #[must_use]
+    pub fn dominators(&self) -> Vec<Option<usize>> {
+        immediate_dominators(self.entry, &self.successors, &self.predecessors)
+    }
+
+    /// Immediate post-dominator of each instruction.
+    ///
+    /// It is `None` when no instruction of the function lies on every path out of it, such
+    /// as for the instructions leaving it, and for the instructions of loops never left.
+    #[must_use]
+    pub fn post_dominators(&self) -> Vec<Option<usize>> {
+        // Paths out of the function are reversed to start from a single exit node.
+        let exit = self.nodes.len();
+        let mut successors = self.predecessors.clone();
+        let mut predecessors = self.successors.clone();
+        successors.push((0..exit).filter(|&node| self.exits[node]).collect());
+        predecessors.push(Vec::new());
+        for (node, &exits) in self.exits.iter().enumerate() {
+            if exits {
+                predecessors[node].push(exit);
+            }
+        }
+        let mut post_dominators = immediate_dominators(exit, &successors, &predecessors);
+        post_dominators.pop();
+        post_dominators
+            .into_iter()
+            .map(|node| node.filter(|&node| node != exit))
+            .collect()
+    }
+}
+
+/// Whether `a` dominates `b` in a tree of immediate dominators, as given by
+/// [`Graph::dominators`] or [`Graph::post_dominators`]. Every instruction dominates itself.
+#[must_use]
+pub fn dominates(tree: &[Option<usize>], a: usize, b: usize) -> bool {
+    let mut current = Some(b);
+    while let Some(node) = current {
+        if node == a {
+            return true;
+        }
+        current = tree[node];
+    }
+    false
+}
+
+/// Immediate dominators of the nodes reached from `entry`, by the iterative algorithm of
+/// Cooper, Harvey and Kennedy.
+fn immediate_dominators(
+    entry: usize,
+    successors: &[Vec<usize>],
+    predecessors: &[Vec<usize>],
+) -> Vec<Option<usize>> {
+    // Postorder of a depth-first search from the entry, walked backwards below.
+    let mut postorder = Vec::with_capacity(successors.len());
+    let mut visited = vec![false; successors.len()];
+    let mut stack = vec![(entry, 0)];
+    visited[entry] = true;
+    while let Some((node, next)) = stack.pop() {
+        if let Some(&successor) = successors[node].get(next) {
+            stack.push((node, next + 1));
+            if !std::mem::replace(&mut visited[successor], true) {
+                stack.push((successor, 0));
+            }
+        } else {
+            postorder.push(node);
+        }
+    }
+    let mut order = vec![usize::MAX; successors.len()];
+    for (number, &node) in postorder.iter().enumerate() {
+        order[node] = number;
+    }
+
+    let mut dominators = vec![None; successors.len()];
+    dominators[entry] = Some(entry);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &node in postorder.iter().rev().filter(|&&node| node != entry) {
+            let mut processed = predecessors[node]
+                .iter()
+                .copied()
+                .filter(|&p| dominators[p].is_some());
+            let Some(first) = processed.next() else {
+                continue;
+            };
+            let dominator = processed.fold(first, |mut a, mut b| {
+                while a != b {
+                    while order[a] < order[b] {
+                        a = dominators[a].unwrap_or(entry);
+                    }
+                    while order[b] < order[a] {
+                        b = dominators[b].unwrap_or(entry);
+                    }
+                }
+                a
+            });
+            if dominators[node] != Some(dominator) {
+                dominators[node] = Some(dominator);
+                changed = true;
+            }
+        }
+    }
+    dominators[entry] = None;
+    dominators
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Graph of instructions at addresses 0, 1, 2... with their successors, entered at 0.
+    fn graph(successors: &[&[usize]], exits: &[usize]) -> Graph {
+        let mut predecessors = vec![Vec::new(); successors.len()];
+        for (from, targets) in successors.iter().enumerate() {
+            for &to in *targets {
+                predecessors[to].push(from);
+            }
+        }
+        Graph {
+            nodes: (0..).take(successors.len()).collect(),
+            successors: successors.iter().map(|targets| targets.to_vec()).collect(),
+            predecessors,
+            exits: (0..successors.len()).map(|n| exits.contains(&n)).collect(),
+            entry: 0,
+        }
+    }
+
+    #[test]
+    fn both_branches_of_a_diamond_meet_at_its_end() {
+        let graph = graph(&[&[1, 2], &[3], &[3], &[]], &[3]);
+        assert_eq!(graph.dominators(), [None, Some(0), Some(0), Some(0)]);
+        assert_eq!(graph.post_dominators(), [Some(3), Some(3), Some(3), None]);
+        let dominators = graph.dominators();
+        assert!(dominates(&dominators, 0, 3));
+        assert!(!dominates(&dominators, 1, 3));
+    }
+
+    #[test]
+    fn the_head_of_a_loop_dominates_its_body() {
+        let graph = graph(&[&[1], &[2], &[1, 3], &[]], &[3]);
+        assert_eq!(graph.dominators(), [None, Some(0), Some(1), Some(2)]);
+        assert_eq!(graph.post_dominators(), [Some(1), Some(2), Some(3), None]);
+    }
+
+    #[test]
+    fn nothing_post_dominates_a_function_with_two_exits() {
+        // 0 returns at 1 or goes on to return at 3.
+        let graph = graph(&[&[1, 2], &[], &[3], &[]], &[1, 3]);
+        assert_eq!(graph.dominators(), [None, Some(0), Some(0), Some(2)]);
+        assert_eq!(graph.post_dominators(), [None, None, Some(3), None]);
+    }
+
+    #[test]
+    fn a_loop_never_left_has_no_post_dominator() {
+        // 1 loops forever, so every path out of 0 goes through 2.
+        let graph = graph(&[&[1, 2], &[1], &[]], &[2]);
+        assert_eq!(graph.post_dominators(), [Some(2), None, None]);
+    }
+
+    #[test]
+    fn predecessors_not_reached_from_the_entry_are_ignored() {
+        // 4 is never reached, and its jump into the diamond does not change its dominators.
+        let graph = graph(&[&[1, 2], &[3], &[3], &[], &[3]], &[3]);
+        assert_eq!(graph.dominators(), [None, Some(0), Some(0), Some(0), None]);
+    }
+}
@@ -0,0 +1,93 @@
+//! Character sets mapping the bytes of a rom to the characters they display.
+//!
+//! Many games do not store their text in ASCII but as indices of tiles in a character rom,
+//! and national variants of ASCII replace some of its characters.
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::encode::parse_number;
+
+/// Character displayed for each byte, if any.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Charset {
+    characters: [Option<char>; 256],
+}
+
+impl Default for Charset {
+    fn default() -> Self {
+        Self::ascii()
+    }
+}
+
+impl Charset {
+    /// Printable ASCII characters, tabs and line breaks.
+    #[must_use]
+    pub fn ascii() -> Self {
+        let mut characters = [None; 256];
+        for byte in (0x20..=0x7E).chain([b'\t', b'\r', b'\n']) {
+            characters[usize::from(byte)] = Some(char::from(byte));
+        }
+        Self { characters }
+    }
+
+    /// Character displayed for a byte.
+    #[must_use]
+    pub const fn get(&self, byte: u8) -> Option<char> {
+        self.characters[byte as usize]
+    }
+
+    /// Read a character map.
+    ///
+    /// The map starts empty. Each line holds a directive: `ascii` adds the characters of
+    /// [`Charset::ascii`], and `map CODE TEXT` gives the characters of `TEXT` to the bytes
+    /// from `CODE` on, so `map 0x00 0123456789` maps the first ten tiles to digits. `TEXT`
+    /// is everything after the single space following `CODE`, and `\s` in it stands for a
+    /// space. Empty lines and lines starting with `#` are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Fails on lines that are not a valid directive, or that map bytes past 0xFF.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut charset = Self {
+            characters: [None; 256],
+        };
+        for (line_index, line) in text.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            charset
+                .parse_directive(line.trim_start())
+                .with_context(|| format!("parsing line {}", line_index + 1))?;
+        }
+        Ok(charset)
+    }
+
+    fn parse_directive(&mut self, line: &str) -> anyhow::Result<()> {
+        let (directive, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match directive {
+            "ascii" => {
+                let ascii = Self::ascii();
+                for (character, ascii) in self.characters.iter_mut().zip(ascii.characters) {
+                    *character = character.or(ascii);
+                }
+            }
+            "map" => {
+                let Some((code, text)) = rest.split_once(' ') else {
+                    bail!("expected \"map CODE TEXT\"");
+                };
+                let code =
+                    parse_number(code).with_context(|| format!("parsing code \"{code}\""))?;
+                let text = text.replace("\\s", " ");
+                for (offset, character) in text.chars().enumerate() {
+                    let Some(slot) = self.characters.get_mut(usize::from(code) + offset) else {
+                        bail!("\"{text}\" maps bytes past 0xff");
+                    };
+                    *slot = Some(character);
+                }
+            }
+            directive => return Err(anyhow!("unknown directive \"{directive}\"")),
+        }
+        Ok(())
+    }
+}
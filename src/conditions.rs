@@ -0,0 +1,133 @@
+//! Instructions setting the flags tested by conditional jumps, calls and returns.
+
+use crate::analysis::{Line, Listing};
+use crate::opcodes::{self, Flags};
+
+/// Instructions looked at before a conditional instruction to find where its flag was set.
+const WINDOW: usize = 16;
+
+/// Flag tested by a conditional instruction, with its name.
+fn tested_flag(mnemonic: &str) -> Option<(Flags, &'static str)> {
+    Some(match mnemonic.get(1..)? {
+        "NZ" | "Z" => (Flags::Z, "Z"),
+        "NC" | "C" => (Flags::CY, "CY"),
+        "PO" | "PE" => (Flags::P, "P"),
+        "P" | "M" => (Flags::S, "S"),
+        _ => return None,
+    })
+}
+
+/// Comment each conditional instruction with the instruction that set the flag it tests,
+/// like `Z from CPI 0x1B at 0x1a30`.
+///
+/// The flag is looked for in the straight-line code before the instruction, up to a label,
+/// and a call is taken to set every flag. When it was set by something other than a
+/// comparison while an earlier comparison went untested, the comment warns that the
+/// comparison was overwritten, a classic source of bugs.
+pub fn annotate(listing: &mut Listing) {
+    let mut comments = Vec::new();
+    for (index, line) in listing.lines.iter().enumerate() {
+        let Line::Instruction { address, bytes } = line else {
+            continue;
+        };
+        let opcode = opcodes::lookup(bytes[0]);
+        let Some((flag, name)) = tested_flag(opcode.mnemonic).filter(|_| opcode.is_conditional())
+        else {
+            continue;
+        };
+
+        let mut setter = None;
+        for previous in listing.lines[index.saturating_sub(WINDOW)..index]
+            .iter()
+            .rev()
+        {
+            let Line::Instruction {
+                address: previous_address,
+                bytes: previous_bytes,
+            } = previous
+            else {
+                break;
+            };
+            let previous_opcode = opcodes::lookup(previous_bytes[0]);
+            // Code after these is only reached through a label.
+            if matches!(previous_opcode.mnemonic, "JMP" | "RET" | "PCHL")
+                || (setter.is_some() && previous_opcode.is_conditional())
+            {
+                break;
+            }
+            match setter {
+                None if sets(previous_opcode.mnemonic, previous_opcode.flags, flag) => {
+                    setter = Some((*previous_address, previous_bytes));
+                    if is_comparison(previous_opcode.mnemonic) {
+                        break;
+                    }
+                }
+                Some((at, setter_bytes)) if is_comparison(previous_opcode.mnemonic) => {
+                    comments.push((
+                        *address,
+                        format!(
+                            "{name} from {} at 0x{at:04x}, overwriting {} at 0x{:04x}",
+                            describe(setter_bytes, listing),
+                            describe(previous_bytes, listing),
+                            previous_address
+                        ),
+                    ));
+                    setter = None;
+                    break;
+                }
+                _ => {}
+            }
+            if listing.labels.contains_key(previous_address) {
+                break;
+            }
+        }
+        if let Some((at, setter_bytes)) = setter {
+            comments.push((
+                *address,
+                format!(
+                    "{name} from {} at 0x{at:04x}",
+                    describe(setter_bytes, listing)
+                ),
+            ));
+        }
+    }
+    for (address, comment) in comments {
+        listing.add_comment(address, comment);
+    }
+}
+
+/// Whether an instruction sets a flag, taking calls to set every flag.
+fn sets(mnemonic: &str, flags: Flags, flag: Flags) -> bool {
+    flags.contains(flag)
+        || mnemonic == "RST"
+        || mnemonic.starts_with("CALL")
+        || (mnemonic.starts_with('C') && tested_flag(mnemonic).is_some())
+}
+
+fn is_comparison(mnemonic: &str) -> bool {
+    matches!(mnemonic, "CMP" | "CPI")
+}
+
+/// Instruction written compactly for a comment, like `CPI 0x1B`.
+fn describe(bytes: &[u8], listing: &Listing) -> String {
+    let opcode = opcodes::lookup(bytes[0]);
+    let immediate = match *bytes {
+        [_, low, high] => {
+            let value = u16::from_le_bytes([low, high]);
+            listing
+                .operand_name(&opcode, value)
+                .map_or_else(|| format!("0x{value:04X}"), str::to_owned)
+        }
+        [_, value] => format!("0x{value:02X}"),
+        _ => String::new(),
+    };
+    let operands: Vec<&str> = [opcode.registers, immediate.as_str()]
+        .into_iter()
+        .filter(|o| !o.is_empty())
+        .collect();
+    if operands.is_empty() {
+        opcode.mnemonic.to_owned()
+    } else {
+        format!("{} {}", opcode.mnemonic, operands.join(","))
+    }
+}
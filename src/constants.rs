@@ -0,0 +1,170 @@
+//! Values of the registers known before each instruction, from the immediates loaded along
+//! every path of execution reaching it.
+//!
+//! Arguments are often loaded several instructions before the call using them, or on
+//! several paths joining in a shared tail. A value is known where every path brings the
+//! same one.
+
+use std::collections::BTreeMap;
+
+use crate::analysis::{self, Line, Listing};
+use crate::instruction::{Instruction, RegisterPair};
+
+/// Registers, in the order of their values in [`Known`].
+const REGISTERS: [&str; 7] = ["A", "B", "C", "D", "E", "H", "L"];
+
+/// Values of the registers before an instruction, when every path reaching it brings the
+/// same one.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Known([Option<u8>; 7]);
+
+impl Known {
+    /// Value of a register named as in operands, such as `C`.
+    #[must_use]
+    pub fn register(&self, name: &str) -> Option<u8> {
+        let index = REGISTERS.iter().position(|&r| r == name)?;
+        self.0[index]
+    }
+
+    /// Value of a register pair named after its high register, such as `D` for DE.
+    #[must_use]
+    pub fn pair(&self, name: &str) -> Option<u16> {
+        let index = REGISTERS.iter().position(|&r| r == name)?;
+        let low = self.register(REGISTERS.get(index + 1)?)?;
+        Some(u16::from_le_bytes([low, self.register(name)?]))
+    }
+
+    fn set(&mut self, name: &str, value: Option<u8>) {
+        if let Some(index) = REGISTERS.iter().position(|&r| r == name) {
+            self.0[index] = value;
+        }
+    }
+
+    fn set_pair(&mut self, name: &str, value: Option<u16>) {
+        let Some(index) = REGISTERS.iter().position(|&r| r == name) else {
+            return;
+        };
+        let bytes = value.map(u16::to_le_bytes);
+        self.0[index] = bytes.map(|[_, high]| high);
+        self.0[index + 1] = bytes.map(|[low, _]| low);
+    }
+
+    /// Values kept where two paths join.
+    fn join(&self, other: &Self) -> Self {
+        let mut joined = *self;
+        for (value, other) in joined.0.iter_mut().zip(other.0) {
+            if *value != other {
+                *value = None;
+            }
+        }
+        joined
+    }
+
+    /// Values after an instruction.
+    ///
+    /// Calls and restarts forget every value, since the subroutine may change any register.
+    fn after(&self, bytes: &[u8]) -> Self {
+        let mut after = *self;
+        let Some(instruction) = Instruction::decode(bytes) else {
+            return after;
+        };
+        match instruction {
+            Instruction::Mvi(r, value) => after.set(r.name(), Some(value)),
+            Instruction::Mov(r, s) => after.set(r.name(), self.register(s.name())),
+            Instruction::Inr(r) => {
+                after.set(r.name(), self.register(r.name()).map(|v| v.wrapping_add(1)));
+            }
+            Instruction::Dcr(r) => {
+                after.set(r.name(), self.register(r.name()).map(|v| v.wrapping_sub(1)));
+            }
+            Instruction::Lxi(pair, value) => after.set_pair(pair.name(), Some(value)),
+            Instruction::Inx(pair) => after.set_pair(
+                pair.name(),
+                self.pair(pair.name()).map(|v| v.wrapping_add(1)),
+            ),
+            Instruction::Dcx(pair) => after.set_pair(
+                pair.name(),
+                self.pair(pair.name()).map(|v| v.wrapping_sub(1)),
+            ),
+            Instruction::Xchg => {
+                after.set_pair("D", self.pair("H"));
+                after.set_pair("H", self.pair("D"));
+            }
+            Instruction::Dad(_) | Instruction::Lhld(_) | Instruction::Xthl => {
+                after.set_pair("H", None);
+            }
+            Instruction::Rst(_) | Instruction::Call(_) | Instruction::Ccc(..) => {
+                after = Self::default();
+            }
+            // POP PSW and the instructions writing A.
+            Instruction::Pop(RegisterPair::Psw)
+            | Instruction::Add(_)
+            | Instruction::Adc(_)
+            | Instruction::Sub(_)
+            | Instruction::Sbb(_)
+            | Instruction::Ana(_)
+            | Instruction::Xra(_)
+            | Instruction::Ora(_)
+            | Instruction::Adi(_)
+            | Instruction::Aci(_)
+            | Instruction::Sui(_)
+            | Instruction::Sbi(_)
+            | Instruction::Ani(_)
+            | Instruction::Xri(_)
+            | Instruction::Ori(_)
+            | Instruction::Lda(_)
+            | Instruction::Ldax(_)
+            | Instruction::In(_)
+            | Instruction::Rlc
+            | Instruction::Rrc
+            | Instruction::Ral
+            | Instruction::Rar
+            | Instruction::Cma
+            | Instruction::Daa => after.set("A", None),
+            Instruction::Pop(pair) => after.set_pair(pair.name(), None),
+            _ => {}
+        }
+        after
+    }
+}
+
+/// Values of the registers known before each instruction of the listing.
+///
+/// They are propagated along the jumps and the instructions following each other from the
+/// entries of the functions, where nothing is known. Instructions missing from the result
+/// were not reached from any function.
+#[must_use]
+pub fn propagate(listing: &Listing) -> BTreeMap<u16, Known> {
+    let instructions: BTreeMap<u16, &[u8]> = listing
+        .lines
+        .iter()
+        .filter_map(|line| match line {
+            Line::Instruction { address, bytes } => Some((*address, bytes.as_slice())),
+            _ => None,
+        })
+        .collect();
+
+    let mut known: BTreeMap<u16, Known> = listing
+        .functions
+        .iter()
+        .filter(|address| instructions.contains_key(address))
+        .map(|&address| (address, Known::default()))
+        .collect();
+    let mut pending: Vec<u16> = known.keys().copied().collect();
+    while let Some(address) = pending.pop() {
+        let (Some(bytes), Some(before)) = (instructions.get(&address), known.get(&address)) else {
+            continue;
+        };
+        let after = before.after(bytes);
+        for target in analysis::successors(address, bytes) {
+            if !instructions.contains_key(&target) {
+                continue;
+            }
+            let joined = known.get(&target).map_or(after, |k| k.join(&after));
+            if known.insert(target, joined) != Some(joined) {
+                pending.push(target);
+            }
+        }
+    }
+    known
+}
@@ -0,0 +1,140 @@
+//! Knowledge of CP/M, the operating system most 8080 programs were written for.
+
+use crate::analysis::{Line, Listing};
+use crate::{constants, opcodes};
+
+/// Address CP/M loads programs at and starts them from.
+pub const ORIGIN: u16 = 0x0100;
+
+/// Address of the BDOS entry point, called with the function number in C.
+pub const BDOS: u16 = 0x0005;
+
+/// Names of the system addresses of the zero page.
+pub const SYMBOLS: [(u16, &str); 16] = [
+    (0x0000, "wboot"),
+    (0x0003, "iobyte"),
+    (0x0004, "cdisk"),
+    (BDOS, "bdos"),
+    (0x005C, "fcb"),
+    (0x005D, "fcb_name"),
+    (0x0065, "fcb_type"),
+    (0x0068, "fcb_ex"),
+    (0x006B, "fcb_rc"),
+    (0x006C, "fcb2"),
+    (0x006D, "fcb2_name"),
+    (0x0075, "fcb2_type"),
+    (0x007C, "fcb_cr"),
+    (0x007D, "fcb_r0"),
+    (0x0080, "tail"),
+    (0x0081, "tail_text"),
+];
+
+/// Fields of a file control block, with their size.
+pub const FCB_FIELDS: [(&str, usize); 9] = [
+    ("fcb dr: drive", 1),
+    ("fcb f1-f8: file name", 8),
+    ("fcb t1-t3: file type", 3),
+    ("fcb ex: extent", 1),
+    ("fcb s1-s2: reserved", 2),
+    ("fcb rc: record count", 1),
+    ("fcb d0-d15: allocation", 16),
+    ("fcb cr: current record", 1),
+    ("fcb r0-r2: random record", 3),
+];
+
+/// Name of a BDOS function, and whether it takes the address of an FCB in DE.
+#[must_use]
+pub const fn bdos_function(number: u8) -> Option<(&'static str, bool)> {
+    Some(match number {
+        0 => ("system reset", false),
+        1 => ("console input", false),
+        2 => ("console output", false),
+        3 => ("reader input", false),
+        4 => ("punch output", false),
+        5 => ("list output", false),
+        6 => ("direct console I/O", false),
+        7 => ("get I/O byte", false),
+        8 => ("set I/O byte", false),
+        9 => ("print string", false),
+        10 => ("read console buffer", false),
+        11 => ("get console status", false),
+        12 => ("return version number", false),
+        13 => ("reset disk system", false),
+        14 => ("select disk", false),
+        15 => ("open file", true),
+        16 => ("close file", true),
+        17 => ("search for first", true),
+        18 => ("search for next", false),
+        19 => ("delete file", true),
+        20 => ("read sequential", true),
+        21 => ("write sequential", true),
+        22 => ("make file", true),
+        23 => ("rename file", true),
+        24 => ("return login vector", false),
+        25 => ("return current disk", false),
+        26 => ("set DMA address", false),
+        27 => ("get allocation vector", false),
+        28 => ("write protect disk", false),
+        29 => ("get read-only vector", false),
+        30 => ("set file attributes", true),
+        31 => ("get disk parameters", false),
+        32 => ("get or set user code", false),
+        33 => ("read random", true),
+        34 => ("write random", true),
+        35 => ("compute file size", true),
+        36 => ("set random record", true),
+        37 => ("reset drive", false),
+        40 => ("write random with zero fill", true),
+        _ => return None,
+    })
+}
+
+/// Name the system addresses, comment the BDOS calls and lay FCBs over the data they use.
+///
+/// The function number is the value C is known to hold at the call, and the FCB address
+/// the value of DE, as loaded on every path of execution reaching it, even several blocks
+/// before. The lines below the program are dropped.
+pub fn annotate(listing: &mut Listing) {
+    listing.start_at(ORIGIN);
+    for (address, name) in SYMBOLS {
+        listing.symbols.insert(address, name.to_owned());
+    }
+
+    let known = constants::propagate(listing);
+    let mut fcbs = Vec::new();
+    for line in &listing.lines {
+        let Line::Instruction { address, bytes } = line else {
+            continue;
+        };
+        let is_bdos_call = matches!(opcodes::lookup(bytes[0]).mnemonic, "CALL" | "JMP")
+            && bytes[1..] == BDOS.to_le_bytes();
+        if !is_bdos_call {
+            continue;
+        }
+
+        let Some(registers) = known.get(address) else {
+            continue;
+        };
+        let argument = registers.pair("D");
+        let Some(number) = registers.register("C") else {
+            continue;
+        };
+        let comment = bdos_function(number).map_or_else(
+            || format!("BDOS {number}"),
+            |(name, takes_fcb)| {
+                if let (true, Some(fcb)) = (takes_fcb, argument) {
+                    fcbs.push(fcb);
+                }
+                format!("BDOS {number}: {name}")
+            },
+        );
+        listing.comments.insert(*address, comment);
+    }
+
+    for fcb in fcbs {
+        // Sequential access only needs the first 33 bytes.
+        if !listing.overlay(fcb, &FCB_FIELDS) {
+            listing.overlay(fcb, &FCB_FIELDS[..8]);
+        }
+    }
+}
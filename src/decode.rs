@@ -0,0 +1,560 @@
+//! Linear decoding of instructions, and fast writing of them as text without allocating.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+#[cfg(feature = "std")]
+use std::{
+    io::{self, Read as _},
+    num::NonZeroUsize,
+    ops::Range,
+};
+
+use crate::instruction::{Bytes, Instruction};
+use crate::memory::MemoryView;
+use crate::opcodes::{self, Opcode};
+use crate::syntax::Syntax;
+
+/// Instruction read from a rom.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Decoded<'a> {
+    /// Offset in the rom, which is the address when it is loaded at 0.
+    pub address: usize,
+    pub bytes: &'a [u8],
+    pub opcode: Opcode,
+}
+
+impl Decoded<'_> {
+    /// Opcode that encoding the instruction back produces, see [`opcodes::canonical_opcode`].
+    #[must_use]
+    pub const fn canonical_opcode(&self) -> u8 {
+        opcodes::canonical_opcode(self.bytes[0])
+    }
+
+    /// The instruction with its operands, to pattern-match on.
+    ///
+    /// # Panics
+    ///
+    /// Never, decoded instructions are complete.
+    #[must_use]
+    pub fn instruction(&self) -> Instruction {
+        Instruction::decode(self.bytes).expect("decoded instructions are complete")
+    }
+
+    /// Value of the byte or word following the opcode.
+    #[must_use]
+    pub fn immediate(&self) -> Option<u16> {
+        match *self.bytes {
+            [_, low, high] => Some(u16::from_le_bytes([low, high])),
+            [_, value] => Some(u16::from(value)),
+            _ => None,
+        }
+    }
+}
+
+/// Decoded instruction owning its bytes, kept inline, to keep or serialize.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecodedLine {
+    /// Address of the instruction, which is its offset in the rom when it is loaded at 0.
+    pub address: usize,
+    pub bytes: Bytes,
+    pub instruction: Instruction,
+}
+
+impl From<Decoded<'_>> for DecodedLine {
+    fn from(decoded: Decoded<'_>) -> Self {
+        Self {
+            address: decoded.address,
+            bytes: Bytes::new(decoded.bytes),
+            instruction: decoded.instruction(),
+        }
+    }
+}
+
+/// Decode every instruction of a rom loaded at `base` in one call, in a linear sweep.
+///
+/// The lines go into a vector allocated once for the most instructions the rom can hold,
+/// and keep their bytes inline, so nothing is allocated per instruction. The decoding ends
+/// like the iteration of a [`Disassembler`], at an instruction cut off by the end of the
+/// rom, whose bytes are left out, or at the end of the 64 KiB the 8080 can address.
+#[must_use]
+pub fn disassemble_all(rom: &[u8], base: u16) -> Vec<DecodedLine> {
+    let addressable = 0x10000 - usize::from(base);
+    let mut lines = Vec::with_capacity(rom.len().min(addressable));
+    let mut disassembler = Disassembler::new(&rom[..rom.len().min(addressable)]);
+    loop {
+        let start = disassembler.address();
+        let Some((_, instruction)) = disassembler.next() else {
+            return lines;
+        };
+        lines.push(DecodedLine {
+            address: usize::from(base) + start,
+            bytes: Bytes::new(&rom[start..disassembler.address()]),
+            instruction,
+        });
+    }
+}
+
+#[cfg(feature = "std")]
+/// Fewest bytes decoded by a thread of [`disassemble_parallel`], as starting threads costs
+/// more than decoding a small rom.
+const MIN_CHUNK: usize = 0x10000;
+
+#[cfg(feature = "std")]
+/// Decode every instruction of a rom loaded at address 0 on several threads, for dumps of
+/// several megabytes.
+///
+/// The rom is split into a chunk per thread, each decoded from its start as if an
+/// instruction began there. When the last instruction of a chunk spans into the next, the
+/// next is decoded again from the end of that instruction until it meets one of its
+/// instructions, which a linear sweep does within a few bytes, so the lines are those of
+/// decoding the rom in one pass. Like [`decode`], the decoding goes on past 64 KiB, the
+/// addresses being offsets in the rom, and leaves out the bytes of an instruction cut off
+/// by its end.
+#[must_use]
+pub fn disassemble_parallel(rom: &[u8], threads: NonZeroUsize) -> Vec<DecodedLine> {
+    let size = rom.len().div_ceil(threads.get()).max(MIN_CHUNK);
+    let chunks: Vec<(Range<usize>, Vec<DecodedLine>)> = std::thread::scope(|scope| {
+        // Every chunk is started before waiting for any.
+        #[allow(clippy::needless_collect)]
+        let handles: Vec<_> = (0..rom.len())
+            .step_by(size)
+            .map(|start| {
+                let range = start..(start + size).min(rom.len());
+                scope.spawn(move || (range.clone(), sweep(rom, range)))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    });
+
+    let mut lines = Vec::with_capacity(chunks.iter().map(|(_, chunk)| chunk.len()).sum());
+    for (range, chunk) in chunks {
+        let mut address = lines
+            .last()
+            .map_or(0, |line: &DecodedLine| line.address + line.bytes.len());
+        while address < range.end {
+            if let Ok(index) = chunk.binary_search_by_key(&address, |line| line.address) {
+                lines.extend(chunk.into_iter().skip(index));
+                break;
+            }
+            let Some(line) = line_at(rom, address) else {
+                break;
+            };
+            address += line.bytes.len();
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+#[cfg(feature = "std")]
+/// Decode the instructions starting in a range of the rom, the last one possibly ending
+/// past it.
+fn sweep(rom: &[u8], range: Range<usize>) -> Vec<DecodedLine> {
+    let mut lines = Vec::with_capacity(range.len());
+    let mut address = range.start;
+    while address < range.end {
+        let Some(line) = line_at(rom, address) else {
+            break;
+        };
+        address += line.bytes.len();
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(feature = "std")]
+/// Instruction at an offset of the rom, unless cut off by its end.
+fn line_at(rom: &[u8], address: usize) -> Option<DecodedLine> {
+    let bytes = rom.get(address..address + opcodes::lookup(*rom.get(address)?).length())?;
+    Some(DecodedLine {
+        address,
+        bytes: Bytes::new(bytes),
+        instruction: Instruction::decode(bytes)?,
+    })
+}
+
+/// Why bytes could not be decoded into an instruction.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum DecodeError {
+    /// No byte was left to decode.
+    Empty { address: usize },
+    /// Instruction cut off by the end of the rom, with the bytes of it left before the end.
+    Truncated { address: usize, bytes: Vec<u8> },
+}
+
+impl DecodeError {
+    /// Offset in the rom of the instruction that could not be decoded.
+    #[must_use]
+    pub const fn address(&self) -> usize {
+        match self {
+            Self::Empty { address } | Self::Truncated { address, .. } => *address,
+        }
+    }
+
+    /// Bytes of the instruction left before the end, none when the rom ended before it.
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        match self {
+            Self::Empty { .. } => &[],
+            Self::Truncated { bytes, .. } => bytes,
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty { address } => {
+                write!(f, "no instruction at 0x{address:04x}, no byte is left")
+            }
+            Self::Truncated { address, bytes } => write!(
+                f,
+                "instruction at 0x{address:04x} cut off after {} of its {} bytes",
+                bytes.len(),
+                opcodes::lookup(bytes[0]).length()
+            ),
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+/// Iterator over the instructions of a rom loaded at address 0, in a linear sweep.
+///
+/// An instruction cut off by the end of the rom is yielded as an error, ending the
+/// iteration.
+#[derive(Clone, Debug)]
+pub struct Decoder<'a> {
+    rom: &'a [u8],
+    address: usize,
+}
+
+/// Decode the instruction the bytes start with, for emulators and tools that decode one
+/// instruction at a time.
+///
+/// # Errors
+///
+/// Fails with [`DecodeError::Empty`] when there is no byte, and with
+/// [`DecodeError::Truncated`] when the instruction is cut off before the end of its
+/// immediate.
+pub fn decode_instruction(bytes: &[u8]) -> Result<Instruction, DecodeError> {
+    Instruction::decode(bytes).ok_or_else(|| match bytes {
+        [] => DecodeError::Empty { address: 0 },
+        _ => DecodeError::Truncated {
+            address: 0,
+            bytes: bytes.to_vec(),
+        },
+    })
+}
+
+/// Decode the instructions of a rom loaded at address 0, one after the other.
+#[must_use]
+pub const fn decode(rom: &[u8]) -> Decoder<'_> {
+    Decoder { rom, address: 0 }
+}
+
+/// Decode the instructions of a rom loaded at address 0 from an offset on, such as the
+/// bytes following a misaligned instruction.
+#[must_use]
+pub const fn decode_from(rom: &[u8], address: usize) -> Decoder<'_> {
+    Decoder { rom, address }
+}
+
+/// Bytes of the instruction at an address of memory as the CPU sees it, such as the live
+/// memory of an emulator.
+///
+/// An instruction at the top of memory continues at address 0, as the program counter
+/// wraps around. The bytes are kept inline, without allocating.
+#[must_use]
+pub fn decode_at(memory: &(impl MemoryView + ?Sized), address: u16) -> Bytes {
+    let length = opcodes::lookup(memory.read(address)).length();
+    let mut bytes = [0; 3];
+    for (offset, byte) in (0..).zip(&mut bytes[..length]) {
+        *byte = memory.read(address.wrapping_add(offset));
+    }
+    Bytes::new(&bytes[..length])
+}
+
+impl<'a> Iterator for Decoder<'a> {
+    type Item = Result<Decoded<'a>, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.address;
+        let opcode = opcodes::lookup(*self.rom.get(start)?);
+        let end = start + opcode.length();
+        self.address = end.min(self.rom.len());
+
+        match self.rom.get(start..end) {
+            Some(bytes) => Some(Ok(Decoded {
+                address: start,
+                bytes,
+                opcode,
+            })),
+            None => Some(Err(DecodeError::Truncated {
+                address: start,
+                bytes: self.rom[start..].to_vec(),
+            })),
+        }
+    }
+}
+
+/// Iterator over the instructions of a rom loaded at address 0 with their address, as
+/// values to pattern-match on.
+///
+/// The iteration ends at an instruction cut off by the end of the rom, whose bytes are
+/// then given by [`Disassembler::truncated`], or past the 64 KiB the 8080 can address.
+#[derive(Clone, Debug)]
+pub struct Disassembler<'a> {
+    rom: &'a [u8],
+    address: usize,
+}
+
+impl<'a> Disassembler<'a> {
+    #[must_use]
+    pub const fn new(rom: &'a [u8]) -> Self {
+        Self { rom, address: 0 }
+    }
+
+    /// Address of the next instruction.
+    #[must_use]
+    pub const fn address(&self) -> usize {
+        self.address
+    }
+
+    /// Continue at another address, such as past bytes that are not code.
+    pub const fn seek(&mut self, address: usize) {
+        self.address = address;
+    }
+
+    /// Bytes left once the iteration is over, when the last instruction is cut off by the
+    /// end of the rom.
+    #[must_use]
+    pub fn truncated(&self) -> Option<&'a [u8]> {
+        let rest = self.rom.get(self.address..)?;
+        let first = rest.first()?;
+        (rest.len() < opcodes::lookup(*first).length()).then_some(rest)
+    }
+}
+
+impl Iterator for Disassembler<'_> {
+    type Item = (u16, Instruction);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let address = u16::try_from(self.address).ok()?;
+        let instruction = Instruction::decode(self.rom.get(self.address..)?)?;
+        self.address += opcodes::lookup(self.rom[self.address]).length();
+        Some((address, instruction))
+    }
+}
+
+/// Consumer of the instructions of a rom, called by [`visit`] for each in turn.
+///
+/// Closures taking the same arguments are visitors too.
+pub trait Visitor {
+    fn visit(&mut self, address: u16, bytes: &[u8], instruction: &Instruction);
+}
+
+impl<F: FnMut(u16, &[u8], &Instruction)> Visitor for F {
+    fn visit(&mut self, address: u16, bytes: &[u8], instruction: &Instruction) {
+        self(address, bytes, instruction);
+    }
+}
+
+/// Call the visitor for each instruction of a rom loaded at address 0, in a linear sweep,
+/// without collecting them first.
+///
+/// The walk ends like the iteration of a [`Disassembler`], and returns the bytes of an
+/// instruction cut off by the end of the rom, if any.
+pub fn visit<'a>(rom: &'a [u8], visitor: &mut (impl Visitor + ?Sized)) -> Option<&'a [u8]> {
+    let mut disassembler = Disassembler::new(rom);
+    loop {
+        let start = disassembler.address();
+        let Some((address, instruction)) = disassembler.next() else {
+            return disassembler.truncated();
+        };
+        visitor.visit(address, &rom[start..disassembler.address()], &instruction);
+    }
+}
+
+#[cfg(feature = "std")]
+/// Iterator over the instructions read from a pipe, socket or file as they come, like
+/// [`Disassembler`] without loading the whole rom first.
+///
+/// A read error is yielded once, ending the iteration.
+#[derive(Debug)]
+pub struct StreamingDisassembler<R> {
+    reader: io::BufReader<R>,
+    address: usize,
+    /// Bytes of an instruction cut off by the end of the input.
+    truncated: Vec<u8>,
+    done: bool,
+}
+
+#[cfg(feature = "std")]
+impl<R: io::Read> StreamingDisassembler<R> {
+    #[must_use]
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: io::BufReader::new(reader),
+            address: 0,
+            truncated: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Address of the next instruction.
+    #[must_use]
+    pub const fn address(&self) -> usize {
+        self.address
+    }
+
+    /// Bytes left once the iteration is over, when the last instruction is cut off by the
+    /// end of the input.
+    #[must_use]
+    pub fn truncated(&self) -> Option<&[u8]> {
+        (!self.truncated.is_empty()).then_some(self.truncated.as_slice())
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        loop {
+            match self.reader.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: io::Read> Iterator for StreamingDisassembler<R> {
+    type Item = io::Result<(u16, Instruction)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let address = u16::try_from(self.address).ok().filter(|_| !self.done)?;
+        let mut bytes = Vec::with_capacity(3);
+        let mut length = 1;
+        while bytes.len() < length {
+            match self.read_byte() {
+                Ok(Some(byte)) => {
+                    if bytes.is_empty() {
+                        length = opcodes::lookup(byte).length();
+                    }
+                    bytes.push(byte);
+                }
+                Ok(None) => {
+                    self.done = true;
+                    self.truncated = bytes;
+                    return None;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.address += bytes.len();
+        Instruction::decode(&bytes).map(|instruction| Ok((address, instruction)))
+    }
+}
+
+/// Item along with the items around it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Window<T> {
+    /// Items before, oldest first, fewer at the start.
+    pub previous: Vec<T>,
+    pub current: T,
+    /// Items after, fewer at the end.
+    pub next: Vec<T>,
+}
+
+/// Iterator adapter returned by [`Windowed::with_context`].
+#[derive(Clone, Debug)]
+pub struct WithContext<I: Iterator> {
+    inner: I,
+    size: usize,
+    buffer: VecDeque<I::Item>,
+    /// Index of the current item in the buffer.
+    position: usize,
+}
+
+/// Adapter giving each item of an iterator along with its neighbours.
+pub trait Windowed: Iterator + Sized {
+    /// Yield each item with up to `size` items before and after it.
+    ///
+    /// `decode(rom).map_while(Result::ok).with_context(1)` gives each instruction with the previous and next one,
+    /// as needed to spot pairs like `MVI C` followed by `CALL 5`.
+    fn with_context(self, size: usize) -> WithContext<Self> {
+        WithContext {
+            inner: self,
+            size,
+            buffer: VecDeque::with_capacity(2 * size + 1),
+            position: 0,
+        }
+    }
+}
+
+impl<I: Iterator> Windowed for I {}
+
+impl<I: Iterator> Iterator for WithContext<I>
+where
+    I::Item: Clone,
+{
+    type Item = Window<I::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.buffer.len() <= self.position + self.size {
+            let Some(item) = self.inner.next() else {
+                break;
+            };
+            self.buffer.push_back(item);
+        }
+
+        let current = self.buffer.get(self.position)?.clone();
+        let window = Window {
+            previous: self.buffer.range(..self.position).cloned().collect(),
+            current,
+            next: self.buffer.range(self.position + 1..).cloned().collect(),
+        };
+        if self.position == self.size {
+            self.buffer.pop_front();
+        } else {
+            self.position += 1;
+        }
+        Some(window)
+    }
+}
+
+/// Write the listing of a rom loaded at address 0 to `out`, one instruction per line.
+///
+/// Lines look like `0003  cd 08 00  CALL  $0008`. An instruction cut off by the end of the
+/// rom is written with the bytes that are left and marked as truncated. [`Syntax`] writes
+/// them another way.
+///
+/// # Errors
+///
+/// Fails when `out` does.
+pub fn decode_into(rom: &[u8], out: &mut impl Write) -> fmt::Result {
+    Syntax::LISTING.decode_into(rom, out)
+}
+
+/// Write the assembly of a complete instruction, such as `MVI   A,#0x41`.
+///
+/// # Errors
+///
+/// Fails when `out` does.
+pub fn write_instruction(bytes: &[u8], out: &mut impl Write) -> fmt::Result {
+    Syntax::LISTING.write_instruction(bytes, out)
+}
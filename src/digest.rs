@@ -0,0 +1,34 @@
+//! Canonical text of the code of a rom, stable enough to checksum between builds.
+
+use intel_8080_disassembler::analysis::{Line, Listing};
+use intel_8080_disassembler::instruction::Instruction;
+use intel_8080_disassembler::opcodes;
+
+/// Print a line per instruction found by following the flow of execution, with its address,
+/// its bytes and its assembly, leaving out labels, comments, data and padding.
+///
+/// Undocumented opcodes are replaced by the documented ones they behave like, so that the
+/// digest only changes with what the code does or where it is.
+pub fn print(out: &mut dyn std::io::Write, listing: &Listing) -> std::io::Result<()> {
+    for line in &listing.lines {
+        let Line::Instruction { address, bytes } = line else {
+            continue;
+        };
+        let mut bytes = bytes.clone();
+        bytes[0] = opcodes::canonical_opcode(bytes[0]);
+        let mnemonic = opcodes::lookup(bytes[0]).mnemonic;
+        let operands: Vec<String> = Instruction::decode(&bytes)
+            .map(Instruction::operands)
+            .unwrap_or_default()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        write!(out, "{address:04x} ")?;
+        for byte in &bytes {
+            write!(out, "{byte:02x}")?;
+        }
+        let text = format!("{mnemonic} {}", operands.join(","));
+        writeln!(out, " {}", text.trim_end())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,144 @@
+//! Report of where a rom diverges from the source it is thought to be assembled from.
+
+use std::ops::Range;
+
+use intel_8080_disassembler::decode;
+use intel_8080_disassembler::source::Statement;
+
+/// Bytes that must match after a divergence for the source and the rom to be back in step.
+const RESYNC_LENGTH: usize = 4;
+
+/// Bytes looked ahead on each side to get back in step.
+const WINDOW: usize = 256;
+
+/// Part of the source and of the rom that do not match.
+struct Divergence {
+    statements: Range<usize>,
+    rom: Range<usize>,
+}
+
+/// Print each place where `rom` diverges from `statements`, returning how many there are.
+///
+/// Statements are compared in order against the rom from the address of the first one on.
+/// Where one does not match, the report shows the statements and the instructions of the
+/// rom up to the closest point where they match again, so that changed, missing and extra
+/// instructions each show up as a single divergence.
+pub fn print(
+    out: &mut dyn std::io::Write,
+    rom: &[u8],
+    statements: &[Statement],
+) -> std::io::Result<usize> {
+    let divergences = align(rom, statements);
+    for (index, divergence) in divergences.iter().enumerate() {
+        if index > 0 {
+            writeln!(out)?;
+        }
+        let lines = &statements[divergence.statements.clone()];
+        let address = divergence.rom.start;
+        let numbers = match (lines.first(), lines.last()) {
+            (Some(first), Some(last)) if first.line != last.line => {
+                format!("lines {}-{}", first.line, last.line)
+            }
+            (Some(first), _) => format!("line {}", first.line),
+            _ => String::new(),
+        };
+        if lines.is_empty() {
+            writeln!(out, "; 0x{address:04x}: rom has bytes not in the source")?;
+        } else if divergence.rom.is_empty() {
+            writeln!(out, "; 0x{address:04x}: rom lacks {numbers}")?;
+        } else {
+            writeln!(out, "; 0x{address:04x}: rom differs from {numbers}")?;
+        }
+        for statement in lines {
+            writeln!(
+                out,
+                "- {:>4}  {:<9} {}",
+                statement.line,
+                hex(&statement.bytes),
+                statement.text
+            )?;
+        }
+        for instruction in decode::decode_from(&rom[..divergence.rom.end], address) {
+            match instruction {
+                Ok(instruction) => {
+                    let mut text = String::new();
+                    // Writing to a string never fails.
+                    let _ = decode::write_instruction(instruction.bytes, &mut text);
+                    writeln!(
+                        out,
+                        "+ {:04x}  {:<9} {text}",
+                        instruction.address,
+                        hex(instruction.bytes)
+                    )?;
+                }
+                Err(e) => {
+                    writeln!(
+                        out,
+                        "+ {:04x}  {}",
+                        e.address(),
+                        hex(&rom[e.address()..divergence.rom.end])
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(divergences.len())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    bytes.join(" ")
+}
+
+fn align(rom: &[u8], statements: &[Statement]) -> Vec<Divergence> {
+    let assembled: Vec<u8> = statements.iter().flat_map(|s| s.bytes.clone()).collect();
+    let mut starts = Vec::with_capacity(statements.len() + 1);
+    let mut start = 0;
+    for statement in statements {
+        starts.push(start);
+        start += statement.bytes.len();
+    }
+    starts.push(start);
+    // Whether the bytes from a statement on match the rom from an offset on.
+    let matches = |statement: usize, offset: usize| {
+        let start = starts[statement];
+        let length = RESYNC_LENGTH
+            .max(statements[statement].bytes.len())
+            .min(assembled.len() - start);
+        rom.get(offset..offset + length) == Some(&assembled[start..start + length])
+    };
+
+    let mut divergences = Vec::new();
+    let mut statement = 0;
+    let mut offset = statements
+        .first()
+        .map_or(rom.len(), |s| usize::from(s.address).min(rom.len()));
+    while statement < statements.len() || offset < rom.len() {
+        let bytes = &statements.get(statement).map_or(&[][..], |s| &s.bytes);
+        if statement < statements.len() && rom.get(offset..offset + bytes.len()) == Some(bytes) {
+            statement += 1;
+            offset += bytes.len();
+            continue;
+        }
+
+        // Closest statement and offset from which both match again, skipping the fewest
+        // bytes in total.
+        let resync = (1..=2 * WINDOW).find_map(|skipped| {
+            (statement..statements.len())
+                .take_while(|&s| starts[s] - starts[statement] <= skipped)
+                .find_map(|s| {
+                    let skipped_rom = skipped - (starts[s] - starts[statement]);
+                    (skipped_rom <= WINDOW && matches(s, offset + skipped_rom))
+                        .then_some((s, offset + skipped_rom))
+                })
+        });
+        let (next_statement, next_offset) = resync.unwrap_or((statements.len(), rom.len()));
+        divergences.push(Divergence {
+            statements: statement..next_statement,
+            rom: offset..next_offset,
+        });
+        statement = next_statement;
+        offset = next_offset;
+    }
+    divergences
+}
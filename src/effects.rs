@@ -0,0 +1,584 @@
+//! Execution of short sequences of instructions, to check whether two of them compute the
+//! same registers, flags, memory and port output.
+//!
+//! Patching a rom often means replacing a few instructions with others that should do the
+//! same thing, and a forgotten flag effect is easy to miss. The sequences are run from many
+//! random states, and every difference in their results is reported.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::decode;
+use crate::error::DisasmError;
+use crate::opcodes::Flags;
+
+/// Registers, flags and memory of the CPU.
+///
+/// Memory not written yet holds bytes derived from a seed, so that sequences run from the
+/// same state read the same values, and so do the input ports.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct State {
+    pub a: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    /// Flags in the bit layout of the PSW.
+    pub flags: u8,
+    pub sp: u16,
+    pub interrupts_enabled: bool,
+    /// Bytes written to memory.
+    pub written: BTreeMap<u16, u8>,
+    /// Values written to the output ports, in order.
+    pub output: Vec<(u8, u8)>,
+    seed: u64,
+}
+
+impl State {
+    /// State with every register, the flags and memory derived from a seed.
+    #[must_use]
+    pub const fn random(seed: u64) -> Self {
+        let registers = mix(seed).to_le_bytes();
+        let [low, high, ..] = mix(seed ^ 0x5350).to_le_bytes();
+        Self {
+            a: registers[0],
+            b: registers[1],
+            c: registers[2],
+            d: registers[3],
+            e: registers[4],
+            h: registers[5],
+            l: registers[6],
+            flags: registers[7] & Flags::ALL.bits() | 0x02,
+            sp: u16::from_le_bytes([low, high]),
+            interrupts_enabled: seed & 1 == 1,
+            written: BTreeMap::new(),
+            output: Vec::new(),
+            seed,
+        }
+    }
+
+    /// Byte of memory, as written or as initially found there.
+    #[must_use]
+    pub fn read(&self, address: u16) -> u8 {
+        self.written
+            .get(&address)
+            .copied()
+            .unwrap_or_else(|| mix(self.seed ^ (u64::from(address) << 16)).to_le_bytes()[0])
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.written.insert(address, value);
+    }
+
+    fn pair(&self, name: &str) -> u16 {
+        match name {
+            "B" => u16::from_le_bytes([self.c, self.b]),
+            "D" => u16::from_le_bytes([self.e, self.d]),
+            "H" => u16::from_le_bytes([self.l, self.h]),
+            "PSW" => u16::from_le_bytes([self.flags, self.a]),
+            _ => self.sp,
+        }
+    }
+
+    fn set_pair(&mut self, name: &str, value: u16) {
+        let [low, high] = value.to_le_bytes();
+        match name {
+            "B" => (self.b, self.c) = (high, low),
+            "D" => (self.d, self.e) = (high, low),
+            "H" => (self.h, self.l) = (high, low),
+            "PSW" => (self.a, self.flags) = (high, low & Flags::ALL.bits() | 0x02),
+            _ => self.sp = value,
+        }
+    }
+
+    fn register(&self, name: &str) -> u8 {
+        match name {
+            "A" => self.a,
+            "B" => self.b,
+            "C" => self.c,
+            "D" => self.d,
+            "E" => self.e,
+            "H" => self.h,
+            "L" => self.l,
+            _ => self.read(self.pair("H")),
+        }
+    }
+
+    fn set_register(&mut self, name: &str, value: u8) {
+        match name {
+            "A" => self.a = value,
+            "B" => self.b = value,
+            "C" => self.c = value,
+            "D" => self.d = value,
+            "E" => self.e = value,
+            "H" => self.h = value,
+            "L" => self.l = value,
+            _ => self.write(self.pair("H"), value),
+        }
+    }
+
+    const fn flag(&self, flag: Flags) -> bool {
+        self.flags & flag.bits() != 0
+    }
+
+    const fn set_flag(&mut self, flag: Flags, value: bool) {
+        if value {
+            self.flags |= flag.bits();
+        } else {
+            self.flags &= !flag.bits();
+        }
+    }
+
+    /// Set the sign, zero and parity flags after a result.
+    const fn set_szp(&mut self, result: u8) {
+        self.set_flag(Flags::S, result & 0x80 != 0);
+        self.set_flag(Flags::Z, result == 0);
+        self.set_flag(Flags::P, result.count_ones().is_multiple_of(2));
+    }
+
+    fn push(&mut self, value: u16) {
+        let [low, high] = value.to_le_bytes();
+        self.sp = self.sp.wrapping_sub(1);
+        self.write(self.sp, high);
+        self.sp = self.sp.wrapping_sub(1);
+        self.write(self.sp, low);
+    }
+
+    fn pop(&mut self) -> u16 {
+        let low = self.read(self.sp);
+        let high = self.read(self.sp.wrapping_add(1));
+        self.sp = self.sp.wrapping_add(2);
+        u16::from_le_bytes([low, high])
+    }
+
+    /// Add to A with a carry in, setting every flag.
+    fn add(&mut self, value: u8, carry: bool) -> u8 {
+        let carry = u8::from(carry);
+        let result = self.a.wrapping_add(value).wrapping_add(carry);
+        self.set_szp(result);
+        self.set_flag(
+            Flags::CY,
+            u16::from(self.a) + u16::from(value) + u16::from(carry) > 0xFF,
+        );
+        self.set_flag(Flags::AC, (self.a & 0xF) + (value & 0xF) + carry > 0xF);
+        result
+    }
+
+    /// Subtract from A with a borrow in, adding the complement the way the 8080 does.
+    fn subtract(&mut self, value: u8, borrow: bool) -> u8 {
+        let result = self.add(!value, !borrow);
+        let carry = self.flag(Flags::CY);
+        self.set_flag(Flags::CY, !carry);
+        result
+    }
+
+    /// Run a sequence of instructions.
+    ///
+    /// # Errors
+    ///
+    /// Fails on instructions that change the flow of execution or halt, since only
+    /// straight-line code is run, and on an instruction cut off by the end of the code.
+    pub fn execute(&mut self, code: &[u8]) -> Result<(), DisasmError> {
+        for instruction in decode::decode(code) {
+            let instruction = instruction?;
+            self.step(instruction.bytes)?;
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn step(&mut self, bytes: &[u8]) -> Result<(), DisasmError> {
+        let opcode = crate::opcodes::lookup(bytes[0]);
+        let mut registers = opcode.registers.split(',');
+        let r = registers.next().unwrap_or_default();
+        let s = registers.next().unwrap_or_default();
+        let byte = bytes.get(1).copied().unwrap_or_default();
+        let word = u16::from_le_bytes([byte, bytes.get(2).copied().unwrap_or_default()]);
+
+        match opcode.mnemonic {
+            "NOP" => {}
+            "MOV" => self.set_register(r, self.register(s)),
+            "MVI" => self.set_register(r, byte),
+            "LXI" => self.set_pair(r, word),
+            "STAX" => self.write(self.pair(r), self.a),
+            "LDAX" => self.a = self.read(self.pair(r)),
+            "STA" => self.write(word, self.a),
+            "LDA" => self.a = self.read(word),
+            "SHLD" => {
+                self.write(word, self.l);
+                self.write(word.wrapping_add(1), self.h);
+            }
+            "LHLD" => {
+                self.l = self.read(word);
+                self.h = self.read(word.wrapping_add(1));
+            }
+            "INX" => self.set_pair(r, self.pair(r).wrapping_add(1)),
+            "DCX" => self.set_pair(r, self.pair(r).wrapping_sub(1)),
+            "INR" => {
+                let result = self.register(r).wrapping_add(1);
+                self.set_szp(result);
+                self.set_flag(Flags::AC, result.trailing_zeros() >= 4);
+                self.set_register(r, result);
+            }
+            "DCR" => {
+                let result = self.register(r).wrapping_sub(1);
+                self.set_szp(result);
+                self.set_flag(Flags::AC, result & 0xF != 0xF);
+                self.set_register(r, result);
+            }
+            "DAD" => {
+                let (result, carry) = self.pair("H").overflowing_add(self.pair(r));
+                self.set_pair("H", result);
+                self.set_flag(Flags::CY, carry);
+            }
+            "RLC" => {
+                self.a = self.a.rotate_left(1);
+                self.set_flag(Flags::CY, self.a & 0x01 != 0);
+            }
+            "RRC" => {
+                self.a = self.a.rotate_right(1);
+                self.set_flag(Flags::CY, self.a & 0x80 != 0);
+            }
+            "RAL" => {
+                let carry = self.a & 0x80 != 0;
+                self.a = self.a << 1 | u8::from(self.flag(Flags::CY));
+                self.set_flag(Flags::CY, carry);
+            }
+            "RAR" => {
+                let carry = self.a & 0x01 != 0;
+                self.a = self.a >> 1 | u8::from(self.flag(Flags::CY)) << 7;
+                self.set_flag(Flags::CY, carry);
+            }
+            "DAA" => {
+                let mut correction = 0;
+                let mut carry = self.flag(Flags::CY);
+                if self.a & 0xF > 9 || self.flag(Flags::AC) {
+                    correction |= 0x06;
+                }
+                if self.a > 0x99 || carry {
+                    correction |= 0x60;
+                    carry = true;
+                }
+                self.a = self.add(correction, false);
+                self.set_flag(Flags::CY, carry);
+            }
+            "CMA" => self.a = !self.a,
+            "STC" => self.set_flag(Flags::CY, true),
+            "CMC" => self.set_flag(Flags::CY, !self.flag(Flags::CY)),
+            "ADD" | "ADC" | "SUB" | "SBB" | "ANA" | "XRA" | "ORA" | "CMP" => {
+                self.arithmetic(opcode.mnemonic, self.register(r));
+            }
+            "ADI" | "ACI" | "SUI" | "SBI" | "ANI" | "XRI" | "ORI" | "CPI" => {
+                let operation = match opcode.mnemonic {
+                    "ADI" => "ADD",
+                    "ACI" => "ADC",
+                    "SUI" => "SUB",
+                    "SBI" => "SBB",
+                    "ANI" => "ANA",
+                    "XRI" => "XRA",
+                    "ORI" => "ORA",
+                    _ => "CMP",
+                };
+                self.arithmetic(operation, byte);
+            }
+            "PUSH" => self.push(self.pair(r)),
+            "POP" => {
+                let value = self.pop();
+                self.set_pair(r, value);
+            }
+            "XTHL" => {
+                let value = self.pop();
+                self.push(self.pair("H"));
+                self.set_pair("H", value);
+            }
+            "XCHG" => {
+                let hl = self.pair("H");
+                self.set_pair("H", self.pair("D"));
+                self.set_pair("D", hl);
+            }
+            "SPHL" => self.sp = self.pair("H"),
+            "OUT" => self.output.push((byte, self.a)),
+            "IN" => self.a = mix(self.seed ^ (u64::from(byte) << 32)).to_le_bytes()[1],
+            "EI" => self.interrupts_enabled = true,
+            "DI" => self.interrupts_enabled = false,
+            mnemonic => {
+                return Err(DisasmError::Unsupported(format!(
+                    "{mnemonic} leaves straight-line code"
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn arithmetic(&mut self, operation: &str, value: u8) {
+        let carry = self.flag(Flags::CY);
+        match operation {
+            "ADD" => self.a = self.add(value, false),
+            "ADC" => self.a = self.add(value, carry),
+            "SUB" => self.a = self.subtract(value, false),
+            "SBB" => self.a = self.subtract(value, carry),
+            "CMP" => {
+                self.subtract(value, false);
+            }
+            _ => {
+                let (result, half_carry) = match operation {
+                    // The 8080 sets AC from bit 3 of the operands of AND.
+                    "ANA" => (self.a & value, (self.a | value) & 0x08 != 0),
+                    "XRA" => (self.a ^ value, false),
+                    _ => (self.a | value, false),
+                };
+                self.a = result;
+                self.set_szp(result);
+                self.set_flag(Flags::AC, half_carry);
+                self.set_flag(Flags::CY, false);
+            }
+        }
+    }
+}
+
+/// Scramble a seed, mixing its bits like a hash.
+const fn mix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Part of the state whose value differs after the two sequences.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Difference {
+    /// Name of the register or flag, like "A" or "flag CY", or "memory".
+    pub what: String,
+    /// Number of states it differs after.
+    pub count: usize,
+    /// First state it differs after, with the values left by each sequence.
+    pub example: (State, String, String),
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (state, original, replacement) = &self.example;
+        write!(
+            f,
+            "{} differs in {} states, e.g. from A={:02x} BC={:04x} DE={:04x} HL={:04x} \
+             SP={:04x} flags={:02x}: {replacement} instead of {original}",
+            self.what,
+            self.count,
+            state.a,
+            state.pair("B"),
+            state.pair("D"),
+            state.pair("H"),
+            state.sp,
+            state.flags
+        )
+    }
+}
+
+/// Run two sequences of instructions from `trials` random states and list what they leave
+/// different, in the order registers, flags, interrupts, port output, then memory.
+///
+/// # Errors
+///
+/// Fails when either sequence cannot be run, see [`State::execute`].
+pub fn compare(
+    original: &[u8],
+    replacement: &[u8],
+    trials: u64,
+) -> Result<Vec<Difference>, DisasmError> {
+    let mut differences: Vec<Difference> = Vec::new();
+    let mut order = Vec::new();
+    for trial in 0..trials {
+        let initial = State::random(trial);
+        let mut left = initial.clone();
+        left.execute(original)?;
+        let mut right = initial.clone();
+        right.execute(replacement)?;
+
+        let observed = observe(&left, &right);
+        if order.is_empty() {
+            order = observed.iter().map(|(what, ..)| what.clone()).collect();
+        }
+        for (what, original, replacement) in observed {
+            if original == replacement {
+                continue;
+            }
+            match differences.iter_mut().find(|d| d.what == what) {
+                Some(difference) => difference.count += 1,
+                None => differences.push(Difference {
+                    what,
+                    count: 1,
+                    example: (initial.clone(), original, replacement),
+                }),
+            }
+        }
+    }
+    differences.sort_by_key(|d| order.iter().position(|what| *what == d.what));
+    Ok(differences)
+}
+
+/// Names of the parts of two states, with their value in each.
+fn observe(left: &State, right: &State) -> Vec<(String, String, String)> {
+    let mut observed = Vec::new();
+    for register in ["A", "B", "C", "D", "E", "H", "L"] {
+        observed.push((
+            register.to_owned(),
+            format!("0x{:02x}", left.register(register)),
+            format!("0x{:02x}", right.register(register)),
+        ));
+    }
+    observed.push((
+        "SP".to_owned(),
+        format!("0x{:04x}", left.sp),
+        format!("0x{:04x}", right.sp),
+    ));
+    let flags = [
+        (Flags::S, "S"),
+        (Flags::Z, "Z"),
+        (Flags::AC, "AC"),
+        (Flags::P, "P"),
+        (Flags::CY, "CY"),
+    ];
+    for (flag, name) in flags {
+        observed.push((
+            format!("flag {name}"),
+            u8::from(left.flag(flag)).to_string(),
+            u8::from(right.flag(flag)).to_string(),
+        ));
+    }
+    observed.push((
+        "interrupts".to_owned(),
+        left.interrupts_enabled.to_string(),
+        right.interrupts_enabled.to_string(),
+    ));
+    observed.push((
+        "port output".to_owned(),
+        format!("{:02x?}", left.output),
+        format!("{:02x?}", right.output),
+    ));
+    // Addresses vary with the registers, so memory is compared as a whole.
+    let addresses: BTreeSet<u16> = left
+        .written
+        .keys()
+        .chain(right.written.keys())
+        .copied()
+        .filter(|&address| left.read(address) != right.read(address))
+        .collect();
+    let memory = |state: &State| {
+        let bytes: Vec<String> = addresses
+            .iter()
+            .map(|&address| format!("(0x{address:04x})=0x{:02x}", state.read(address)))
+            .collect();
+        bytes.join(" ")
+    };
+    observed.push(("memory".to_owned(), memory(left), memory(right)));
+    observed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Names of what differs after two sequences.
+    fn differences(original: &[u8], replacement: &[u8]) -> Vec<String> {
+        compare(original, replacement, 100)
+            .unwrap()
+            .into_iter()
+            .map(|difference| difference.what)
+            .collect()
+    }
+
+    /// State left by running code from A and the flags given, with CY set or not.
+    fn run(a: u8, carry: bool, code: &[u8]) -> State {
+        let mut state = State::random(0);
+        state.a = a;
+        state.flags = 0x02 | u8::from(carry);
+        state.execute(code).unwrap();
+        state
+    }
+
+    #[test]
+    fn clearing_a_with_xra_also_sets_the_flags() {
+        // XRA A against MVI A, 0.
+        assert_eq!(
+            differences(&[0xAF], &[0x3E, 0x00]),
+            ["flag S", "flag Z", "flag AC", "flag P", "flag CY"]
+        );
+    }
+
+    #[test]
+    fn ana_sets_the_auxiliary_carry_and_ora_clears_it() {
+        // ANA A against ORA A.
+        assert_eq!(differences(&[0xA7], &[0xB7]), ["flag AC"]);
+        assert!(run(0x08, true, &[0xA7]).flag(Flags::AC));
+        assert!(!run(0x08, true, &[0xB7]).flag(Flags::AC));
+        assert!(!run(0x08, true, &[0xB7]).flag(Flags::CY));
+    }
+
+    #[test]
+    fn sequences_differ_only_in_what_they_change() {
+        // XCHG twice against nothing, and PUSH B; POP B, which leaves BC written below the
+        // stack.
+        assert_eq!(differences(&[0xEB, 0xEB], &[]), Vec::<String>::new());
+        assert_eq!(differences(&[0xC5, 0xC1], &[]), ["memory"]);
+    }
+
+    #[test]
+    fn daa_adjusts_a_to_binary_coded_decimal() {
+        // A, CY before ADD B with B, then A, CY after DAA.
+        let vectors = [
+            (0x15, 0x27, 0x42, false),
+            (0x38, 0x29, 0x67, false),
+            (0x99, 0x01, 0x00, true),
+            (0x50, 0x50, 0x00, true),
+            (0x99, 0x99, 0x98, true),
+        ];
+        for (a, b, adjusted, carry) in vectors {
+            let mut state = State::random(0);
+            state.a = a;
+            state.b = b;
+            state.execute(&[0x80, 0x27]).unwrap();
+            assert_eq!(
+                (state.a, state.flag(Flags::CY)),
+                (adjusted, carry),
+                "{a:02x} + {b:02x}"
+            );
+            assert_eq!(state.flag(Flags::Z), adjusted == 0);
+        }
+        // The example of the 8080 manual.
+        let state = run(0x9B, false, &[0x27]);
+        assert_eq!(state.a, 0x01);
+        assert!(state.flag(Flags::CY));
+        assert!(state.flag(Flags::AC));
+    }
+
+    #[test]
+    fn subtraction_sets_the_carry_on_a_borrow() {
+        // SUI 1 from 0, then from 1.
+        let state = run(0x00, false, &[0xD6, 0x01]);
+        assert_eq!(state.a, 0xFF);
+        assert!(state.flag(Flags::CY));
+        assert!(state.flag(Flags::S));
+        let state = run(0x01, true, &[0xD6, 0x01]);
+        assert_eq!(state.a, 0x00);
+        assert!(!state.flag(Flags::CY));
+        assert!(state.flag(Flags::Z));
+        // CMP B leaves A as it is.
+        let mut state = run(0x10, false, &[]);
+        state.b = 0x20;
+        state.execute(&[0xB8]).unwrap();
+        assert_eq!(state.a, 0x10);
+        assert!(state.flag(Flags::CY));
+    }
+
+    #[test]
+    fn straight_line_code_is_left_only_by_failing() {
+        let mut state = State::random(0);
+        assert!(matches!(
+            state.execute(&[0xC9]),
+            Err(DisasmError::Unsupported(_))
+        ));
+        assert!(compare(&[0x00], &[0x76], 1).is_err());
+    }
+}
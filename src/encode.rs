@@ -0,0 +1,194 @@
+//! Assembly of single instructions back into machine code.
+
+#[cfg(doc)]
+use crate::decode;
+use crate::error::DisasmError;
+use crate::instruction::{Condition, Instruction, Register, RegisterPair};
+use crate::opcodes;
+
+/// Encode an instruction written like "LXI H, 0x2400" into its bytes.
+///
+/// Immediates may be written in decimal, as hexadecimal with a "0x" or "$" prefix or an
+/// "h" suffix, or as a character in single quotes. A "#" before the immediate is ignored.
+/// Undocumented opcodes are never produced.
+///
+/// Encoding is the inverse of decoding: for the bytes of any instruction, encoding the text
+/// written by [`decode::write_instruction`] gives back the same bytes, except that the opcode
+/// is replaced by its [`opcodes::canonical_opcode`]. Decoding the bytes of an encoded
+/// instruction gives back the same instruction.
+///
+/// # Errors
+///
+/// Fails when the mnemonic is unknown, the operands match none of its encodings, or the
+/// immediate is invalid or out of range.
+pub fn encode(text: &str) -> Result<Vec<u8>, DisasmError> {
+    let text = text.trim();
+    let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let mnemonic = mnemonic.to_ascii_uppercase();
+    if opcodes::summary(&mnemonic).is_none() {
+        return Err(DisasmError::Encode(format!(
+            "unknown mnemonic \"{mnemonic}\""
+        )));
+    }
+    let operands: Vec<&str> = if operands.trim().is_empty() {
+        Vec::new()
+    } else {
+        operands.split(',').map(str::trim).collect()
+    };
+
+    let invalid = || {
+        DisasmError::Encode(format!(
+            "invalid operands for {mnemonic}: \"{}\"",
+            operands.join(",")
+        ))
+    };
+    let instruction = parse_instruction(&mnemonic, &operands)?.ok_or_else(invalid)?;
+    Ok(instruction.encode().ok_or_else(invalid)?.to_vec())
+}
+
+/// Instruction written with a known mnemonic and its operands, `None` when the operands
+/// are not those of the mnemonic.
+///
+/// The registers are checked before the immediate is parsed, so that an immediate given in
+/// place of a register makes the operands invalid rather than the number.
+fn parse_instruction(
+    mnemonic: &str,
+    operands: &[&str],
+) -> Result<Option<Instruction>, DisasmError> {
+    use Instruction as I;
+
+    let register = |operand: &str| Register::from_name(&operand.to_ascii_uppercase());
+    let pair = |operand: &str| RegisterPair::from_name(&operand.to_ascii_uppercase());
+    let byte = |operand: &str| {
+        let value = parse_number(operand)?;
+        u8::try_from(value).map_err(|_| {
+            DisasmError::Encode(format!("immediate {value:#x} does not fit in a byte"))
+        })
+    };
+
+    Ok(Some(match (mnemonic, operands) {
+        ("NOP", []) => I::Nop,
+        ("RLC", []) => I::Rlc,
+        ("RRC", []) => I::Rrc,
+        ("RAL", []) => I::Ral,
+        ("RAR", []) => I::Rar,
+        ("DAA", []) => I::Daa,
+        ("CMA", []) => I::Cma,
+        ("STC", []) => I::Stc,
+        ("CMC", []) => I::Cmc,
+        ("HLT", []) => I::Hlt,
+        ("RET", []) => I::Ret,
+        ("XTHL", []) => I::Xthl,
+        ("SPHL", []) => I::Sphl,
+        ("PCHL", []) => I::Pchl,
+        ("XCHG", []) => I::Xchg,
+        ("DI", []) => I::Di,
+        ("EI", []) => I::Ei,
+        ("LXI", [p, value]) => match pair(p) {
+            Some(p) => I::Lxi(p, parse_number(value)?),
+            None => return Ok(None),
+        },
+        ("MVI", [r, value]) => match register(r) {
+            Some(r) => I::Mvi(r, byte(value)?),
+            None => return Ok(None),
+        },
+        ("MOV", [to, from]) => match (register(to), register(from)) {
+            (Some(to), Some(from)) => I::Mov(to, from),
+            _ => return Ok(None),
+        },
+        ("STAX" | "INX" | "DAD" | "LDAX" | "DCX" | "PUSH" | "POP", [p]) => {
+            let Some(p) = pair(p) else {
+                return Ok(None);
+            };
+            match mnemonic {
+                "STAX" => I::Stax(p),
+                "INX" => I::Inx(p),
+                "DAD" => I::Dad(p),
+                "LDAX" => I::Ldax(p),
+                "DCX" => I::Dcx(p),
+                "PUSH" => I::Push(p),
+                _ => I::Pop(p),
+            }
+        }
+        ("INR" | "DCR" | "ADD" | "ADC" | "SUB" | "SBB" | "ANA" | "XRA" | "ORA" | "CMP", [r]) => {
+            let Some(r) = register(r) else {
+                return Ok(None);
+            };
+            match mnemonic {
+                "INR" => I::Inr(r),
+                "DCR" => I::Dcr(r),
+                "ADD" => I::Add(r),
+                "ADC" => I::Adc(r),
+                "SUB" => I::Sub(r),
+                "SBB" => I::Sbb(r),
+                "ANA" => I::Ana(r),
+                "XRA" => I::Xra(r),
+                "ORA" => I::Ora(r),
+                _ => I::Cmp(r),
+            }
+        }
+        ("ADI", [value]) => I::Adi(byte(value)?),
+        ("ACI", [value]) => I::Aci(byte(value)?),
+        ("SUI", [value]) => I::Sui(byte(value)?),
+        ("SBI", [value]) => I::Sbi(byte(value)?),
+        ("ANI", [value]) => I::Ani(byte(value)?),
+        ("XRI", [value]) => I::Xri(byte(value)?),
+        ("ORI", [value]) => I::Ori(byte(value)?),
+        ("CPI", [value]) => I::Cpi(byte(value)?),
+        ("OUT", [port]) => I::Out(byte(port)?),
+        ("IN", [port]) => I::In(byte(port)?),
+        ("SHLD", [address]) => I::Shld(parse_number(address)?),
+        ("LHLD", [address]) => I::Lhld(parse_number(address)?),
+        ("STA", [address]) => I::Sta(parse_number(address)?),
+        ("LDA", [address]) => I::Lda(parse_number(address)?),
+        ("JMP", [address]) => I::Jmp(parse_number(address)?),
+        ("CALL", [address]) => I::Call(parse_number(address)?),
+        ("RST", [vector]) => match vector.as_bytes() {
+            [digit @ b'0'..=b'7'] => I::Rst(digit - b'0'),
+            _ => return Ok(None),
+        },
+        _ => {
+            // Conditional jumps, calls and returns, named after their condition.
+            let condition = mnemonic.get(1..).and_then(Condition::from_name);
+            match (mnemonic.as_bytes().first(), condition, operands) {
+                (Some(b'J'), Some(c), [address]) => I::Jcc(c, parse_number(address)?),
+                (Some(b'C'), Some(c), [address]) => I::Ccc(c, parse_number(address)?),
+                (Some(b'R'), Some(c), []) => I::Rcc(c),
+                _ => return Ok(None),
+            }
+        }
+    }))
+}
+
+/// Parse a number written in decimal, hexadecimal or as a quoted character.
+///
+/// # Errors
+///
+/// Fails when the text is not a number or does not fit in 16 bits.
+pub fn parse_number(text: &str) -> Result<u16, DisasmError> {
+    let invalid = |message: String| DisasmError::Number {
+        text: text.to_owned(),
+        message,
+    };
+    let text = text.strip_prefix('#').unwrap_or(text);
+
+    let (digits, radix) = if let Some(hex) = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .or_else(|| text.strip_prefix('$'))
+    {
+        (hex, 16)
+    } else if let Some(hex) = text.strip_suffix(['h', 'H']) {
+        (hex, 16)
+    } else if let Some(character) = text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+        let mut chars = character.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii() => Ok(c as u16),
+            _ => Err(invalid("expected a single ASCII character".to_owned())),
+        };
+    } else {
+        (text, 10)
+    };
+
+    u16::from_str_radix(digits, radix).map_err(|e| invalid(e.to_string()))
+}
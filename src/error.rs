@@ -0,0 +1,70 @@
+//! Errors of the library, to match on instead of reading their message.
+
+use std::fmt;
+use std::io;
+
+use crate::decode::DecodeError;
+
+/// Failure to read, parse, patch, encode or run code.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DisasmError {
+    /// Instruction cut off by the end of the code.
+    Decode(DecodeError),
+    /// File that could not be read or decompressed, with what was being read.
+    Io { context: String, source: io::Error },
+    /// Zip archive that could not be read, with what was being read.
+    Zip {
+        context: String,
+        source: zip::result::ZipError,
+    },
+    /// Zip archive holding no file, or several files when none was selected.
+    Member(String),
+    /// Line of hexadecimal text or Intel HEX that could not be parsed.
+    Parse { line: usize, message: String },
+    /// Intel HEX record whose bytes do not add up to its checksum.
+    Checksum { line: usize },
+    /// Image, file or change that does not fit where it is placed.
+    Placement(String),
+    /// Patch that could not be applied or written.
+    Patch(String),
+    /// Instruction whose mnemonic, operands or immediate are invalid.
+    Encode(String),
+    /// Number written wrongly or too large.
+    Number { text: String, message: String },
+    /// Instruction that cannot be run, such as a jump in straight-line code.
+    Unsupported(String),
+}
+
+impl fmt::Display for DisasmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode(error) => error.fmt(f),
+            Self::Io { context, .. } | Self::Zip { context, .. } => f.write_str(context),
+            Self::Member(message)
+            | Self::Placement(message)
+            | Self::Patch(message)
+            | Self::Encode(message)
+            | Self::Unsupported(message) => f.write_str(message),
+            Self::Parse { line, message } => write!(f, "{message} on line {line}"),
+            Self::Checksum { line } => write!(f, "wrong checksum of the record on line {line}"),
+            Self::Number { text, message } => write!(f, "invalid number \"{text}\": {message}"),
+        }
+    }
+}
+
+impl std::error::Error for DisasmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Zip { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for DisasmError {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
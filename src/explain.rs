@@ -0,0 +1,261 @@
+//! Plain English description of what each instruction does.
+
+use crate::opcodes::{self, Cycles, Flags, Opcode};
+
+/// Describe the effect of an instruction, e.g. "B <- (HL)" for "MOV B,M".
+///
+/// The immediate data is described by its value when known, or by the placeholder
+/// of the reference tables otherwise.
+pub fn describe(opcode: &Opcode, immediate: Option<u16>) -> String {
+    let imm = match immediate {
+        Some(value) if opcode.length() == 2 => format!("0x{value:02x}"),
+        Some(value) => format!("0x{value:04x}"),
+        None => opcode.immediate.placeholder().to_owned(),
+    };
+    let mut registers = opcode.registers.split(',').map(operand);
+    let r = registers.next().unwrap_or_default();
+    let s = registers.next().unwrap_or_default();
+    let rp = pair(opcode.registers);
+
+    match opcode.mnemonic {
+        "MOV" => format!("{r} <- {s}"),
+        "MVI" => format!("{r} <- {imm}"),
+        "LXI" => format!("{rp} <- {imm}"),
+        "STAX" => format!("({rp}) <- A"),
+        "LDAX" => format!("A <- ({rp})"),
+        "INX" => format!("{rp} <- {rp} + 1"),
+        "DCX" => format!("{rp} <- {rp} - 1"),
+        "INR" => format!("{r} <- {r} + 1"),
+        "DCR" => format!("{r} <- {r} - 1"),
+        "DAD" => format!("HL <- HL + {rp}"),
+        "SHLD" => format!("({imm}) <- HL"),
+        "LHLD" => format!("HL <- ({imm})"),
+        "STA" => format!("({imm}) <- A"),
+        "LDA" => format!("A <- ({imm})"),
+        "CMA" => "A <- ~A".to_owned(),
+        "STC" => "CY <- 1".to_owned(),
+        "CMC" => "CY <- ~CY".to_owned(),
+        "ADD" => format!("A <- A + {r}"),
+        "ADC" => format!("A <- A + {r} + CY"),
+        "SUB" => format!("A <- A - {r}"),
+        "SBB" => format!("A <- A - {r} - CY"),
+        "ANA" => format!("A <- A & {r}"),
+        "XRA" => format!("A <- A ^ {r}"),
+        "ORA" => format!("A <- A | {r}"),
+        "CMP" => format!("compare A with {r}"),
+        "ADI" => format!("A <- A + {imm}"),
+        "ACI" => format!("A <- A + {imm} + CY"),
+        "SUI" => format!("A <- A - {imm}"),
+        "SBI" => format!("A <- A - {imm} - CY"),
+        "ANI" => format!("A <- A & {imm}"),
+        "XRI" => format!("A <- A ^ {imm}"),
+        "ORI" => format!("A <- A | {imm}"),
+        "CPI" => format!("compare A with {imm}"),
+        "JMP" => format!("jump to {imm}"),
+        "CALL" => format!("call {imm}"),
+        "RET" => "return".to_owned(),
+        "PCHL" => "jump to HL".to_owned(),
+        "POP" => format!("{rp} <- pop"),
+        "PUSH" => format!("push {rp}"),
+        "OUT" => format!("port {imm} <- A"),
+        "IN" => format!("A <- port {imm}"),
+        "XTHL" => "HL <-> (SP)".to_owned(),
+        "XCHG" => "HL <-> DE".to_owned(),
+        "SPHL" => "SP <- HL".to_owned(),
+        "RST" => {
+            let vector = opcode.registers.parse::<u16>().unwrap_or_default() * 8;
+            format!("call 0x{vector:04x}")
+        }
+        mnemonic => match (condition(mnemonic), &mnemonic[..1]) {
+            (Some(condition), "J") => format!("jump to {imm} if {condition}"),
+            (Some(condition), "C") => format!("call {imm} if {condition}"),
+            (Some(condition), _) => format!("return if {condition}"),
+            // Rotations, DAA, NOP, HLT, EI and DI.
+            (None, _) => crate::opcodes::summary(mnemonic)
+                .unwrap_or_default()
+                .to_owned(),
+        },
+    }
+}
+
+/// Instruction starting at an offset of a file, as shown by editors hovering over its bytes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Explained<'a> {
+    /// Bytes of the instruction.
+    pub bytes: &'a [u8],
+    pub opcode: Opcode,
+    /// Value of the byte or word following the opcode.
+    pub immediate: Option<u16>,
+}
+
+impl Explained<'_> {
+    #[must_use]
+    pub const fn length(&self) -> usize {
+        self.bytes.len()
+    }
+
+    #[must_use]
+    pub const fn cycles(&self) -> Cycles {
+        self.opcode.cycles
+    }
+
+    /// What the mnemonic does, like "move register or memory to register".
+    #[must_use]
+    pub fn summary(&self) -> &'static str {
+        opcodes::summary(self.opcode.mnemonic).unwrap_or_default()
+    }
+
+    /// Effect of the instruction with its immediate, like "A <- 0x41", the only part
+    /// allocating.
+    #[must_use]
+    pub fn description(&self) -> String {
+        describe(&self.opcode, self.immediate)
+    }
+}
+
+/// Decode the instruction starting at `offset` of `bytes`, such as a hex file open in an
+/// editor, without allocating.
+///
+/// Returns `None` when the offset is past the end of the bytes or the instruction is cut
+/// off by it.
+#[must_use]
+pub fn explain_bytes(bytes: &[u8], offset: usize) -> Option<Explained<'_>> {
+    let opcode = opcodes::lookup(*bytes.get(offset)?);
+    let bytes = bytes.get(offset..offset + opcode.length())?;
+    let immediate = match *bytes {
+        [_, low, high] => Some(u16::from_le_bytes([low, high])),
+        [_, value] => Some(u16::from(value)),
+        _ => None,
+    };
+    Some(Explained {
+        bytes,
+        opcode,
+        immediate,
+    })
+}
+
+/// Name the memory operand "M" after the location it refers to.
+fn operand(register: &str) -> &str {
+    if register == "M" {
+        "(HL)"
+    } else {
+        register
+    }
+}
+
+/// Full name of the register pair encoded as "B", "D", "H", "SP" or "PSW".
+fn pair(registers: &str) -> &str {
+    match registers {
+        "B" => "BC",
+        "D" => "DE",
+        "H" => "HL",
+        other => other,
+    }
+}
+
+/// Condition tested by a conditional jump, call or return.
+fn condition(mnemonic: &str) -> Option<&'static str> {
+    if matches!(mnemonic, "CMA" | "CMC" | "CMP" | "CPI" | "CALL") {
+        return None;
+    }
+
+    Some(match mnemonic.get(1..)? {
+        "NZ" => "not zero",
+        "Z" => "zero",
+        "NC" => "no carry",
+        "C" => "carry",
+        "PO" => "parity odd",
+        "PE" => "parity even",
+        "P" => "plus",
+        "M" => "minus",
+        _ => return None,
+    })
+}
+
+/// Explain how each flag modified by an instruction is determined.
+///
+/// Returns the name of every affected flag paired with its rule, in PSW order.
+#[must_use]
+pub fn flag_rules(opcode: &Opcode) -> Vec<(&'static str, &'static str)> {
+    let group = match opcode.mnemonic {
+        "ADD" | "ADC" | "ADI" | "ACI" => Group::Addition,
+        "SUB" | "SBB" | "SUI" | "SBI" => Group::Subtraction,
+        "CMP" | "CPI" => Group::Comparison,
+        "ANA" | "ANI" => Group::And,
+        "XRA" | "ORA" | "XRI" | "ORI" => Group::Logical,
+        "INR" => Group::Increment,
+        "DCR" => Group::Decrement,
+        "DAA" => Group::DecimalAdjust,
+        "DAD" => Group::DoubleAdd,
+        "RLC" | "RAL" => Group::RotateLeft,
+        "RRC" | "RAR" => Group::RotateRight,
+        "STC" => Group::SetCarry,
+        "CMC" => Group::ComplementCarry,
+        _ => return Vec::new(),
+    };
+
+    Flags::NAMED
+        .into_iter()
+        .filter(|(flag, _)| opcode.flags.contains(*flag))
+        .map(|(flag, _)| {
+            let name = match flag {
+                Flags::S => "S",
+                Flags::Z => "Z",
+                Flags::AC => "AC",
+                Flags::P => "P",
+                _ => "CY",
+            };
+            (name, group.rule(flag))
+        })
+        .collect()
+}
+
+/// Instructions sharing the same rules for setting the flags.
+#[derive(Clone, Copy)]
+enum Group {
+    Addition,
+    Subtraction,
+    Comparison,
+    And,
+    Logical,
+    Increment,
+    Decrement,
+    DecimalAdjust,
+    DoubleAdd,
+    RotateLeft,
+    RotateRight,
+    SetCarry,
+    ComplementCarry,
+}
+
+impl Group {
+    const fn rule(self, flag: Flags) -> &'static str {
+        match (self, flag) {
+            (Self::Comparison, Flags::S) => "set to bit 7 of A minus the operand",
+            (Self::Comparison, Flags::Z) => "set if A equals the operand",
+            (Self::Comparison, Flags::P) => "set if A minus the operand has even parity",
+            (_, Flags::S) => "set to bit 7 of the result",
+            (_, Flags::Z) => "set if the result is zero",
+            (_, Flags::P) => "set if the result has an even number of one bits",
+            (Self::Addition | Self::Increment, Flags::AC) => "set on carry out of bit 3",
+            (Self::Subtraction | Self::Comparison, Flags::AC) => {
+                "set on carry out of bit 3 when adding the two's complement of the operand"
+            }
+            (Self::Decrement, Flags::AC) => "set unless the low nibble of the result is 0xF",
+            (Self::And, Flags::AC) => "set to the OR of bit 3 of both operands",
+            (Self::DecimalAdjust, Flags::AC) => "set on carry out of bit 3 when adjusting",
+            (Self::Addition, _) => "set on carry out of bit 7",
+            (Self::Subtraction, _) => "set on borrow, when the operand is larger than A",
+            (Self::Comparison, _) => "set if A is lower than the operand (unsigned)",
+            (Self::And | Self::Logical, _) => "cleared",
+            (Self::DecimalAdjust, _) => "set on carry out of bit 7 when adjusting, else kept",
+            (Self::DoubleAdd, _) => "set on carry out of bit 15",
+            (Self::RotateLeft, _) => "set to the bit shifted out of bit 7",
+            (Self::RotateRight, _) => "set to the bit shifted out of bit 0",
+            (Self::SetCarry, _) => "set",
+            (Self::ComplementCarry, _) => "complemented",
+            // INR and DCR do not modify the carry.
+            (Self::Increment | Self::Decrement, _) => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! C interface to the decoder, for emulators and tools written in C or C++.
+//!
+//! `include/i8080.h` declares the same types and functions for C.
+
+use core::ffi::c_char;
+
+use crate::opcodes::{self, Immediate};
+
+/// No immediate follows the opcode.
+pub const I8080_IMMEDIATE_NONE: u8 = 0;
+/// A byte of data follows the opcode.
+pub const I8080_IMMEDIATE_BYTE: u8 = 1;
+/// A word of data follows the opcode.
+pub const I8080_IMMEDIATE_WORD: u8 = 2;
+/// The address of a jump, call or access to memory follows the opcode.
+pub const I8080_IMMEDIATE_ADDRESS: u8 = 3;
+/// An I/O port follows the opcode.
+pub const I8080_IMMEDIATE_PORT: u8 = 4;
+
+/// Instruction decoded by [`i8080_decode`], laid out like `i8080_insn` in C.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(non_camel_case_types)]
+pub struct i8080_insn {
+    /// Bytes of the instruction, followed by zeros.
+    pub bytes: [u8; 3],
+    /// Number of bytes of the instruction, from 1 to 3.
+    pub length: u8,
+    /// Mnemonic, like `"MVI"`, ending with a NUL.
+    pub mnemonic: [c_char; 5],
+    /// Registers or vector encoded in the opcode, like `"A"` or `"B,C"`, ending with a NUL.
+    pub registers: [c_char; 4],
+    /// One of the `I8080_IMMEDIATE_` constants.
+    pub immediate_kind: u8,
+    /// Value of the byte or word following the opcode, 0 without one.
+    pub immediate: u16,
+    /// Clock states taken when the condition of a conditional call or return is met, and
+    /// by any other instruction.
+    pub cycles_taken: u8,
+    /// Clock states taken when the condition of a conditional call or return is not met.
+    pub cycles_not_taken: u8,
+    /// Flags modified, in their PSW bits.
+    pub flags: u8,
+    /// Whether the opcode is undocumented, executed as another instruction.
+    pub undocumented: u8,
+}
+
+/// Decode the instruction at the start of `length` bytes into `insn`.
+///
+/// Returns the number of bytes of the instruction, or 0 when the bytes end before the
+/// instruction does, leaving `insn` untouched.
+///
+/// # Safety
+///
+/// `bytes` must point to `length` readable bytes, and `insn` to an `i8080_insn` that can be
+/// written. Null pointers decode nothing.
+#[no_mangle]
+pub unsafe extern "C" fn i8080_decode(
+    bytes: *const u8,
+    length: usize,
+    insn: *mut i8080_insn,
+) -> usize {
+    if bytes.is_null() || insn.is_null() || length == 0 {
+        return 0;
+    }
+    // SAFETY: the caller guarantees that `bytes` points to `length` readable bytes.
+    let bytes = unsafe { core::slice::from_raw_parts(bytes, length) };
+    let opcode = opcodes::lookup(bytes[0]);
+    let Some(bytes) = bytes.get(..opcode.length()) else {
+        return 0;
+    };
+
+    let mut decoded = i8080_insn {
+        bytes: [0; 3],
+        length: 0,
+        mnemonic: [0; 5],
+        registers: [0; 4],
+        immediate_kind: match opcode.immediate {
+            Immediate::Empty => I8080_IMMEDIATE_NONE,
+            Immediate::Byte => I8080_IMMEDIATE_BYTE,
+            Immediate::Word => I8080_IMMEDIATE_WORD,
+            Immediate::Address => I8080_IMMEDIATE_ADDRESS,
+            Immediate::Port => I8080_IMMEDIATE_PORT,
+        },
+        immediate: match *bytes {
+            [_, low, high] => u16::from_le_bytes([low, high]),
+            [_, value] => u16::from(value),
+            _ => 0,
+        },
+        cycles_taken: opcode.cycles.taken,
+        cycles_not_taken: opcode.cycles.not_taken,
+        flags: opcode.flags.bits(),
+        undocumented: u8::from(opcodes::is_undocumented(bytes[0])),
+    };
+    decoded.bytes[..bytes.len()].copy_from_slice(bytes);
+    // Instructions are 3 bytes long at most.
+    decoded.length = u8::try_from(bytes.len()).unwrap_or(3);
+    copy_str(opcode.mnemonic, &mut decoded.mnemonic);
+    copy_str(opcode.registers, &mut decoded.registers);
+    // SAFETY: the caller guarantees that `insn` points to an `i8080_insn` that can be written.
+    unsafe { insn.write(decoded) };
+    bytes.len()
+}
+
+/// Copy the ASCII text of the opcode table to a C string, keeping room for its NUL.
+fn copy_str(text: &str, out: &mut [c_char]) {
+    let room = out.len() - 1;
+    for (c, byte) in out.iter_mut().zip(text.bytes().take(room)) {
+        *c = c_char::from_ne_bytes([byte]);
+    }
+}
@@ -0,0 +1,70 @@
+//! Instructions forbidden in the code, for cores or environments missing some of them.
+
+use intel_8080_disassembler::analysis::{Line, Listing};
+use intel_8080_disassembler::opcodes;
+
+/// Instructions forbidden by `--forbid`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Rule {
+    /// Every instruction with this mnemonic.
+    Mnemonic(String),
+    /// Opcodes that the 8080 executes as another instruction.
+    Undocumented,
+    /// IN and OUT.
+    Io,
+    /// EI and DI.
+    Interrupts,
+}
+
+impl Rule {
+    /// Parse a list of mnemonics and sets of instructions separated by commas.
+    pub fn from_names(names: &str) -> Option<Vec<Self>> {
+        names
+            .split(',')
+            .map(|name| match name {
+                "undocumented" => Some(Self::Undocumented),
+                "io" => Some(Self::Io),
+                "interrupts" => Some(Self::Interrupts),
+                _ => {
+                    let mnemonic = name.to_ascii_uppercase();
+                    opcodes::summary(&mnemonic).map(|_| Self::Mnemonic(mnemonic))
+                }
+            })
+            .collect()
+    }
+
+    fn forbids(&self, opcode: u8) -> bool {
+        let mnemonic = opcodes::lookup(opcode).mnemonic;
+        match self {
+            Self::Mnemonic(forbidden) => mnemonic == forbidden,
+            Self::Undocumented => opcodes::is_undocumented(opcode),
+            Self::Io => matches!(mnemonic, "IN" | "OUT"),
+            Self::Interrupts => matches!(mnemonic, "EI" | "DI"),
+        }
+    }
+}
+
+/// Describe each instruction of the code forbidden by a rule, like
+/// `0x0012: OUT is forbidden by io`, in the order of their addresses.
+pub fn violations(listing: &Listing, rules: &[Rule]) -> Vec<String> {
+    let mut violations = Vec::new();
+    for line in &listing.lines {
+        let Line::Instruction { address, bytes } = line else {
+            continue;
+        };
+        let Some(rule) = rules.iter().find(|rule| rule.forbids(bytes[0])) else {
+            continue;
+        };
+        let mnemonic = opcodes::lookup(bytes[0]).mnemonic;
+        let description = match rule {
+            Rule::Mnemonic(_) => format!("{mnemonic} is forbidden"),
+            Rule::Undocumented => {
+                format!("undocumented opcode 0x{:02x} is forbidden", bytes[0])
+            }
+            Rule::Io => format!("{mnemonic} is forbidden by io"),
+            Rule::Interrupts => format!("{mnemonic} is forbidden by interrupts"),
+        };
+        violations.push(format!("0x{address:04x}: {description}"));
+    }
+    violations
+}
@@ -0,0 +1,22 @@
+//! Checksums identifying the disassembled image.
+
+use std::fmt::Write;
+
+use sha2::{Digest, Sha256};
+
+#[must_use]
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32fast::hash(data)
+}
+
+/// SHA-256 digest in lowercase hexadecimal.
+#[must_use]
+pub fn sha256(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            // Writing to a string never fails.
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
@@ -0,0 +1,166 @@
+//! Picture of a rom in SVG: how often each opcode is used, and what each address holds.
+
+use intel_8080_disassembler::analysis::{LabelKind, Line, Listing};
+use intel_8080_disassembler::opcodes;
+
+/// Side of a cell of the opcode grid, in pixels.
+const CELL: usize = 28;
+/// Room left of and above the grid for the low and high digits of the opcodes.
+const MARGIN: usize = 24;
+/// Width of the address strip, in pixels.
+const STRIP_WIDTH: usize = MARGIN + 16 * CELL;
+/// Height of the address strip, in pixels.
+const STRIP_HEIGHT: usize = 32;
+
+/// What the bytes of an address range were found to be.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Class {
+    /// Instructions reached by the flow of execution.
+    Code,
+    /// Bytes not executed but read or written by the code.
+    Data,
+    /// Block of filler bytes.
+    Padding,
+    /// Bytes neither executed nor referred to.
+    Unknown,
+}
+
+impl Class {
+    const ALL: [Self; 4] = [Self::Code, Self::Data, Self::Padding, Self::Unknown];
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Code => "code",
+            Self::Data => "data",
+            Self::Padding => "padding",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    const fn color(self) -> &'static str {
+        match self {
+            Self::Code => "#d9534f",
+            Self::Data => "#5b7fde",
+            Self::Padding => "#bbbbbb",
+            Self::Unknown => "#eeeeee",
+        }
+    }
+}
+
+/// Print an SVG picture of the listing: a 16×16 grid of the opcodes, high digit down and
+/// low digit across, shaded by how many instructions use each, above a strip of the
+/// addresses of the image colored by what they hold.
+pub fn print(out: &mut dyn std::io::Write, listing: &Listing) -> std::io::Result<()> {
+    let mut counts = [0_usize; 256];
+    for line in &listing.lines {
+        if let Line::Instruction { bytes, .. } = line {
+            counts[usize::from(bytes[0])] += 1;
+        }
+    }
+    let most = counts.iter().copied().max().unwrap_or(0).max(1);
+
+    let grid_bottom = MARGIN + 16 * CELL;
+    let strip_top = grid_bottom + MARGIN;
+    let legend_top = strip_top + STRIP_HEIGHT + 8;
+    let height = legend_top + 16;
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{STRIP_WIDTH}" height="{height}" font-family="monospace" font-size="10">"#
+    )?;
+    for digit in 0..16 {
+        let offset = MARGIN + digit * CELL + CELL / 2;
+        writeln!(
+            out,
+            r#"<text x="{offset}" y="{}" text-anchor="middle">x{digit:X}</text>"#,
+            MARGIN - 8
+        )?;
+        writeln!(
+            out,
+            r#"<text x="{}" y="{}" text-anchor="middle">{digit:X}x</text>"#,
+            MARGIN / 2,
+            offset + 4
+        )?;
+    }
+    for (opcode, &count) in counts.iter().enumerate() {
+        let (x, y) = (MARGIN + opcode % 16 * CELL, MARGIN + opcode / 16 * CELL);
+        // From white for unused opcodes to red for the most used.
+        let lightness = 100 - 55 * count / most;
+        let mnemonic = opcodes::lookup(u8::try_from(opcode).unwrap_or(0)).mnemonic;
+        writeln!(
+            out,
+            r##"<rect x="{x}" y="{y}" width="{CELL}" height="{CELL}" fill="hsl(0,70%,{lightness}%)" stroke="#ffffff"><title>{opcode:02X} {mnemonic}: {count}</title></rect>"##
+        )?;
+    }
+
+    print_strip(out, listing, strip_top)?;
+    let mut x = 0;
+    for class in Class::ALL {
+        writeln!(
+            out,
+            r#"<rect x="{x}" y="{legend_top}" width="10" height="10" fill="{}"/><text x="{}" y="{}">{}</text>"#,
+            class.color(),
+            x + 14,
+            legend_top + 9,
+            class.name()
+        )?;
+        x += 80;
+    }
+    writeln!(out, "</svg>")
+}
+
+/// Print the strip of the addresses of the listing, from its first line to its end.
+fn print_strip(out: &mut dyn std::io::Write, listing: &Listing, top: usize) -> std::io::Result<()> {
+    let (Some(first), Some(last)) = (listing.lines.first(), listing.lines.last()) else {
+        return Ok(());
+    };
+    let start = usize::from(first.address());
+    let end = usize::from(last.address()) + last.length();
+    let scale = |address: usize| (address - start) * STRIP_WIDTH / (end - start);
+
+    // Neighbouring lines of the same class are drawn as one rectangle.
+    let mut runs: Vec<(usize, usize, Class)> = Vec::new();
+    for line in &listing.lines {
+        let address = usize::from(line.address());
+        let class = classify(line, listing);
+        match runs.last_mut() {
+            Some((_, run_end, run_class)) if *run_class == class && *run_end == address => {
+                *run_end += line.length();
+            }
+            _ => runs.push((address, address + line.length(), class)),
+        }
+    }
+    for (run_start, run_end, class) in runs {
+        writeln!(
+            out,
+            r#"<rect x="{}" y="{top}" width="{}" height="{STRIP_HEIGHT}" fill="{}"><title>{run_start:04x}-{:04x} {}</title></rect>"#,
+            scale(run_start),
+            // Runs narrower than a pixel are still shown.
+            (scale(run_end) - scale(run_start)).max(1),
+            class.color(),
+            run_end - 1,
+            class.name()
+        )?;
+    }
+    Ok(())
+}
+
+/// Class of the bytes of a line, data when the code refers to any of them.
+fn classify(line: &Line, listing: &Listing) -> Class {
+    match line {
+        Line::Instruction { .. } => Class::Code,
+        Line::Padding { .. } => Class::Padding,
+        Line::Data { address, bytes } => {
+            let end = usize::from(*address) + bytes.len();
+            let referred = listing
+                .labels
+                .range(*address..)
+                .take_while(|(&label, _)| usize::from(label) < end)
+                .any(|(_, label)| label.kind == LabelKind::Data);
+            if referred {
+                Class::Data
+            } else {
+                Class::Unknown
+            }
+        }
+    }
+}
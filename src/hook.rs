@@ -0,0 +1,66 @@
+//! Names of labels and symbols transformed by a user provided command.
+
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Context};
+use intel_8080_disassembler::analysis::Listing;
+
+/// Rename the labels and symbols of the listing with a shell command.
+///
+/// The command reads a line per name, made of its address in hexadecimal and the name, like
+/// `0a3c sub_0a3c`, and writes the new names in the same order, one per line.
+pub fn rename(listing: &mut Listing, command: &str) -> anyhow::Result<()> {
+    let labels = listing
+        .labels
+        .iter()
+        .map(|(&a, label)| (a, label.name.as_str()));
+    let symbols = listing.symbols.iter().map(|(&a, name)| (a, name.as_str()));
+    let mut input = String::new();
+    for (address, name) in labels.chain(symbols) {
+        // Writing to a string never fails.
+        let _ = writeln!(input, "{address:04x} {name}");
+    }
+
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("running \"{command}\""))?;
+    let (Some(mut stdin), Some(mut stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        bail!("connecting to \"{command}\"");
+    };
+    // Written from another thread, as the command may write names before reading them all.
+    let output = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || stdin.write_all(input.as_bytes()));
+        let mut output = String::new();
+        let read = stdout.read_to_string(&mut output);
+        // A command closing its input early is told apart by its names.
+        let _ = writer.join();
+        read.map(|_| output)
+    })
+    .with_context(|| format!("reading the names written by \"{command}\""))?;
+    let status = child
+        .wait()
+        .with_context(|| format!("running \"{command}\""))?;
+    if !status.success() {
+        bail!("\"{command}\" failed with {status}");
+    }
+
+    let mut names = output.lines();
+    let mut missing = false;
+    listing.rename_all(|_, old| match names.next() {
+        Some(name) if !name.trim().is_empty() => name.trim().to_owned(),
+        _ => {
+            missing = true;
+            old.to_owned()
+        }
+    });
+    if missing || names.next().is_some() {
+        bail!("expected \"{command}\" to write a name for each of the names it read");
+    }
+    Ok(())
+}
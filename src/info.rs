@@ -0,0 +1,78 @@
+//! Lookup of everything known about a single opcode or mnemonic.
+
+use intel_8080_disassembler::explain;
+use intel_8080_disassembler::opcodes::{self, Opcode};
+
+/// What the user asked information about.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Query {
+    Opcode(u8),
+    Mnemonic(String),
+}
+
+impl Query {
+    /// Parse an opcode like "0xC3" or "C3", or a mnemonic like "dad".
+    ///
+    /// Mnemonics take precedence, so "CC" is the call instruction and "0xCC" its opcode.
+    pub fn parse(text: &str) -> Option<Self> {
+        if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            return u8::from_str_radix(hex, 16).ok().map(Self::Opcode);
+        }
+
+        let mnemonic = text.to_ascii_uppercase();
+        if opcodes::summary(&mnemonic).is_some() {
+            Some(Self::Mnemonic(mnemonic))
+        } else {
+            u8::from_str_radix(text, 16).ok().map(Self::Opcode)
+        }
+    }
+
+    fn matches(&self, opcode: &Opcode) -> bool {
+        match self {
+            Self::Opcode(byte) => {
+                let queried = opcodes::lookup(*byte);
+                queried.mnemonic == opcode.mnemonic && queried.registers == opcode.registers
+            }
+            Self::Mnemonic(mnemonic) => opcode.mnemonic == mnemonic,
+        }
+    }
+}
+
+/// Print the description of the queried instruction followed by all its encodings.
+pub fn print(out: &mut dyn std::io::Write, query: &Query) -> std::io::Result<()> {
+    let mnemonic = match query {
+        Query::Opcode(byte) => opcodes::lookup(*byte).mnemonic,
+        Query::Mnemonic(mnemonic) => mnemonic,
+    };
+    // Every mnemonic in the opcode table has a summary.
+    writeln!(
+        out,
+        "{mnemonic}  {}",
+        opcodes::summary(mnemonic).unwrap_or_default()
+    )?;
+    writeln!(out)?;
+    writeln!(out, "Hex  Instruction  Length  Cycles  Flags  Effect")?;
+
+    for byte in 0..=u8::MAX {
+        let opcode = opcodes::lookup(byte);
+        if !query.matches(&opcode) {
+            continue;
+        }
+
+        let instruction = format!("{} {}", opcode.mnemonic, opcode.operands());
+        let undocumented = if opcodes::is_undocumented(byte) {
+            "  (undocumented)"
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "{byte:02X}   {instruction:<13}{:<8}{:<8}{}  {}{undocumented}",
+            opcode.length(),
+            opcode.cycles.to_string(),
+            opcode.flags,
+            explain::describe(&opcode, None)
+        )?;
+    }
+    Ok(())
+}
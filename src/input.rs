@@ -0,0 +1,625 @@
+//! Loading of the rom image, decompressing archives on the fly.
+
+use std::io::{Cursor, Read};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::error::DisasmError;
+
+use crate::machines::Machine;
+use crate::patch;
+
+type ZipArchive = zip::ZipArchive<Cursor<Vec<u8>>>;
+
+/// Memory contents to disassemble, starting at address 0.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Image {
+    pub bytes: Vec<u8>,
+    /// Files the image was assembled from.
+    pub segments: Vec<Segment>,
+    /// Bytes changed by patches after loading.
+    pub patched: Vec<Patched>,
+    /// Files the image was read from, as they were read.
+    pub files: Vec<File>,
+}
+
+/// Contents of a file, after decompressing and decoding it, but before dropping a header,
+/// patching it or moving it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct File {
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Bytes of the image changed by a patch.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Patched {
+    pub range: Range<usize>,
+    /// Name of the patch file.
+    pub name: String,
+}
+
+/// Part of the image loaded from a single file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Segment {
+    pub name: String,
+    pub address: u16,
+    pub length: usize,
+}
+
+impl Image {
+    /// Image made of a single file.
+    #[must_use]
+    pub fn from_file(name: &str, bytes: Vec<u8>) -> Self {
+        let segment = Segment {
+            name: name.to_owned(),
+            address: 0,
+            length: bytes.len(),
+        };
+        let file = File {
+            name: name.to_owned(),
+            bytes: bytes.clone(),
+        };
+        Self {
+            bytes,
+            segments: vec![segment],
+            patched: Vec::new(),
+            files: vec![file],
+        }
+    }
+
+    /// Drop the first `length` bytes of the image, such as the header of a dump format, so
+    /// that the code after them starts at address 0.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the header covers the whole image.
+    pub fn without_header(self, length: usize) -> Result<Self, DisasmError> {
+        if length >= self.bytes.len() {
+            return Err(DisasmError::Placement(format!(
+                "a header of 0x{length:x} bytes leaves nothing of the 0x{:x} bytes of the image",
+                self.bytes.len()
+            )));
+        }
+        let segments = self
+            .segments
+            .into_iter()
+            .filter_map(|segment| {
+                let start = usize::from(segment.address);
+                let end = start + segment.length;
+                (end > length).then(|| Segment {
+                    // Segments start in the image, so they still do once it is shorter.
+                    address: u16::try_from(start.saturating_sub(length)).unwrap_or_default(),
+                    length: end - start.max(length),
+                    ..segment
+                })
+            })
+            .collect();
+        let patched = self
+            .patched
+            .into_iter()
+            .filter(|patched| patched.range.end > length)
+            .map(|patched| Patched {
+                range: patched.range.start.saturating_sub(length)..patched.range.end - length,
+                ..patched
+            })
+            .collect();
+        Ok(Self {
+            bytes: self.bytes[length..].to_vec(),
+            segments,
+            patched,
+            files: self.files,
+        })
+    }
+
+    /// Move the image to start at `address`, filling the memory below with 0xFF.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the image does not fit in memory anymore.
+    pub fn moved_to(self, address: u16) -> Result<Self, DisasmError> {
+        let start = usize::from(address);
+        if start + self.bytes.len() > 0x10000 {
+            return Err(DisasmError::Placement(format!(
+                "image does not fit in memory at 0x{address:04x}"
+            )));
+        }
+        let mut bytes = vec![0xFF; start];
+        bytes.extend_from_slice(&self.bytes);
+        let segments = self
+            .segments
+            .into_iter()
+            .map(|segment| Segment {
+                // The whole image fits in memory, so every segment does.
+                address: segment.address + address,
+                ..segment
+            })
+            .collect();
+        let patched = self
+            .patched
+            .into_iter()
+            .map(|patched| Patched {
+                range: patched.range.start + start..patched.range.end + start,
+                ..patched
+            })
+            .collect();
+        Ok(Self {
+            bytes,
+            segments,
+            patched,
+            files: self.files,
+        })
+    }
+
+    /// Image with the bytes of a patched copy, recording the bytes changed under the name
+    /// of the patch. The last segment grows or shrinks to the end of the new bytes.
+    #[must_use]
+    pub fn patched_with(mut self, name: &str, bytes: Vec<u8>) -> Self {
+        // Bytes past the end of the image are all new.
+        let changed = patch::changes(&self.bytes, &bytes)
+            .into_iter()
+            .map(|change| change.offset..change.offset + change.patched.len())
+            .chain(std::iter::once(self.bytes.len()..bytes.len()))
+            .filter(|range| !range.is_empty());
+        self.patched.extend(changed.map(|range| Patched {
+            range,
+            name: name.to_owned(),
+        }));
+        if let Some(last) = self.segments.last_mut() {
+            last.length = bytes.len().saturating_sub(usize::from(last.address));
+        }
+        self.bytes = bytes;
+        self
+    }
+
+    /// Name of the last patch changing any of the bytes in a range.
+    #[must_use]
+    pub fn patch_at(&self, range: Range<usize>) -> Option<&str> {
+        self.patched
+            .iter()
+            .rev()
+            .find(|patched| patched.range.start < range.end && range.start < patched.range.end)
+            .map(|patched| patched.name.as_str())
+    }
+
+    /// Bytes of a segment of the image.
+    #[must_use]
+    pub fn segment_bytes(&self, segment: &Segment) -> &[u8] {
+        let start = usize::from(segment.address);
+        &self.bytes[start..start + segment.length]
+    }
+}
+
+/// Encoding of the rom file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Format {
+    /// Raw bytes.
+    #[default]
+    Binary,
+    /// Text of hexadecimal digits, such as the output of `xxd` or `hexdump -C`.
+    Hex,
+    /// Intel HEX records, as produced by most assemblers.
+    IntelHex,
+}
+
+impl Format {
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "binary" | "bin" => Some(Self::Binary),
+            "hex" => Some(Self::Hex),
+            "ihex" => Some(Self::IntelHex),
+            _ => None,
+        }
+    }
+}
+
+/// Read the rom at `path`, decompressing it first when it is a gzip or zip archive.
+///
+/// A `path` of "-" reads the standard input. Zip archives holding more than one file need
+/// the name of the `member` to read.
+///
+/// # Errors
+///
+/// Fails when the file cannot be read, decompressed or parsed.
+pub fn read_rom(path: &str, member: Option<&str>, format: Format) -> Result<Image, DisasmError> {
+    let (name, data) = read_file(path, member)?;
+    let rom = match format {
+        Format::Binary => data,
+        Format::Hex => parse_hex(&String::from_utf8_lossy(&data))?,
+        Format::IntelHex => parse_intel_hex(&String::from_utf8_lossy(&data))?,
+    };
+    Ok(Image::from_file(&name, rom))
+}
+
+/// Read and decompress a file, returning its name along with its contents.
+fn read_file(path: &str, member: Option<&str>) -> Result<(String, Vec<u8>), DisasmError> {
+    if path == "-" {
+        let mut data = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut data)
+            .map_err(io_error("reading standard input"))?;
+        return Ok(("(stdin)".to_owned(), data));
+    }
+
+    let data = std::fs::read(path).map_err(io_error("opening rom file"))?;
+    let file_name = Path::new(path)
+        .file_name()
+        .map_or_else(|| path.to_owned(), |n| n.to_string_lossy().into_owned());
+
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("gz") => {
+            let mut rom = Vec::new();
+            flate2::read::GzDecoder::new(data.as_slice())
+                .read_to_end(&mut rom)
+                .map_err(io_error("decompressing gzip file"))?;
+            let name = file_name.strip_suffix(".gz").unwrap_or(&file_name);
+            Ok((name.to_owned(), rom))
+        }
+        Some("zip") => {
+            let mut archive =
+                ZipArchive::new(Cursor::new(data)).map_err(zip_error("reading zip file"))?;
+            let name = select_member(&archive, member)?;
+            let rom = read_member(&mut archive, &name)?;
+            Ok((name, rom))
+        }
+        _ => Ok((file_name, data)),
+    }
+}
+
+/// Parse bytes written as hexadecimal text.
+///
+/// Bytes are separated by spaces or commas, or packed together like "c30010", and may be
+/// prefixed by "0x". Comments start with `#` or `;`. Dumps made by `xxd` and `hexdump -C`
+/// are recognized by their first line, and their offset and text columns are skipped.
+///
+/// # Errors
+///
+/// Fails on text that is neither a hexadecimal byte nor part of a dump.
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, DisasmError> {
+    let is_offset = |s: &str| s.len() >= 4 && s.chars().all(|c| c.is_ascii_hexdigit());
+    let first_line = text
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .unwrap_or_default();
+    let is_xxd = first_line
+        .split_once(": ")
+        .is_some_and(|(offset, _)| is_offset(offset));
+    let is_hexdump = first_line
+        .split_once("  ")
+        .is_some_and(|(offset, _)| offset.len() == 8 && is_offset(offset));
+
+    let mut rom = Vec::new();
+    let mut previous_line = Vec::new();
+    let mut repeat = false;
+    for (line_index, line) in text.lines().enumerate() {
+        let line = line.split(['#', ';']).next().unwrap_or_default();
+
+        let digits = if is_xxd {
+            // "00000010: 3e01 c300 10ff  >.....", with two spaces before the text.
+            let (_, rest) = line.split_once(": ").unwrap_or_default();
+            rest.split("  ").next().unwrap_or_default()
+        } else if is_hexdump {
+            // "00000010  3e 01 c3 00 10 ff  |>.....|", with "*" in place of repeated lines.
+            let (offset, rest) = line.split_once("  ").unwrap_or((line, ""));
+            if line.trim() == "*" {
+                repeat = true;
+                continue;
+            }
+            if repeat {
+                let offset =
+                    usize::from_str_radix(offset.trim(), 16).map_err(|e| DisasmError::Parse {
+                        line: line_index + 1,
+                        message: format!("invalid offset \"{}\": {e}", offset.trim()),
+                    })?;
+                while rom.len() < offset && !previous_line.is_empty() {
+                    rom.extend_from_slice(&previous_line);
+                }
+                rom.truncate(offset);
+                repeat = false;
+            }
+            rest.split('|').next().unwrap_or_default()
+        } else {
+            line
+        };
+
+        let line_start = rom.len();
+        for token in digits.split(|c: char| c.is_whitespace() || c == ',') {
+            let token = token
+                .strip_prefix("0x")
+                .or_else(|| token.strip_prefix("0X"))
+                .unwrap_or(token);
+            if token.len() % 2 != 0 || !token.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(DisasmError::Parse {
+                    line: line_index + 1,
+                    message: format!("invalid byte \"{token}\""),
+                });
+            }
+            for pair in token.as_bytes().chunks(2) {
+                // Both characters were checked to be hexadecimal digits.
+                let pair = std::str::from_utf8(pair).unwrap_or_default();
+                rom.push(u8::from_str_radix(pair, 16).unwrap_or_default());
+            }
+        }
+        previous_line = rom[line_start..].to_vec();
+    }
+    Ok(rom)
+}
+
+/// Parse Intel HEX records into the memory image they describe, starting at address 0.
+///
+/// Addresses not covered by any record are filled with 0xFF.
+///
+/// # Errors
+///
+/// Fails on malformed records and wrong checksums.
+pub fn parse_intel_hex(text: &str) -> Result<Vec<u8>, DisasmError> {
+    let mut rom = Vec::new();
+    for (line_index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = |message: &str| DisasmError::Parse {
+            line: line_index + 1,
+            message: format!("invalid record, {message}"),
+        };
+
+        let record = line
+            .strip_prefix(':')
+            .filter(|r| r.len() % 2 == 0 && r.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| invalid("expected \":\" followed by pairs of digits"))?;
+        let bytes: Vec<u8> = record
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| {
+                // Both characters were checked to be hexadecimal digits.
+                let pair = std::str::from_utf8(pair).unwrap_or_default();
+                u8::from_str_radix(pair, 16).unwrap_or_default()
+            })
+            .collect();
+
+        let [length, high, low, kind, ..] = bytes[..] else {
+            return Err(invalid("too short"));
+        };
+        let data = bytes
+            .get(4..4 + usize::from(length))
+            .filter(|_| bytes.len() == 5 + usize::from(length))
+            .ok_or_else(|| invalid("its length does not match its data"))?;
+        if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+            return Err(DisasmError::Checksum {
+                line: line_index + 1,
+            });
+        }
+
+        match kind {
+            0x00 => {
+                let start = usize::from(u16::from_be_bytes([high, low]));
+                let end = start + data.len();
+                if rom.len() < end {
+                    rom.resize(end, 0xFF);
+                }
+                rom[start..end].copy_from_slice(data);
+            }
+            0x01 => break,
+            // Start addresses do not affect the memory contents.
+            0x03 | 0x05 => {}
+            _ => {
+                return Err(DisasmError::Parse {
+                    line: line_index + 1,
+                    message: format!("unsupported record type {kind:02x}"),
+                })
+            }
+        }
+    }
+    Ok(rom)
+}
+
+/// Read the rom files of `machine` from the romset at `path` and place them at their address.
+///
+/// The gaps between the files are filled with 0xFF, like unprogrammed memory.
+///
+/// # Errors
+///
+/// Fails when the romset cannot be read, lacks one of the files, or a file does not fit in
+/// memory.
+pub fn read_machine(path: &str, machine: &Machine) -> Result<Image, DisasmError> {
+    let data = std::fs::read(path).map_err(io_error("opening romset file"))?;
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(zip_error("reading zip file"))?;
+
+    let mut rom = Vec::new();
+    let mut segments = Vec::new();
+    let mut files = Vec::new();
+    for file in &machine.roms {
+        let data = read_member(&mut archive, &file.name)?;
+        let start = usize::from(file.address);
+        let end = start + data.len();
+        if end > 0x10000 {
+            return Err(DisasmError::Placement(format!(
+                "rom file \"{}\" does not fit in memory",
+                file.name
+            )));
+        }
+        if rom.len() < end {
+            rom.resize(end, 0xFF);
+        }
+        rom[start..end].copy_from_slice(&data);
+        segments.push(Segment {
+            name: file.name.clone(),
+            address: file.address,
+            length: data.len(),
+        });
+        files.push(File {
+            name: file.name.clone(),
+            bytes: data,
+        });
+    }
+
+    Ok(Image {
+        bytes: rom,
+        segments,
+        patched: Vec::new(),
+        files,
+    })
+}
+
+/// Name of the zip member to read, which may be omitted when there is only one.
+fn select_member(archive: &ZipArchive, member: Option<&str>) -> Result<String, DisasmError> {
+    if let Some(name) = member {
+        return Ok(name.to_owned());
+    }
+
+    let names = archive
+        .file_names()
+        .map(|name| name.map(String::from))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(zip_error("reading zip file"))?;
+    match names.as_slice() {
+        [name] => Ok(name.clone()),
+        [] => Err(DisasmError::Member("zip file is empty".to_owned())),
+        _ => Err(DisasmError::Member(format!(
+            "zip file has several members, select one with --member: {}",
+            names.join(", ")
+        ))),
+    }
+}
+
+fn read_member(archive: &mut ZipArchive, name: &str) -> Result<Vec<u8>, DisasmError> {
+    let mut file = archive
+        .by_name(name)
+        .map_err(zip_error(&format!("reading member \"{name}\" of zip file")))?;
+    let mut rom = Vec::new();
+    file.read_to_end(&mut rom).map_err(io_error(&format!(
+        "decompressing member \"{name}\" of zip file"
+    )))?;
+    Ok(rom)
+}
+
+/// Wrap an I/O error with what was being read.
+fn io_error(context: &str) -> impl FnOnce(std::io::Error) -> DisasmError + '_ {
+    move |source| DisasmError::Io {
+        context: context.to_owned(),
+        source,
+    }
+}
+
+/// Wrap a zip error with what was being read.
+fn zip_error(context: &str) -> impl FnOnce(zip::result::ZipError) -> DisasmError + '_ {
+    move |source| DisasmError::Zip {
+        context: context.to_owned(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_bytes_are_read_with_or_without_separators() {
+        let text = "3e 01, 0xC3 0X00\n# whole line comment\nc30010 ; jump\n\n";
+        assert_eq!(
+            parse_hex(text).unwrap(),
+            [0x3E, 0x01, 0xC3, 0x00, 0xC3, 0x00, 0x10]
+        );
+    }
+
+    #[test]
+    fn xxd_dumps_are_read_without_their_offsets_and_text() {
+        let text = "\
+00000000: 3e01 c300 10ff 0000 0000 0000 0000 0000  >...............
+00000010: 0000 0000 0000 0000 0000 0000 0000 0000  ................
+00000020: 0000 0000 0000 0000 4142                 ........AB
+";
+        let rom = parse_hex(text).unwrap();
+        assert_eq!(rom.len(), 0x2A);
+        assert_eq!(rom[..6], [0x3E, 0x01, 0xC3, 0x00, 0x10, 0xFF]);
+        assert_eq!(rom[0x28..], [0x41, 0x42]);
+    }
+
+    #[test]
+    fn hexdump_dumps_repeat_the_line_before_a_star() {
+        let text = "\
+00000000  3e 01 c3 00 10 ff 00 00  00 00 00 00 00 00 00 00  |>...............|
+00000010  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|
+*
+00000040  41 42                                             |AB|
+00000042
+";
+        let rom = parse_hex(text).unwrap();
+        assert_eq!(rom.len(), 0x42);
+        assert_eq!(rom[..6], [0x3E, 0x01, 0xC3, 0x00, 0x10, 0xFF]);
+        assert!(rom[6..0x40].iter().all(|&b| b == 0));
+        assert_eq!(rom[0x40..], [0x41, 0x42]);
+    }
+
+    #[test]
+    fn invalid_hex_bytes_fail_with_their_line() {
+        assert!(matches!(
+            parse_hex("3e 01\nc3 0g 10\n"),
+            Err(DisasmError::Parse { line: 2, message }) if message == "invalid byte \"0g\""
+        ));
+        assert!(matches!(
+            parse_hex("3e 1"),
+            Err(DisasmError::Parse { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn intel_hex_records_are_placed_at_their_address() {
+        // Data at 0x0100, 0x0000 and 0x0008, start addresses of types 03 and 05, then the
+        // end of the file, after which nothing is read.
+        let text = "\
+:030100003E01C3FA
+:02000000AFC986
+:010008007681
+:0400000300000100F8
+:0400000500000100F6
+:00000001FF
+:020004001234B4
+";
+        let rom = parse_intel_hex(text).unwrap();
+        assert_eq!(rom.len(), 0x103);
+        assert_eq!(rom[..2], [0xAF, 0xC9]);
+        // Addresses between records are left unprogrammed.
+        assert_eq!(rom[2..8], [0xFF; 6]);
+        assert_eq!(rom[8], 0x76);
+        assert!(rom[9..0x100].iter().all(|&b| b == 0xFF));
+        assert_eq!(rom[0x100..], [0x3E, 0x01, 0xC3]);
+    }
+
+    #[test]
+    fn intel_hex_records_with_a_wrong_checksum_fail() {
+        assert!(matches!(
+            parse_intel_hex(":02000000AFC986\n:030100003E01C3FB\n"),
+            Err(DisasmError::Checksum { line: 2 })
+        ));
+    }
+
+    #[test]
+    fn malformed_intel_hex_records_fail_with_their_line() {
+        for text in [
+            "030100003E01C3FA",
+            ":030100003E01C3F",
+            ":0301",
+            ":040100003E01C3F9",
+            ":0301ZZ003E01C3FA",
+            ":+1010000FF",
+            ":0\u{e9}0",
+            ":0\u{fffd}00",
+            ":020000021000EC",
+        ] {
+            assert!(
+                matches!(
+                    parse_intel_hex(text),
+                    Err(DisasmError::Parse { line: 1, .. })
+                ),
+                "{text}"
+            );
+        }
+    }
+}
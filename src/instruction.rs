@@ -0,0 +1,656 @@
+//! Instructions as values to pattern-match on, rather than mnemonics and operands to parse.
+
+use core::fmt;
+
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+use core::ops::Deref;
+
+use crate::opcodes;
+
+/// Register operand, encoded in the opcode itself.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Register {
+    A,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    /// The byte of memory HL points to.
+    M,
+}
+
+impl Register {
+    /// Register named as in operands, such as `C`.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "A" => Self::A,
+            "B" => Self::B,
+            "C" => Self::C,
+            "D" => Self::D,
+            "E" => Self::E,
+            "H" => Self::H,
+            "L" => Self::L,
+            "M" => Self::M,
+            _ => return None,
+        })
+    }
+
+    /// Field of the register in opcodes, such as 7 for A in `MOV A,B`.
+    const fn code(self) -> u8 {
+        match self {
+            Self::B => 0,
+            Self::C => 1,
+            Self::D => 2,
+            Self::E => 3,
+            Self::H => 4,
+            Self::L => 5,
+            Self::M => 6,
+            Self::A => 7,
+        }
+    }
+
+    /// Register of a field of opcodes, the inverse of [`Register::code`].
+    const fn from_code(code: u8) -> Self {
+        [
+            Self::B,
+            Self::C,
+            Self::D,
+            Self::E,
+            Self::H,
+            Self::L,
+            Self::M,
+            Self::A,
+        ][(code & 7) as usize]
+    }
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::A => "A",
+            Self::B => "B",
+            Self::C => "C",
+            Self::D => "D",
+            Self::E => "E",
+            Self::H => "H",
+            Self::L => "L",
+            Self::M => "M",
+        }
+    }
+}
+
+/// Register pair operand, encoded in the opcode itself.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RegisterPair {
+    Bc,
+    De,
+    Hl,
+    /// The stack pointer, for `LXI`, `INX`, `DCX` and `DAD`.
+    Sp,
+    /// A and the flags, for `PUSH` and `POP`.
+    Psw,
+}
+
+impl RegisterPair {
+    /// Register pair named as in operands, after its high register such as `D` for DE.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "B" => Self::Bc,
+            "D" => Self::De,
+            "H" => Self::Hl,
+            "SP" => Self::Sp,
+            "PSW" => Self::Psw,
+            _ => return None,
+        })
+    }
+
+    /// Field of the register pair in opcodes, where PSW takes the place of SP in `PUSH`
+    /// and `POP`.
+    const fn code(self) -> u8 {
+        match self {
+            Self::Bc => 0,
+            Self::De => 1,
+            Self::Hl => 2,
+            Self::Sp | Self::Psw => 3,
+        }
+    }
+
+    /// Register pair of a field of opcodes, SP for 3, the inverse of [`RegisterPair::code`].
+    const fn from_code(code: u8) -> Self {
+        [Self::Bc, Self::De, Self::Hl, Self::Sp][(code & 3) as usize]
+    }
+
+    /// Name as in operands, such as `D` for DE.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Bc => "B",
+            Self::De => "D",
+            Self::Hl => "H",
+            Self::Sp => "SP",
+            Self::Psw => "PSW",
+        }
+    }
+}
+
+/// Condition of the conditional jumps, calls and returns.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Condition {
+    NotZero,
+    Zero,
+    NoCarry,
+    Carry,
+    ParityOdd,
+    ParityEven,
+    Plus,
+    Minus,
+}
+
+impl Condition {
+    /// Condition named as in mnemonics, such as `NZ` in `JNZ`.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "NZ" => Self::NotZero,
+            "Z" => Self::Zero,
+            "NC" => Self::NoCarry,
+            "C" => Self::Carry,
+            "PO" => Self::ParityOdd,
+            "PE" => Self::ParityEven,
+            "P" => Self::Plus,
+            "M" => Self::Minus,
+            _ => return None,
+        })
+    }
+
+    /// Field of the condition in opcodes, such as 1 for Z in `JZ`.
+    const fn code(self) -> u8 {
+        match self {
+            Self::NotZero => 0,
+            Self::Zero => 1,
+            Self::NoCarry => 2,
+            Self::Carry => 3,
+            Self::ParityOdd => 4,
+            Self::ParityEven => 5,
+            Self::Plus => 6,
+            Self::Minus => 7,
+        }
+    }
+
+    /// Condition of a field of opcodes, the inverse of [`Condition::code`].
+    const fn from_code(code: u8) -> Self {
+        [
+            Self::NotZero,
+            Self::Zero,
+            Self::NoCarry,
+            Self::Carry,
+            Self::ParityOdd,
+            Self::ParityEven,
+            Self::Plus,
+            Self::Minus,
+        ][(code & 7) as usize]
+    }
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::NotZero => "NZ",
+            Self::Zero => "Z",
+            Self::NoCarry => "NC",
+            Self::Carry => "C",
+            Self::ParityOdd => "PO",
+            Self::ParityEven => "PE",
+            Self::Plus => "P",
+            Self::Minus => "M",
+        }
+    }
+}
+
+/// Operand of an instruction, as written after its mnemonic.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operand {
+    Register(Register),
+    RegisterPair(RegisterPair),
+    /// Byte of data.
+    Immediate8(u8),
+    /// Word of data, loaded by `LXI`.
+    Immediate16(u16),
+    /// Memory read or written, or target of a jump or call.
+    Address(u16),
+    Port(u8),
+    /// Number of the restart vector called by `RST`, from 0 to 7.
+    RstVector(u8),
+}
+
+impl fmt::Display for Operand {
+    /// Write the operand like `B`, `0x41`, `0x1a30` or `7`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Register(register) => f.write_str(register.name()),
+            Self::RegisterPair(pair) => f.write_str(pair.name()),
+            Self::Immediate8(value) | Self::Port(value) => write!(f, "0x{value:02x}"),
+            Self::Immediate16(value) | Self::Address(value) => write!(f, "0x{value:04x}"),
+            Self::RstVector(vector) => write!(f, "{vector}"),
+        }
+    }
+}
+
+/// Instruction of the 8080 with its operands.
+///
+/// Undocumented opcodes decode to the instruction the 8080 executes for them, as in
+/// [`opcodes::lookup`], and conditional instructions hold their [`Condition`], like
+/// `Jcc(Condition::NotZero, 0x1a30)` for `JNZ 0x1a30`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Instruction {
+    Nop,
+    Lxi(RegisterPair, u16),
+    Stax(RegisterPair),
+    Inx(RegisterPair),
+    Inr(Register),
+    Dcr(Register),
+    Mvi(Register, u8),
+    Rlc,
+    Dad(RegisterPair),
+    Ldax(RegisterPair),
+    Dcx(RegisterPair),
+    Rrc,
+    Ral,
+    Rar,
+    Shld(u16),
+    Daa,
+    Lhld(u16),
+    Cma,
+    Sta(u16),
+    Stc,
+    Lda(u16),
+    Cmc,
+    /// Copy the second register into the first.
+    Mov(Register, Register),
+    Hlt,
+    Add(Register),
+    Adc(Register),
+    Sub(Register),
+    Sbb(Register),
+    Ana(Register),
+    Xra(Register),
+    Ora(Register),
+    Cmp(Register),
+    Adi(u8),
+    Aci(u8),
+    Sui(u8),
+    Sbi(u8),
+    Ani(u8),
+    Xri(u8),
+    Ori(u8),
+    Cpi(u8),
+    Jmp(u16),
+    Jcc(Condition, u16),
+    Call(u16),
+    Ccc(Condition, u16),
+    Ret,
+    Rcc(Condition),
+    /// Call the restart vector, from 0 to 7, at eight times its number.
+    Rst(u8),
+    Push(RegisterPair),
+    Pop(RegisterPair),
+    Out(u8),
+    In(u8),
+    Xthl,
+    Sphl,
+    Pchl,
+    Xchg,
+    Di,
+    Ei,
+}
+
+impl Instruction {
+    /// Decode the instruction the bytes start with, `None` when it is cut off before its
+    /// immediate.
+    ///
+    /// The operands are read from the fields of the opcode that [`Instruction::encode`]
+    /// writes them to, after replacing an undocumented opcode by its canonical one.
+    #[must_use]
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let opcode = opcodes::canonical_opcode(*bytes.first()?);
+        let bytes = bytes.get(..opcodes::lookup(opcode).length())?;
+        let byte = bytes.get(1).copied().unwrap_or_default();
+        let word = match *bytes {
+            [_, low, high] => u16::from_le_bytes([low, high]),
+            _ => 0,
+        };
+        let pair = RegisterPair::from_code(opcode >> 4);
+        // PSW takes the place of SP in PUSH and POP.
+        let stacked = if pair == RegisterPair::Sp {
+            RegisterPair::Psw
+        } else {
+            pair
+        };
+        let destination = Register::from_code(opcode >> 3);
+        let source = Register::from_code(opcode);
+        let condition = Condition::from_code(opcode >> 3);
+        let arithmetic: [fn(Register) -> Self; 8] = [
+            Self::Add,
+            Self::Adc,
+            Self::Sub,
+            Self::Sbb,
+            Self::Ana,
+            Self::Xra,
+            Self::Ora,
+            Self::Cmp,
+        ];
+
+        Some(match opcode {
+            0x00 => Self::Nop,
+            0x07 => Self::Rlc,
+            0x0F => Self::Rrc,
+            0x17 => Self::Ral,
+            0x1F => Self::Rar,
+            0x22 => Self::Shld(word),
+            0x27 => Self::Daa,
+            0x2A => Self::Lhld(word),
+            0x2F => Self::Cma,
+            0x32 => Self::Sta(word),
+            0x37 => Self::Stc,
+            0x3A => Self::Lda(word),
+            0x3F => Self::Cmc,
+            0x76 => Self::Hlt,
+            0xC3 => Self::Jmp(word),
+            0xC6 => Self::Adi(byte),
+            0xC9 => Self::Ret,
+            0xCD => Self::Call(word),
+            0xCE => Self::Aci(byte),
+            0xD3 => Self::Out(byte),
+            0xD6 => Self::Sui(byte),
+            0xDB => Self::In(byte),
+            0xDE => Self::Sbi(byte),
+            0xE3 => Self::Xthl,
+            0xE6 => Self::Ani(byte),
+            0xE9 => Self::Pchl,
+            0xEB => Self::Xchg,
+            0xEE => Self::Xri(byte),
+            0xF3 => Self::Di,
+            0xF6 => Self::Ori(byte),
+            0xF9 => Self::Sphl,
+            0xFB => Self::Ei,
+            0xFE => Self::Cpi(byte),
+            _ if opcode & 0xCF == 0x01 => Self::Lxi(pair, word),
+            _ if opcode & 0xCF == 0x02 => Self::Stax(pair),
+            _ if opcode & 0xCF == 0x03 => Self::Inx(pair),
+            _ if opcode & 0xC7 == 0x04 => Self::Inr(destination),
+            _ if opcode & 0xC7 == 0x05 => Self::Dcr(destination),
+            _ if opcode & 0xC7 == 0x06 => Self::Mvi(destination, byte),
+            _ if opcode & 0xCF == 0x09 => Self::Dad(pair),
+            _ if opcode & 0xCF == 0x0A => Self::Ldax(pair),
+            _ if opcode & 0xCF == 0x0B => Self::Dcx(pair),
+            0x40..=0x7F => Self::Mov(destination, source),
+            0x80..=0xBF => arithmetic[usize::from(opcode >> 3 & 7)](source),
+            _ if opcode & 0xC7 == 0xC0 => Self::Rcc(condition),
+            _ if opcode & 0xCF == 0xC1 => Self::Pop(stacked),
+            _ if opcode & 0xC7 == 0xC2 => Self::Jcc(condition, word),
+            _ if opcode & 0xC7 == 0xC4 => Self::Ccc(condition, word),
+            _ if opcode & 0xCF == 0xC5 => Self::Push(stacked),
+            // The opcodes left are those of RST, 0xC7 to 0xFF by steps of 8.
+            _ => Self::Rst(opcode >> 3 & 7),
+        })
+    }
+
+    /// Bytes of the instruction, with the opcode assemblers produce, or `None` when the
+    /// 8080 has no opcode for it, such as `Mov(Register::M, Register::M)` or `Rst(8)`.
+    #[must_use]
+    pub fn encode(self) -> Option<Bytes> {
+        let opcode = self.opcode_byte()?;
+        let [low, high] = self.immediate().unwrap_or_default().to_le_bytes();
+        Some(Bytes::new(
+            &[opcode, low, high][..opcodes::lookup(opcode).length()],
+        ))
+    }
+
+    /// Opcode assemblers produce for the instruction, made of the fields of its registers,
+    /// register pair, condition or restart vector.
+    fn opcode_byte(self) -> Option<u8> {
+        use RegisterPair::{Bc, De, Psw, Sp};
+
+        // Register pairs of LXI, INX, DCX and DAD, of STAX and LDAX, and of PUSH and POP.
+        let pair =
+            |pair: RegisterPair, opcode: u8| (pair != Psw).then(|| opcode | pair.code() << 4);
+        let indirect = |pair: RegisterPair, opcode: u8| {
+            matches!(pair, Bc | De).then(|| opcode | pair.code() << 4)
+        };
+        let stacked =
+            |pair: RegisterPair, opcode: u8| (pair != Sp).then(|| opcode | pair.code() << 4);
+        let register = |register: Register, opcode: u8| opcode | register.code();
+        let destination = |register: Register, opcode: u8| opcode | register.code() << 3;
+        let condition = |condition: Condition, opcode: u8| opcode | condition.code() << 3;
+
+        Some(match self {
+            Self::Nop => 0x00,
+            Self::Lxi(p, _) => pair(p, 0x01)?,
+            Self::Stax(p) => indirect(p, 0x02)?,
+            Self::Inx(p) => pair(p, 0x03)?,
+            Self::Inr(r) => destination(r, 0x04),
+            Self::Dcr(r) => destination(r, 0x05),
+            Self::Mvi(r, _) => destination(r, 0x06),
+            Self::Rlc => 0x07,
+            Self::Dad(p) => pair(p, 0x09)?,
+            Self::Ldax(p) => indirect(p, 0x0A)?,
+            Self::Dcx(p) => pair(p, 0x0B)?,
+            Self::Rrc => 0x0F,
+            Self::Ral => 0x17,
+            Self::Rar => 0x1F,
+            Self::Shld(_) => 0x22,
+            Self::Daa => 0x27,
+            Self::Lhld(_) => 0x2A,
+            Self::Cma => 0x2F,
+            Self::Sta(_) => 0x32,
+            Self::Stc => 0x37,
+            Self::Lda(_) => 0x3A,
+            Self::Cmc => 0x3F,
+            // The opcode of MOV M,M is that of HLT.
+            Self::Mov(Register::M, Register::M) => return None,
+            Self::Mov(to, from) => register(from, destination(to, 0x40)),
+            Self::Hlt => 0x76,
+            Self::Add(r) => register(r, 0x80),
+            Self::Adc(r) => register(r, 0x88),
+            Self::Sub(r) => register(r, 0x90),
+            Self::Sbb(r) => register(r, 0x98),
+            Self::Ana(r) => register(r, 0xA0),
+            Self::Xra(r) => register(r, 0xA8),
+            Self::Ora(r) => register(r, 0xB0),
+            Self::Cmp(r) => register(r, 0xB8),
+            Self::Rcc(c) => condition(c, 0xC0),
+            Self::Pop(p) => stacked(p, 0xC1)?,
+            Self::Jcc(c, _) => condition(c, 0xC2),
+            Self::Jmp(_) => 0xC3,
+            Self::Ccc(c, _) => condition(c, 0xC4),
+            Self::Push(p) => stacked(p, 0xC5)?,
+            Self::Adi(_) => 0xC6,
+            Self::Rst(vector) if vector < 8 => 0xC7 | vector << 3,
+            Self::Rst(_) => return None,
+            Self::Ret => 0xC9,
+            Self::Call(_) => 0xCD,
+            Self::Aci(_) => 0xCE,
+            Self::Out(_) => 0xD3,
+            Self::Sui(_) => 0xD6,
+            Self::In(_) => 0xDB,
+            Self::Sbi(_) => 0xDE,
+            Self::Xthl => 0xE3,
+            Self::Ani(_) => 0xE6,
+            Self::Pchl => 0xE9,
+            Self::Xchg => 0xEB,
+            Self::Xri(_) => 0xEE,
+            Self::Di => 0xF3,
+            Self::Ori(_) => 0xF6,
+            Self::Sphl => 0xF9,
+            Self::Ei => 0xFB,
+            Self::Cpi(_) => 0xFE,
+        })
+    }
+
+    /// Clock states the instruction takes, depending on whether the condition of a
+    /// conditional call or return is met, or `None` when the 8080 has no opcode for it.
+    #[must_use]
+    pub fn cycles(self) -> Option<opcodes::Cycles> {
+        self.opcode().map(|opcode| opcode.cycles)
+    }
+
+    /// Flags among S, Z, AC, P and CY the instruction modifies, or `None` when the 8080 has
+    /// no opcode for it.
+    #[must_use]
+    pub fn flags_affected(self) -> Option<opcodes::Flags> {
+        self.opcode().map(|opcode| opcode.flags)
+    }
+
+    /// Reference data of the opcode assemblers produce for the instruction, read from the
+    /// opcode table at that opcode.
+    fn opcode(self) -> Option<opcodes::Opcode> {
+        self.opcode_byte().map(opcodes::lookup)
+    }
+
+    /// Operands in the order they are written, leaving out the condition of conditional
+    /// instructions, which is part of the mnemonic.
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn operands(self) -> Vec<Operand> {
+        use Operand::{Address, Immediate16, Immediate8, Port, Register, RegisterPair, RstVector};
+
+        match self {
+            Self::Lxi(pair, value) => vec![RegisterPair(pair), Immediate16(value)],
+            Self::Stax(pair)
+            | Self::Inx(pair)
+            | Self::Dad(pair)
+            | Self::Ldax(pair)
+            | Self::Dcx(pair)
+            | Self::Push(pair)
+            | Self::Pop(pair) => vec![RegisterPair(pair)],
+            Self::Mvi(register, value) => vec![Register(register), Immediate8(value)],
+            Self::Mov(to, from) => vec![Register(to), Register(from)],
+            Self::Inr(register)
+            | Self::Dcr(register)
+            | Self::Add(register)
+            | Self::Adc(register)
+            | Self::Sub(register)
+            | Self::Sbb(register)
+            | Self::Ana(register)
+            | Self::Xra(register)
+            | Self::Ora(register)
+            | Self::Cmp(register) => vec![Register(register)],
+            Self::Adi(value)
+            | Self::Aci(value)
+            | Self::Sui(value)
+            | Self::Sbi(value)
+            | Self::Ani(value)
+            | Self::Xri(value)
+            | Self::Ori(value)
+            | Self::Cpi(value) => vec![Immediate8(value)],
+            Self::Shld(address)
+            | Self::Lhld(address)
+            | Self::Sta(address)
+            | Self::Lda(address)
+            | Self::Jmp(address)
+            | Self::Jcc(_, address)
+            | Self::Call(address)
+            | Self::Ccc(_, address) => vec![Address(address)],
+            Self::Out(port) | Self::In(port) => vec![Port(port)],
+            Self::Rst(vector) => vec![RstVector(vector)],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Value of the byte or word following the opcode.
+    #[must_use]
+    pub fn immediate(self) -> Option<u16> {
+        match self {
+            Self::Mvi(_, value)
+            | Self::Adi(value)
+            | Self::Aci(value)
+            | Self::Sui(value)
+            | Self::Sbi(value)
+            | Self::Ani(value)
+            | Self::Xri(value)
+            | Self::Ori(value)
+            | Self::Cpi(value)
+            | Self::Out(value)
+            | Self::In(value) => Some(u16::from(value)),
+            Self::Lxi(_, value)
+            | Self::Shld(value)
+            | Self::Lhld(value)
+            | Self::Sta(value)
+            | Self::Lda(value)
+            | Self::Jmp(value)
+            | Self::Jcc(_, value)
+            | Self::Call(value)
+            | Self::Ccc(_, value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Bytes of an encoded instruction, kept inline rather than allocated.
+///
+/// With the `serde` feature, they are serialized as a sequence of bytes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(
+    all(feature = "serde", feature = "alloc"),
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "Vec<u8>", try_from = "Vec<u8>")
+)]
+pub struct Bytes {
+    /// The bytes, followed by zeros past the length of the instruction.
+    buffer: [u8; 3],
+    length: usize,
+}
+
+impl Bytes {
+    /// Copy the bytes of an instruction, at most 3.
+    pub(crate) fn new(bytes: &[u8]) -> Self {
+        let mut buffer = [0; 3];
+        buffer[..bytes.len()].copy_from_slice(bytes);
+        Self {
+            buffer,
+            length: bytes.len(),
+        }
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer[..self.length]
+    }
+}
+
+impl AsRef<[u8]> for Bytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<Bytes> for Vec<u8> {
+    fn from(bytes: Bytes) -> Self {
+        bytes.to_vec()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl TryFrom<Vec<u8>> for Bytes {
+    type Error = &'static str;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        if (1..=3).contains(&bytes.len()) {
+            Ok(Self::new(&bytes))
+        } else {
+            Err("expected the 1 to 3 bytes of an instruction")
+        }
+    }
+}
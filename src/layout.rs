@@ -0,0 +1,125 @@
+//! Placement of the fields of a line of output in columns.
+
+use crate::syntax::Syntax;
+
+/// Columns of a terminal tab stop.
+const TAB_WIDTH: usize = 8;
+
+/// Columns at which the fields of a line start, counting from 0.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Layout {
+    pub mnemonic_column: usize,
+    pub operand_column: usize,
+    pub comment_column: usize,
+    /// Indent with spaces only, instead of tabs completed with spaces.
+    pub spaces: bool,
+}
+
+impl Layout {
+    /// Layout of the listing, with the mnemonic after the address and bytes, in the columns
+    /// of [`Syntax::LISTING`].
+    pub const LISTING: Self = Self {
+        mnemonic_column: Syntax::LISTING.mnemonic_column(),
+        operand_column: Syntax::LISTING.mnemonic_column() + Syntax::LISTING.mnemonic_width,
+        comment_column: 40,
+        spaces: true,
+    };
+
+    /// Layout of source code for an assembler.
+    pub const SOURCE: Self = Self {
+        mnemonic_column: 8,
+        operand_column: 16,
+        comment_column: 32,
+        spaces: false,
+    };
+
+    /// Move the operand column, and the comment column along with it.
+    #[must_use]
+    pub const fn with_operand_column(self, column: usize) -> Self {
+        let column = if column > self.mnemonic_column {
+            column
+        } else {
+            self.mnemonic_column + 1
+        };
+        Self {
+            operand_column: column,
+            comment_column: self.comment_column + column - self.operand_column,
+            ..self
+        }
+    }
+
+    /// Pad `line` up to `column`, or with a single space when the line already reaches it.
+    ///
+    /// The escape sequences coloring the text take no room.
+    pub fn pad(&self, line: &mut String, column: usize) {
+        let mut width = width(line);
+        if width >= column {
+            line.push(' ');
+            return;
+        }
+        if !self.spaces {
+            while next_tab_stop(width) <= column {
+                line.push('\t');
+                width = next_tab_stop(width);
+            }
+        }
+        line.extend(std::iter::repeat_n(' ', column - width));
+    }
+}
+
+const fn next_tab_stop(column: usize) -> usize {
+    (column / TAB_WIDTH + 1) * TAB_WIDTH
+}
+
+/// Columns taken by `text` on a terminal.
+#[must_use]
+pub fn width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // Hyperlink escape sequences start with "]" and end with ESC and a backslash,
+            // color escape sequences end with "m".
+            '\x1b' => match chars.next() {
+                Some(']') => {
+                    while chars.next().is_some_and(|c| c != '\x1b') {}
+                    chars.next();
+                }
+                _ => {
+                    chars.find(|&c| c == 'm');
+                }
+            },
+            '\t' => width = next_tab_stop(width),
+            _ => width += 1,
+        }
+    }
+    width
+}
+
+/// Split `text` into lines of at most `width` columns, breaking between words, and inside
+/// the words longer than a line.
+#[must_use]
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        let used = line.chars().count();
+        if used > 0 && used + 1 + word.len() <= width {
+            line.push(' ');
+        } else if used > 0 {
+            lines.push(std::mem::take(&mut line));
+        }
+        while line.is_empty() && word.len() > width {
+            let rest = word.split_off(width);
+            lines.push(word.into_iter().collect());
+            word = rest;
+        }
+        line.extend(word);
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
@@ -0,0 +1,108 @@
+//! Disassembler for the Intel 8080 machine language.
+//!
+//! [`disassemble_with_labels`] separates code from data by following the flow of
+//! execution, and names the addresses referred to by the code.
+//!
+//! The library keeps no global state and reports failures as [`DisasmError`] values instead of exiting.
+//! The opcode table is an immutable static, and every type is `Send + Sync`, so images, listings,
+//! dialects and layouts can be shared between threads disassembling concurrently.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+#![warn(
+    clippy::complexity,
+    clippy::correctness,
+    clippy::nursery,
+    clippy::pedantic,
+    clippy::perf,
+    clippy::style,
+    clippy::suspicious
+)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod analysis;
+#[cfg(feature = "std")]
+pub mod asm;
+#[cfg(feature = "std")]
+pub mod cfg;
+#[cfg(feature = "std")]
+pub mod charset;
+#[cfg(feature = "std")]
+pub mod conditions;
+#[cfg(feature = "std")]
+pub mod constants;
+#[cfg(feature = "std")]
+pub mod cpm;
+#[cfg(feature = "alloc")]
+pub mod decode;
+#[cfg(feature = "std")]
+pub mod effects;
+#[cfg(feature = "std")]
+pub mod encode;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod explain;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod hash;
+#[cfg(feature = "std")]
+pub mod input;
+pub mod instruction;
+#[cfg(feature = "std")]
+pub mod layout;
+#[cfg(feature = "std")]
+pub mod machines;
+pub mod memory;
+pub mod opcodes;
+#[cfg(feature = "std")]
+pub mod origin;
+#[cfg(feature = "std")]
+pub mod padding;
+#[cfg(feature = "std")]
+pub mod patch;
+#[cfg(feature = "std")]
+pub mod pointers;
+#[cfg(feature = "std")]
+pub mod project;
+#[cfg(feature = "std")]
+pub mod source;
+#[cfg(feature = "std")]
+pub mod strings;
+#[cfg(feature = "alloc")]
+pub mod syntax;
+#[cfg(feature = "std")]
+pub mod tokens;
+
+#[cfg(feature = "std")]
+pub use analysis::{disassemble_with_labels, Listing};
+#[cfg(feature = "std")]
+pub use error::DisasmError;
+
+/// Check at compile time that the public types can be shared between threads.
+#[cfg(feature = "std")]
+const _: () = {
+    const fn shareable<T: Clone + Send + Sync>() {}
+    shareable::<analysis::Listing>();
+    shareable::<analysis::Parameters>();
+    shareable::<analysis::Problem>();
+    shareable::<asm::Dialect>();
+    shareable::<cfg::Graph>();
+    shareable::<decode::DecodedLine>();
+    shareable::<constants::Known>();
+    shareable::<effects::State>();
+    shareable::<explain::Explained>();
+    shareable::<input::Image>();
+    shareable::<instruction::Bytes>();
+    shareable::<instruction::Instruction>();
+    shareable::<layout::Layout>();
+    shareable::<machines::Database>();
+    shareable::<opcodes::Opcode>();
+    shareable::<origin::Candidate>();
+    shareable::<padding::Padding>();
+    shareable::<syntax::Syntax>();
+    shareable::<tokens::TokenLine>();
+};
@@ -0,0 +1,320 @@
+//! Checks of the code for likely bugs, to find them in old firmware being restored.
+
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use intel_8080_disassembler::analysis::{LabelKind, Line, Listing};
+use intel_8080_disassembler::instruction::{Condition, Instruction, RegisterPair};
+use intel_8080_disassembler::opcodes::{self, Flags};
+
+/// Instructions looked at before a conditional instruction to find where its flag was set.
+const WINDOW: usize = 16;
+
+/// Likely bug found at an address.
+pub struct Warning {
+    pub address: u16,
+    pub message: String,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{:04x}: {}", self.address, self.message)
+    }
+}
+
+/// Warnings about the code of the listing, in the order of their addresses.
+///
+/// Writes to the image are only reported when it is a `rom`, rather than a program loaded
+/// into RAM.
+pub fn warnings(listing: &Listing, rom: bool) -> Vec<Warning> {
+    let functions: Vec<(u16, Range<usize>)> = listing
+        .functions
+        .iter()
+        .filter_map(|&function| Some((function, listing.function_range(function)?)))
+        .collect();
+
+    let mut warnings = Vec::new();
+    untested_flags(listing, &mut warnings);
+    for (function, range) in &functions {
+        unbalanced_stack(listing, *function, range, &mut warnings);
+    }
+    calls_into_functions(listing, &mut warnings);
+    if rom {
+        writes_to_image(listing, &mut warnings);
+    }
+    warnings.sort_by_key(|warning| warning.address);
+    warnings
+}
+
+/// Name of an address for a warning, from its label or symbol.
+fn name(listing: &Listing, address: u16) -> String {
+    listing
+        .name(address)
+        .map_or_else(|| format!("0x{address:04x}"), str::to_owned)
+}
+
+/// Flag tested by a conditional instruction, with its name.
+const fn tested_flag(condition: Condition) -> (Flags, &'static str) {
+    match condition {
+        Condition::NotZero | Condition::Zero => (Flags::Z, "Z"),
+        Condition::NoCarry | Condition::Carry => (Flags::CY, "CY"),
+        Condition::ParityOdd | Condition::ParityEven => (Flags::P, "P"),
+        Condition::Plus | Condition::Minus => (Flags::S, "S"),
+    }
+}
+
+/// Conditional instructions testing a flag that nothing sets between the start of their
+/// function and them.
+///
+/// Calls are taken to set every flag. Code after another label may be reached with the
+/// flag set elsewhere, so the search gives up there, as it does after `WINDOW`
+/// instructions.
+fn untested_flags(listing: &Listing, warnings: &mut Vec<Warning>) {
+    for (index, line) in listing.lines.iter().enumerate() {
+        let Line::Instruction { address, bytes } = line else {
+            continue;
+        };
+        let Some(
+            Instruction::Jcc(condition, _)
+            | Instruction::Ccc(condition, _)
+            | Instruction::Rcc(condition),
+        ) = Instruction::decode(bytes)
+        else {
+            continue;
+        };
+        let (flag, flag_name) = tested_flag(condition);
+
+        let mut position = index;
+        loop {
+            let at = listing.lines[position].address();
+            if let Some(label) = listing.labels.get(&at) {
+                if label.kind == LabelKind::Function {
+                    warnings.push(Warning {
+                        address: *address,
+                        message: format!(
+                            "{} tests {flag_name}, which {} does not set before",
+                            opcodes::lookup(bytes[0]).mnemonic,
+                            label.name
+                        ),
+                    });
+                }
+                break;
+            }
+            if position == 0 || index - position >= WINDOW {
+                break;
+            }
+            position -= 1;
+            let Line::Instruction {
+                bytes: previous, ..
+            } = &listing.lines[position]
+            else {
+                break;
+            };
+            let sets = opcodes::lookup(previous[0]).flags.contains(flag)
+                || matches!(
+                    Instruction::decode(previous),
+                    Some(Instruction::Call(_) | Instruction::Ccc(..) | Instruction::Rst(_))
+                );
+            // Code after an unconditional jump or return is only reached through a label.
+            let ends = matches!(
+                Instruction::decode(previous),
+                Some(Instruction::Jmp(_) | Instruction::Ret | Instruction::Pchl)
+            );
+            if sets || ends {
+                break;
+            }
+        }
+    }
+}
+
+/// Returns of a function with register pairs still pushed or popped too many times, and
+/// code reached with different numbers of them pushed.
+///
+/// The pushes and pops are counted along every path through the function, without
+/// following calls, which are taken to leave the stack as they found it. Popping the return
+/// address to read parameters is fine as long as something is pushed back before
+/// returning. Functions moving the stack pointer are left out.
+fn unbalanced_stack(
+    listing: &Listing,
+    function: u16,
+    range: &Range<usize>,
+    warnings: &mut Vec<Warning>,
+) {
+    // Each instruction with the address of the next one.
+    let instructions: BTreeMap<u16, (Instruction, u16)> = listing
+        .lines
+        .iter()
+        .filter(|line| range.contains(&usize::from(line.address())))
+        .filter_map(|line| match line {
+            Line::Instruction { address, bytes } => {
+                let next = u16::try_from(usize::from(*address) + bytes.len()).ok()?;
+                Some((*address, (Instruction::decode(bytes)?, next)))
+            }
+            _ => None,
+        })
+        .collect();
+    let moves_stack = instructions.values().any(|(instruction, _)| {
+        matches!(
+            instruction,
+            Instruction::Sphl
+                | Instruction::Lxi(RegisterPair::Sp, _)
+                | Instruction::Inx(RegisterPair::Sp)
+                | Instruction::Dcx(RegisterPair::Sp)
+        )
+    });
+    if moves_stack {
+        return;
+    }
+
+    let function_name = name(listing, function);
+    let mut depths: BTreeMap<u16, i32> = BTreeMap::new();
+    let mut pending = vec![(function, 0)];
+    while let Some((address, depth)) = pending.pop() {
+        let Some(&(instruction, next)) = instructions.get(&address) else {
+            continue;
+        };
+        match depths.entry(address) {
+            Entry::Occupied(entry) => {
+                // Reported once, even when reached with more numbers of pairs.
+                if *entry.get() != depth && !warnings.iter().any(|w| w.address == address) {
+                    warnings.push(Warning {
+                        address,
+                        message: format!(
+                            "reached with {} and {depth} register pairs pushed in {function_name}",
+                            entry.get()
+                        ),
+                    });
+                }
+                continue;
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(depth);
+            }
+        }
+
+        match instruction {
+            Instruction::Push(_) => pending.push((next, depth + 1)),
+            Instruction::Pop(_) => pending.push((next, depth - 1)),
+            Instruction::Ret | Instruction::Rcc(_) => {
+                if depth != 0 {
+                    let message = if depth > 0 {
+                        format!(
+                            "returns from {function_name} with {depth} register pairs still pushed"
+                        )
+                    } else {
+                        format!(
+                            "returns from {function_name} with {} more register pairs popped than pushed",
+                            -depth
+                        )
+                    };
+                    warnings.push(Warning { address, message });
+                }
+                if matches!(instruction, Instruction::Rcc(_)) {
+                    pending.push((next, depth));
+                }
+            }
+            Instruction::Jmp(target) => pending.push((target, depth)),
+            Instruction::Jcc(_, target) => {
+                pending.push((target, depth));
+                pending.push((next, depth));
+            }
+            Instruction::Pchl | Instruction::Hlt => {}
+            _ => pending.push((next, depth)),
+        }
+    }
+}
+
+/// Calls to an address that another function reaches by falling or jumping into it, such
+/// as a shared tail or a second entry point.
+///
+/// Functions are followed from their entry to their returns, unlike `function_range`, which
+/// also goes on past a `HLT` into whatever follows it.
+fn calls_into_functions(listing: &Listing, warnings: &mut Vec<Warning>) {
+    // Each instruction with the address of the next one.
+    let instructions: BTreeMap<u16, (Instruction, u16)> = listing
+        .lines
+        .iter()
+        .filter_map(|line| match line {
+            Line::Instruction { address, bytes } => {
+                let next = u16::try_from(usize::from(*address) + bytes.len()).ok()?;
+                Some((*address, (Instruction::decode(bytes)?, next)))
+            }
+            _ => None,
+        })
+        .collect();
+
+    // The first function found to reach each address, other than by starting there.
+    let mut owners: BTreeMap<u16, u16> = BTreeMap::new();
+    for &function in &listing.functions {
+        let mut reached = BTreeSet::new();
+        let mut pending = vec![function];
+        while let Some(address) = pending.pop() {
+            let Some(&(instruction, next)) = instructions.get(&address) else {
+                continue;
+            };
+            if !reached.insert(address) {
+                continue;
+            }
+            if address != function {
+                owners.entry(address).or_insert(function);
+            }
+            match instruction {
+                Instruction::Jmp(target) => pending.push(target),
+                Instruction::Jcc(_, target) => pending.extend([target, next]),
+                Instruction::Ret | Instruction::Pchl | Instruction::Hlt => {}
+                _ => pending.push(next),
+            }
+        }
+    }
+
+    for line in &listing.lines {
+        let Line::Instruction { address, bytes } = line else {
+            continue;
+        };
+        let Some(Instruction::Call(target) | Instruction::Ccc(_, target)) =
+            Instruction::decode(bytes)
+        else {
+            continue;
+        };
+        let Some(&function) = owners.get(&target) else {
+            continue;
+        };
+        warnings.push(Warning {
+            address: *address,
+            message: format!(
+                "{} to {} enters the middle of {}",
+                opcodes::lookup(bytes[0]).mnemonic,
+                name(listing, target),
+                name(listing, function)
+            ),
+        });
+    }
+}
+
+/// Stores to an address inside the image, which is read-only in a rom.
+fn writes_to_image(listing: &Listing, warnings: &mut Vec<Warning>) {
+    let (Some(first), Some(last)) = (listing.lines.first(), listing.lines.last()) else {
+        return;
+    };
+    let image = usize::from(first.address())..usize::from(last.address()) + last.length();
+    for line in &listing.lines {
+        let Line::Instruction { address, bytes } = line else {
+            continue;
+        };
+        let Some(Instruction::Sta(target) | Instruction::Shld(target)) = Instruction::decode(bytes)
+        else {
+            continue;
+        };
+        if image.contains(&usize::from(target)) {
+            warnings.push(Warning {
+                address: *address,
+                message: format!(
+                    "{} writes to {}, inside the rom",
+                    opcodes::lookup(bytes[0]).mnemonic,
+                    name(listing, target)
+                ),
+            });
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Database of known machines, mapping the files of a MAME romset to their addresses.
+
+use anyhow::{anyhow, bail, Context};
+
+/// Built-in machines, in the format of a machine database file.
+const BUILTIN: &str = "
+# Space Invaders
+invaders  invaders.h@0x0000 invaders.g@0x0800 invaders.f@0x1000 invaders.e@0x1800
+# Space Invaders Part II
+invadpt2  pv01@0x0000 pv02@0x0800 pv03@0x1000 pv04@0x1800 pv05@0x4000
+";
+
+/// A rom file of a machine.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RomFile {
+    /// Name of the file inside the romset.
+    pub name: String,
+    /// CPU address the file is loaded at.
+    pub address: u16,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Machine {
+    pub name: String,
+    pub roms: Vec<RomFile>,
+}
+
+/// Collection of machines, from the built-in list and user database files.
+#[derive(Clone, Debug)]
+pub struct Database {
+    machines: Vec<Machine>,
+}
+
+impl Database {
+    /// Database of the machines known out of the box.
+    ///
+    /// # Panics
+    ///
+    /// Never, the built-in database is valid.
+    #[must_use]
+    pub fn builtin() -> Self {
+        let mut database = Self {
+            machines: Vec::new(),
+        };
+        database
+            .extend(BUILTIN)
+            .expect("the built-in machine database is valid");
+        database
+    }
+
+    /// Add the machines of a database file, replacing known machines with the same name.
+    ///
+    /// Each line holds the name of a machine followed by its rom files as `name@address`.
+    /// Empty lines and lines starting with `#` are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Fails on lines that are not a valid machine; the machines before them are kept.
+    pub fn extend(&mut self, text: &str) -> anyhow::Result<()> {
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let machine =
+                parse_machine(line).with_context(|| format!("parsing line {}", line_index + 1))?;
+            self.machines.retain(|m| m.name != machine.name);
+            self.machines.push(machine);
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Machine> {
+        self.machines.iter().find(|m| m.name == name)
+    }
+}
+
+fn parse_machine(line: &str) -> anyhow::Result<Machine> {
+    let mut fields = line.split_whitespace();
+    // Lines are never empty.
+    let name = fields.next().unwrap_or_default().to_owned();
+
+    let roms = fields
+        .map(|field| {
+            let (file, address) = field
+                .split_once('@')
+                .ok_or_else(|| anyhow!("expected \"file@address\", found \"{field}\""))?;
+            let digits = address.strip_prefix("0x").unwrap_or(address);
+            let address = u16::from_str_radix(digits, 16)
+                .with_context(|| format!("parsing address \"{address}\""))?;
+            Ok(RomFile {
+                name: file.to_owned(),
+                address,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if roms.is_empty() {
+        bail!("machine \"{name}\" has no rom files");
+    }
+
+    Ok(Machine { name, roms })
+}
@@ -20,350 +20,2627 @@ const COLOR_BLUE: &str = "\x1b[34m";
 const COLOR_PURPLE: &str = "\x1b[35m";
 const COLOR_GRAY: &str = "\x1b[37m";
 
+/// Escape sequences coloring the listing, empty when colors are disabled.
+#[derive(Clone, Copy)]
+struct Palette {
+    reset: &'static str,
+    bold: &'static str,
+    red: &'static str,
+    blue: &'static str,
+    purple: &'static str,
+    gray: &'static str,
+}
+
+impl Palette {
+    const COLORS: Self = Self {
+        reset: COLOR_RESET,
+        bold: COLOR_BOLD,
+        red: COLOR_RED,
+        blue: COLOR_BLUE,
+        purple: COLOR_PURPLE,
+        gray: COLOR_GRAY,
+    };
+
+    const PLAIN: Self = Self {
+        reset: "",
+        bold: "",
+        red: "",
+        blue: "",
+        purple: "",
+        gray: "",
+    };
+}
+
 /// Print formatted text on stderr with an "error: " prefix.
-macro_rules! error {
-    ($exitcode:expr, $($arg:tt)*) => {{
+macro_rules! report {
+    ($($arg:tt)*) => {{
         // "error: " displayed in red and bold font.
         eprintln!("{COLOR_RED}{COLOR_BOLD}error:{COLOR_RESET} {}", format!($($arg)*));
+    }}
+}
+
+/// Print formatted text on stderr with a "note: " prefix.
+macro_rules! note {
+    ($($arg:tt)*) => {{
+        // "note: " displayed in blue and bold font.
+        eprintln!("{COLOR_BLUE}{COLOR_BOLD}note:{COLOR_RESET} {}", format!($($arg)*));
+    }}
+}
+
+/// Print formatted text on stderr with an "error: " prefix and exit.
+macro_rules! error {
+    ($exitcode:expr, $($arg:tt)*) => {{
+        report!($($arg)*);
         std::process::exit($exitcode);
     }}
 }
 
+/// Random states the sequences of the equivalent subcommand are run from.
+const EQUIVALENCE_TRIALS: u64 = 1000;
+
+/// Jumps and calls landing in the rom from which another origin than 0 is suggested.
+const MIN_ORIGIN_REFERENCES: usize = 4;
+
+/// Time between two checks of the files watched by --live.
+const LIVE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Problems reported by --strict when --max-errors is not given.
+const MAX_ERRORS: usize = 20;
+
+/// Help text printed with --help, or when no arguments are given.
+const USAGE: &str = concat!(
+    "usage: ",
+    env!("CARGO_PKG_NAME"),
+    " [OPTIONS] <FILE|->\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " table [--format text|csv|markdown]\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " info <OPCODE|MNEMONIC>\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " decode [OPTIONS] <BYTES>...\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " encode <INSTRUCTION>...\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " verify-dialect --assembler COMMAND [OPTIONS] <FILE|->\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " r2\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " symbols [--sort address|name] [OPTIONS] <FILE|->\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " metrics [--json] [OPTIONS] <FILE|->\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " strings [--min-length N] [--emit-labels] [OPTIONS] <FILE|->\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " alternates --at ADDRESS [--offsets N] [OPTIONS] <FILE|->\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " slice --at ADDRESS [--output FILE] [OPTIONS] <FILE|->\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " extract --range START..END [--pad-to SIZE] [--fill BYTE] [--checksum OFFSET[:SUM]] [--output FILE] [OPTIONS] <FILE|->\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " timing --at ADDRESS [OPTIONS] <FILE|->\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " equivalent <ORIGINAL> <REPLACEMENT>\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " patch --at ADDRESS [--emit-patch ips|text] [--output FILE] <INSTRUCTIONS> <FILE|->\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " compare-source [OPTIONS] <SOURCE> <FILE|->\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " similarity [OPTIONS] <FILE> <FILE>\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " lint [OPTIONS] <FILE|->\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " rename --project FILE [--dialect intel|zasm|gas] [OPTIONS] <OLD> <NEW> <FILE|->\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " batch --output-dir DIR [OPTIONS] <FILE|DIR>...\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " timeline --trace FILE [OPTIONS] <FILE|->\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " --list-passes\n",
+    "       ",
+    env!("CARGO_PKG_NAME"),
+    " --help | --version\n",
+    "\n",
+    "options:\n",
+    "  --explain           describe each instruction\n",
+    "  --teach             also explain how each flag is set\n",
+    "  --labels            separate code from data and label addresses\n",
+    "  --member NAME       file to read from a zip archive\n",
+    "  --machine NAME      read the romset of a known machine\n",
+    "  --machine-db FILE   add machines to the database\n",
+    "  --profile cpm       load a CP/M program at 0x100 and annotate system calls\n",
+    "  --org ADDRESS       load the rom at ADDRESS and start from there\n",
+    "  --base-file-offset N skip a header of N bytes, loading the rest at --org\n",
+    "  --rst-params LIST   skip N parameter bytes after RST V, as V:N,..., implies --labels\n",
+    "  --call-params LIST  same after calls to ADDRESS, as ADDRESS:N or ADDRESS:..END,...\n",
+    "  --project FILE      name labels after the project file\n",
+    "  --label-hook CMD    rename labels to the names CMD prints for them, implies --labels\n",
+    "  --appendix LIST     end with symbols, xrefs and/or stats, implies --labels\n",
+    "  --function NAME     list only the function with this label, implies --labels\n",
+    "  --around ADDR:±N    list only the N bytes around ADDR, implies --labels\n",
+    "  --flag-sources      comment conditions with the instruction setting their flag\n",
+    "  --pointers          comment accesses through register pairs with what they reach\n",
+    "  --decimal           comment immediates with their value in decimal\n",
+    "  --passes LIST       run these analysis passes in order, implies --labels\n",
+    "  --charset FILE      map bytes to characters for strings and immediates\n",
+    "  --apply-patch FILE  patch the image with an IPS or BPS file first\n",
+    "  --no-hashes         omit the checksums of the image\n",
+    "  --input-format FMT  binary (default), hex text or ihex records\n",
+    "  --format FORMAT     listing (default), asm source, objdump layout, digest, svg or tokens\n",
+    "  --output-dir DIR    write each --format given to a file in DIR\n",
+    "  --strict            report the problems of a damaged image instead of listing it\n",
+    "  --max-errors N      report at most N problems, implies --strict\n",
+    "  --fail-on-truncated exit with an error at an instruction cut off by the end of the image\n",
+    "  --forbid LIST       report the forbidden instructions instead of listing the code\n",
+    "  --provenance        start with the tool and command used to make the listing\n",
+    "  --live              print the listing again when the rom or project file changes\n",
+    "  --deterministic     print identical output on every run, without colors\n",
+    "  --spaces            indent source with spaces instead of tabs\n",
+    "  --operand-column N  column at which the operands start\n",
+    "  --width N           wrap lines to N columns, the terminal width by default\n",
+    "  --links URL         link jump and call targets to URL, with {address} and {name}\n",
+    "  --dialect NAME      syntax of the source: intel (default), zasm or gas\n",
+    "  --macros            roll repeated instruction sequences into macros in the source\n",
+    "  --assembler COMMAND assembler run on {source} to write {output}\n",
+    "  --threads N         decode the listing on N threads, past 64 KiB for large dumps\n",
+    "  --trace FILE        addresses run by an emulator, one per line, for timeline\n",
+);
+
+mod alternates;
+mod appendix;
+mod batch;
+mod digest;
+mod divergence;
+mod forbid;
+mod heatmap;
+mod hook;
+mod info;
+mod lint;
+mod metrics;
+mod objdump;
+mod passes;
+mod r2;
+mod similarity;
+mod symbols;
+mod table;
+mod timeline;
+mod timing;
+mod verify;
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::IsTerminal;
+
+use intel_8080_disassembler::analysis::{self, Line};
+use intel_8080_disassembler::layout::{self, Layout};
+use intel_8080_disassembler::syntax::Syntax;
+use intel_8080_disassembler::{
+    asm, charset, conditions, cpm, decode, effects, encode, explain, hash, input, machines,
+    opcodes, origin, padding, patch, pointers, project, source, strings, tokens, DisasmError,
+};
+use passes::Pass;
+
 fn main() {
-    let mut args = std::env::args();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("--help" | "-h") => print!("{USAGE}"),
+        Some("--version" | "-V") => {
+            println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        }
+        Some("table") => print_table(&args[1..]),
+        Some("info") => {
+            let Some(arg) = args.get(1) else {
+                error!(exitcode::USAGE, "expected an opcode or a mnemonic");
+            };
+            no_more(&args[2..]);
+            let query = info::Query::parse(arg).unwrap_or_else(|| {
+                error!(
+                    exitcode::USAGE,
+                    "\"{arg}\" is neither an opcode nor a mnemonic"
+                )
+            });
+            printed(info::print(&mut std::io::stdout().lock(), &query));
+        }
+        Some("decode") => decode(&args[1..]),
+        Some("encode") => {
+            if args.len() < 2 {
+                error!(exitcode::USAGE, "expected an instruction to encode");
+            }
+            for instruction in &args[1..] {
+                let bytes = encode::encode(instruction).unwrap_or_else(|e| {
+                    error!(
+                        exitcode::DATAERR,
+                        "{:?}",
+                        anyhow!(e).context(format!("encoding \"{instruction}\""))
+                    )
+                });
+                let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+                println!("{}", hex.join(" "));
+            }
+        }
+        Some("verify-dialect") => verify_dialect(&args[1..]),
+        Some("symbols") => {
+            let (options, paths) = parse_options(&args[1..]);
+            let [rom_file_path] = paths.as_slice() else {
+                error!(exitcode::USAGE, "expected a single rom file");
+            };
+            let image = load_image(rom_file_path, &options);
+            let listing = analyze(&image, &options);
+            printed(symbols::print(
+                &mut std::io::stdout().lock(),
+                &listing,
+                image.bytes.len(),
+                options.sort,
+            ));
+        }
+        Some("metrics") => print_metrics(&args[1..]),
+        Some("strings") => print_strings(&args[1..]),
+        Some("alternates") => print_alternates(&args[1..]),
+        Some("slice") => slice(&args[1..]),
+        Some("extract") => extract(&args[1..]),
+        Some("timing") => print_timing(&args[1..]),
+        Some("equivalent") => check_equivalence(&args[1..]),
+        Some("patch") => patch(&args[1..]),
+        Some("compare-source") => compare_source(&args[1..]),
+        Some("similarity") => print_similarity(&args[1..]),
+        Some("lint") => lint(&args[1..]),
+        Some("rename") => rename(&args[1..]),
+        Some("batch") => batch(&args[1..]),
+        Some("timeline") => print_timeline(&args[1..]),
+        Some("--list-passes") => {
+            no_more(&args[1..]);
+            printed(passes::list(&mut std::io::stdout().lock()));
+        }
+        Some("r2") => {
+            no_more(&args[1..]);
+            if let Err(e) = r2::serve() {
+                error!(
+                    exitcode::IOERR,
+                    "{:?}",
+                    anyhow!(e).context("serving radare2")
+                );
+            }
+        }
+        Some(_) => {
+            let (options, paths) = parse_options(&args);
+            let [rom_file_path] = paths.as_slice() else {
+                error!(exitcode::USAGE, "expected a single rom file");
+            };
+            disassemble(rom_file_path, &options);
+        }
+        None => {
+            print!("{USAGE}");
+            std::process::exit(exitcode::USAGE);
+        }
+    }
+}
+
+/// Print the opcode table in the style given with --format.
+fn print_table(args: &[String]) {
+    let style = match args.first().map(String::as_str) {
+        None => table::Style::Text,
+        Some("--format") => {
+            no_more(&args[2.min(args.len())..]);
+            args.get(1)
+                .and_then(|name| table::Style::from_name(name))
+                .unwrap_or_else(|| {
+                    error!(
+                        exitcode::USAGE,
+                        "expected one of text, csv or markdown after --format"
+                    )
+                })
+        }
+        Some(arg) => error!(exitcode::USAGE, "unexpected argument \"{arg}\""),
+    };
+    printed(table::print(&mut std::io::stdout().lock(), style));
+}
+
+/// Exit if arguments are left after those a subcommand takes, rather than ignoring them.
+fn no_more(args: &[String]) {
+    if let Some(arg) = args.first() {
+        error!(exitcode::USAGE, "unexpected argument \"{arg}\"");
+    }
+}
+
+/// Print the listing of bytes given on the command line.
+fn decode(args: &[String]) {
+    let (options, bytes) = parse_options(args);
+    if bytes.is_empty() {
+        error!(exitcode::USAGE, "expected the bytes to decode");
+    }
+    let bytes = input::parse_hex(&bytes.join(" ")).unwrap_or_else(|e| {
+        error!(
+            exitcode::USAGE,
+            "{:?}",
+            anyhow!(e).context("parsing bytes to decode")
+        )
+    });
+    let image = input::Image::from_file("(command line)", bytes);
+    let listing = options.labels.then(|| analyze(&image, &options));
+    printed(print_listing(
+        &mut std::io::stdout().lock(),
+        &image,
+        listing.as_ref(),
+        &options,
+    ));
+}
+
+/// Exit if printing failed, unless the output was closed early, as by `head`.
+fn printed(result: std::io::Result<()>) {
+    match result {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => error!(
+            exitcode::IOERR,
+            "{:?}",
+            anyhow!(e).context("printing the listing")
+        ),
+        _ => {}
+    }
+}
+
+/// Parse the options of the listing, returning them with the remaining arguments.
+#[allow(clippy::too_many_lines)]
+fn parse_options(args: &[String]) -> (Options, Vec<&str>) {
+    let mut options = Options::default();
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" | "-h" => {
+                print!("{USAGE}");
+                std::process::exit(exitcode::OK);
+            }
+            "--explain" => options.explain = true,
+            "--teach" => {
+                options.explain = true;
+                options.teach = true;
+            }
+            "--no-hashes" => options.no_hashes = true,
+            "--labels" => options.labels = true,
+            "--input-format" => {
+                let expected = "binary, hex or ihex";
+                options.input_format = value(&mut args, arg, expected, input::Format::from_name);
+            }
+            "--format" => {
+                let format = value(
+                    &mut args,
+                    arg,
+                    "listing, asm, objdump, digest, svg or tokens",
+                    Format::from_name,
+                );
+                options.formats.retain(|&f| f != format);
+                options.formats.push(format);
+            }
+            "--dialect" => {
+                let expected = "intel, zasm or gas";
+                options.dialect = Some(value(&mut args, arg, expected, asm::Dialect::from_name));
+            }
+            "--spaces" => options.spaces = true,
+            "--links" => options.links = Some(parsed(&mut args, arg, "a URL")),
+            "--decimal" => options.decimal = true,
+            "--macros" => options.macros = true,
+            "--json" => options.json = true,
+            "--pointers" => {
+                options.pointers = true;
+                options.labels = true;
+            }
+            "--passes" => {
+                let expected = "pass names separated by commas, or none";
+                options.passes = Some(value(&mut args, arg, expected, Pass::from_names));
+                options.labels = true;
+            }
+            "--flag-sources" => {
+                options.flag_sources = true;
+                options.labels = true;
+            }
+            "--deterministic" => options.deterministic = true,
+            "--live" => options.live = true,
+            "--provenance" => options.provenance = true,
+            "--strict" => options.strict = true,
+            "--fail-on-truncated" => options.fail_on_truncated = true,
+            "--max-errors" => {
+                options.strict = true;
+                options.max_errors = Some(parsed(&mut args, arg, "a number"));
+            }
+            "--forbid" => {
+                options.forbid = value(
+                    &mut args,
+                    arg,
+                    "a list of mnemonics, undocumented, io or interrupts",
+                    forbid::Rule::from_names,
+                );
+            }
+            "--width" => options.width = Some(parsed(&mut args, arg, "a number of columns")),
+            "--operand-column" => options.operand_column = Some(parsed(&mut args, arg, "a column")),
+            "--profile" => {
+                options.profile = Some(value(&mut args, arg, "cpm", Profile::from_name));
+                options.labels = true;
+            }
+            "--base-file-offset" => {
+                let parse = |a: &str| {
+                    a.parse()
+                        .ok()
+                        .or_else(|| encode::parse_number(a).ok().map(usize::from))
+                };
+                options.base_file_offset = Some(value(&mut args, arg, "an offset", parse));
+            }
+            "--org" => {
+                let parse = |a: &str| encode::parse_number(a).ok();
+                options.org = Some(value(&mut args, arg, "an address", parse));
+            }
+            "--rst-params" => {
+                let parse = |a: &str| {
+                    parse_parameters(a, |vector| vector.parse().ok().filter(|&v: &u8| v < 8))
+                };
+                options.parameters.restarts =
+                    value(&mut args, arg, "a list of VECTOR:BYTES", parse);
+                options.labels = true;
+            }
+            "--call-params" => {
+                let parse = |a: &str| parse_parameters(a, |a| encode::parse_number(a).ok());
+                options.parameters.calls = value(&mut args, arg, "a list of ADDRESS:BYTES", parse);
+                options.labels = true;
+            }
+            "--min-length" => options.min_length = Some(parsed(&mut args, arg, "a length")),
+            "--project" => options.project = Some(parsed(&mut args, arg, "a file")),
+            "--charset" => {
+                let path: String = parsed(&mut args, arg, "a file");
+                options.charset = Some(load_charset(&path));
+            }
+            "--appendix" => {
+                let expected = "symbols, xrefs or stats separated by commas";
+                options.appendix = Some(value(
+                    &mut args,
+                    arg,
+                    expected,
+                    appendix::Sections::from_names,
+                ));
+                options.labels = true;
+            }
+            "--at" => {
+                let parse = |a: &str| encode::parse_number(a).ok();
+                options.at = Some(value(&mut args, arg, "an address", parse));
+            }
+            "--apply-patch" => options.patches.push(parsed(&mut args, arg, "a file")),
+            "--emit-patch" => {
+                let format = value(&mut args, arg, "ips or text", patch::Format::from_name);
+                options.emit_patch = Some(format);
+            }
+            "--output" => options.output = Some(parsed(&mut args, arg, "a file")),
+            "--range" => {
+                let parse = |a: &str| {
+                    let (start, end) = a.split_once("..")?;
+                    (!start.is_empty()).then(|| (start.to_owned(), end.to_owned()))
+                };
+                options.range = Some(value(&mut args, arg, "a range START..END", parse));
+            }
+            "--pad-to" => {
+                let parse = |a: &str| {
+                    a.parse()
+                        .ok()
+                        .or_else(|| encode::parse_number(a).ok().map(usize::from))
+                };
+                options.pad_to = Some(value(&mut args, arg, "a size", parse));
+            }
+            "--fill" => {
+                let parse = |a: &str| u8::try_from(encode::parse_number(a).ok()?).ok();
+                options.fill = Some(value(&mut args, arg, "a byte", parse));
+            }
+            "--checksum" => {
+                options.checksum = Some(value(&mut args, arg, "an offset", parse_checksum));
+            }
+            "--function" => {
+                options.function = Some(parsed(&mut args, arg, "a function name"));
+                options.labels = true;
+            }
+            "--around" => {
+                let expected = "an address and a distance, like 0x1a32:±32";
+                options.around = Some(value(&mut args, arg, expected, parse_around));
+                options.labels = true;
+            }
+            "--output-dir" => options.output_dir = Some(parsed(&mut args, arg, "a directory")),
+            "--offsets" => options.offsets = Some(parsed(&mut args, arg, "a number")),
+            "--emit-labels" => options.emit_labels = true,
+            "--sort" => {
+                let expected = "address or name";
+                options.sort = value(&mut args, arg, expected, symbols::Sort::from_name);
+            }
+            "--assembler" => options.assembler = Some(parsed(&mut args, arg, "a command")),
+            "--threads" => options.threads = Some(parsed(&mut args, arg, "a number of threads")),
+            "--trace" => options.trace = Some(parsed(&mut args, arg, "a file")),
+            "--label-hook" => {
+                options.label_hook = Some(parsed(&mut args, arg, "a command"));
+                options.labels = true;
+            }
+            "--member" => options.member = Some(parsed(&mut args, arg, "a file name")),
+            "--machine" => options.machine = Some(parsed(&mut args, arg, "a machine name")),
+            "--machine-db" => options
+                .machine_databases
+                .push(parsed(&mut args, arg, "a file")),
+            flag if flag.starts_with("--") => {
+                error!(exitcode::USAGE, "unknown option \"{flag}\"")
+            }
+            arg => positional.push(arg),
+        }
+    }
+    if options.width.is_none()
+        && !options.deterministic
+        && options.output_dir.is_none()
+        && std::io::stdout().is_terminal()
+    {
+        options.width = std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok());
+    }
+    (options, positional)
+}
+
+/// Parse the value following an option with its `FromStr` implementation.
+fn parsed<'a, T: std::str::FromStr>(
+    args: &mut impl Iterator<Item = &'a String>,
+    option: &str,
+    expected: &str,
+) -> T {
+    value(args, option, expected, |s| s.parse().ok())
+}
+
+/// Parse an address and the distance to it of the lines around, like `0x1a32:±32`.
+fn parse_around(text: &str) -> Option<(u16, usize)> {
+    let (address, distance) = text.split_once(':')?;
+    let distance = distance
+        .strip_prefix('±')
+        .or_else(|| distance.strip_prefix("+-"))
+        .unwrap_or(distance);
+    let address = encode::parse_number(address).ok()?;
+    let distance = encode::parse_number(distance).ok()?;
+    Some((address, usize::from(distance)))
+}
+
+/// Parse the offset of a checksum byte, followed by the sum of the bytes after a colon,
+/// 0 by default.
+fn parse_checksum(text: &str) -> Option<(usize, u8)> {
+    let (offset, sum) = text.split_once(':').unwrap_or((text, "0"));
+    let offset = encode::parse_number(offset).ok()?;
+    let sum = u8::try_from(encode::parse_number(sum).ok()?).ok()?;
+    Some((usize::from(offset), sum))
+}
+
+/// Parse the inline parameters following restarts or calls, as comma-separated pairs of
+/// a vector or address and a number of bytes or a terminator, like `5:1,0x0150:..0x24`.
+fn parse_parameters<K: Ord>(
+    text: &str,
+    parse_key: impl Fn(&str) -> Option<K>,
+) -> Option<BTreeMap<K, analysis::Inline>> {
+    text.split(',')
+        .map(|pair| {
+            let (key, inline) = pair.split_once(':')?;
+            Some((parse_key(key)?, analysis::Inline::from_text(inline)?))
+        })
+        .collect()
+}
+
+/// Parse the value following an option, exiting with what was `expected` when it is invalid.
+fn value<'a, T>(
+    args: &mut impl Iterator<Item = &'a String>,
+    option: &str,
+    expected: &str,
+    parse: impl FnOnce(&str) -> Option<T>,
+) -> T {
+    args.next()
+        .and_then(|v| parse(v))
+        .unwrap_or_else(|| error!(exitcode::USAGE, "expected {expected} after {option}"))
+}
+
+/// Check that the generated source assembles back to the bytes of the rom.
+fn verify_dialect(args: &[String]) {
+    let (options, paths) = parse_options(args);
+    let [rom_file_path] = paths.as_slice() else {
+        error!(exitcode::USAGE, "expected a single rom file");
+    };
+    let Some(command) = &options.assembler else {
+        error!(
+            exitcode::USAGE,
+            "expected an assembler command with --assembler"
+        );
+    };
+    let image = load_image(rom_file_path, &options);
+    let dialect = options.dialect.unwrap_or(asm::INTEL);
+    match verify::verify(&image, &dialect, command) {
+        Ok(verify::Verdict::Identical) => {
+            println!("{} source assembles to identical bytes", dialect.name);
+        }
+        Ok(verify::Verdict::Different {
+            first_offset,
+            count,
+            assembled_length,
+        }) => error!(
+            exitcode::DATAERR,
+            "{} source assembles to different bytes: {count} bytes differ, the first at \
+             0x{first_offset:04x} (0x{assembled_length:x} bytes assembled, 0x{:x} expected)",
+            dialect.name,
+            image.bytes.len()
+        ),
+        Err(e) => error!(
+            exitcode::SOFTWARE,
+            "{:?}",
+            e.context("assembling generated source")
+        ),
+    }
+}
+
+/// Print the decodings starting at each of the offsets following an address.
+fn print_alternates(args: &[String]) {
+    let (options, paths) = parse_options(args);
+    let [rom_file_path] = paths.as_slice() else {
+        error!(exitcode::USAGE, "expected a single rom file");
+    };
+    let Some(address) = options.at else {
+        error!(exitcode::USAGE, "expected an address with --at");
+    };
+    let image = load_image(rom_file_path, &options);
+    printed(alternates::print(
+        &mut std::io::stdout().lock(),
+        &image.bytes,
+        usize::from(address),
+        options.offsets.unwrap_or(alternates::OFFSETS),
+    ));
+}
+
+/// Assemble instructions over the image, writing the patched image or the patch.
+fn patch(args: &[String]) {
+    let (options, positional) = parse_options(args);
+    let [instructions, rom_file_path] = positional.as_slice() else {
+        error!(
+            exitcode::USAGE,
+            "expected instructions and a single rom file"
+        );
+    };
+    let Some(address) = options.at else {
+        error!(exitcode::USAGE, "expected the address to patch with --at");
+    };
+    let image = load_image(rom_file_path, &options);
+    let code = assemble(instructions);
+    let Some((file, start)) = file_offset(&image, address, &options) else {
+        error!(
+            exitcode::DATAERR,
+            "0x{address:04x} is not loaded from a file"
+        );
+    };
+    let Some(target) = file.bytes.get(start..start + code.len()) else {
+        error!(
+            exitcode::DATAERR,
+            "the {} bytes at 0x{address:04x} go past the end of {}",
+            code.len(),
+            file.name
+        );
+    };
+    let mut patched = file.bytes.clone();
+    patched[start..start + code.len()].copy_from_slice(&code);
+    let changes = patch::changes(target, &code)
+        .into_iter()
+        .map(|change| patch::Change {
+            offset: change.offset + start,
+            ..change
+        })
+        .collect::<Vec<_>>();
 
-    // Read only the first argument, ignore the others.
-    // Error if the first argument is missing.
-    let rom_file_path;
-    if let Some(arg) = args.nth(1) {
-        rom_file_path = arg;
+    let output = match options.emit_patch {
+        None => patched,
+        Some(patch::Format::Text) => patch::to_text(&changes).into_bytes(),
+        Some(patch::Format::Ips) => patch::to_ips(&changes, &file.bytes).unwrap_or_else(|e| {
+            error!(
+                exitcode::DATAERR,
+                "{:?}",
+                anyhow!(e).context("writing IPS patch")
+            )
+        }),
+    };
+    let result = match &options.output {
+        Some(path) => std::fs::write(path, output),
+        None => std::io::Write::write_all(&mut std::io::stdout(), &output),
+    };
+    if let Err(e) = result {
+        error!(
+            exitcode::IOERR,
+            "{:?}",
+            anyhow!(e).context("writing the patch")
+        );
+    }
+}
+
+/// File an address of the image was loaded from, with the offset of the address in it.
+fn file_offset<'a>(
+    image: &'a input::Image,
+    address: u16,
+    options: &Options,
+) -> Option<(&'a input::File, usize)> {
+    if options.machine.is_some() {
+        let segment = image.segments.iter().find(|segment| {
+            let start = usize::from(segment.address);
+            (start..start + segment.length).contains(&usize::from(address))
+        })?;
+        let file = image.files.iter().find(|file| file.name == segment.name)?;
+        return Some((file, usize::from(address - segment.address)));
+    }
+    let loaded = usize::from(address).checked_sub(usize::from(options.origin().unwrap_or(0)))?;
+    Some((
+        image.files.first()?,
+        loaded + options.base_file_offset.unwrap_or(0),
+    ))
+}
+
+/// Encode instructions separated by ";", exiting when one is invalid.
+fn assemble(sequence: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for instruction in sequence.split(';').filter(|i| !i.trim().is_empty()) {
+        bytes.extend(encode::encode(instruction).unwrap_or_else(|e| {
+            error!(
+                exitcode::DATAERR,
+                "{:?}",
+                anyhow!(e).context(format!("encoding \"{instruction}\""))
+            )
+        }));
+    }
+    bytes
+}
+
+/// Compare what two sequences of instructions separated by ";" compute, exiting with 1
+/// when they differ.
+fn check_equivalence(args: &[String]) {
+    let [original, replacement] = args else {
+        error!(exitcode::USAGE, "expected two sequences of instructions");
+    };
+    let differences = effects::compare(
+        &assemble(original),
+        &assemble(replacement),
+        EQUIVALENCE_TRIALS,
+    )
+    .unwrap_or_else(|e| {
+        error!(
+            exitcode::DATAERR,
+            "{:?}",
+            anyhow!(e).context("running the sequences")
+        )
+    });
+
+    if differences.is_empty() {
+        println!("equivalent from {EQUIVALENCE_TRIALS} random states");
+        return;
+    }
+    for difference in &differences {
+        println!("{difference}");
+    }
+    std::process::exit(1);
+}
+
+/// Report where a rom diverges from the source it is thought to be assembled from, exiting
+/// with 1 when it does.
+fn compare_source(args: &[String]) {
+    let (options, paths) = parse_options(args);
+    let [source_path, rom_file_path] = paths.as_slice() else {
+        error!(exitcode::USAGE, "expected a source file and a rom file");
+    };
+    let result = std::fs::read_to_string(source_path)
+        .map_err(|e| anyhow!(e))
+        .and_then(|text| source::assemble(&text));
+    let statements = result.unwrap_or_else(|e| {
+        error!(
+            exitcode::DATAERR,
+            "{:?}",
+            e.context(format!("assembling \"{source_path}\""))
+        )
+    });
+    let image = load_image(rom_file_path, &options);
+
+    match divergence::print(&mut std::io::stdout().lock(), &image.bytes, &statements) {
+        Ok(0) => println!("the rom matches the source"),
+        // A divergence was being printed when writing failed.
+        result => {
+            printed(result.map(drop));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Write the bytes of a function to a file, or print its source.
+fn slice(args: &[String]) {
+    let (options, paths) = parse_options(args);
+    let [rom_file_path] = paths.as_slice() else {
+        error!(exitcode::USAGE, "expected a single rom file");
+    };
+    let Some(address) = options.at else {
+        error!(
+            exitcode::USAGE,
+            "expected the address of a function with --at"
+        );
+    };
+    let image = load_image(rom_file_path, &options);
+    let listing = analyze(&image, &options);
+    let Some(range) = listing.function_range(address) else {
+        error!(
+            exitcode::DATAERR,
+            "no function found at 0x{address:04x} by following the flow of execution"
+        );
+    };
+
+    let Some(path) = &options.output else {
+        let layout = options.layout(Layout::SOURCE);
+        let dialect = options.dialect.unwrap_or(asm::INTEL);
+        let source = asm::render(&listing.slice(range), &dialect, &layout);
+        printed(std::io::Write::write_all(
+            &mut std::io::stdout(),
+            source.as_bytes(),
+        ));
+        return;
+    };
+    if let Err(e) = std::fs::write(path, &image.bytes[range]) {
+        error!(
+            exitcode::CANTCREAT,
+            "{:?}",
+            anyhow!(e).context(format!("writing \"{path}\""))
+        );
+    }
+}
+
+/// Write a range of the image to a file, padded and with its checksum fixed if asked to.
+fn extract(args: &[String]) {
+    let (options, paths) = parse_options(args);
+    let [rom_file_path] = paths.as_slice() else {
+        error!(exitcode::USAGE, "expected a single rom file");
+    };
+    let Some((start, end)) = &options.range else {
+        error!(
+            exitcode::USAGE,
+            "expected the range to extract with --range"
+        );
+    };
+    let image = load_image(rom_file_path, &options);
+
+    // Names are only looked up, following the flow of execution, when the range has one.
+    let mut listing = None;
+    let mut resolve = |bound: &str| {
+        if let Ok(address) = encode::parse_number(bound) {
+            return usize::from(address);
+        }
+        let listing = listing.get_or_insert_with(|| analyze(&image, &options));
+        address_of(listing, bound).map_or_else(
+            || error!(exitcode::DATAERR, "no label or symbol named \"{bound}\""),
+            usize::from,
+        )
+    };
+    let start = resolve(start);
+    let end = if end.is_empty() {
+        image.bytes.len()
     } else {
-        println!("usage: {} <FILE>", env!("CARGO_PKG_NAME"));
-        std::process::exit(exitcode::USAGE);
+        resolve(end)
+    };
+    let Some(bytes) = image.bytes.get(start..end) else {
+        error!(
+            exitcode::DATAERR,
+            "the range 0x{start:04x}..0x{end:04x} is not within the {} bytes of the image",
+            image.bytes.len()
+        );
+    };
+
+    let mut bytes = bytes.to_vec();
+    if let Some(size) = options.pad_to {
+        if bytes.len() > size {
+            error!(
+                exitcode::DATAERR,
+                "the {} bytes extracted do not fit in {size}",
+                bytes.len()
+            );
+        }
+        bytes.resize(size, options.fill.unwrap_or(0xFF));
+    }
+    if let Some((offset, sum)) = options.checksum {
+        if offset >= bytes.len() {
+            error!(
+                exitcode::DATAERR,
+                "the checksum offset 0x{offset:04x} is past the {} bytes extracted",
+                bytes.len()
+            );
+        }
+        bytes[offset] = 0;
+        let total = bytes.iter().fold(0_u8, |total, &b| total.wrapping_add(b));
+        bytes[offset] = sum.wrapping_sub(total);
     }
 
-    // Read the file into a vector.
-    let rom = match std::fs::read(rom_file_path).map_err(|e| anyhow!(e)) {
-        Ok(r) => r,
-        Err(e) => {
-            error!(exitcode::IOERR, "{:?}", e.context("opening rom file"));
-        }
-    };
-
-    let mut rom_iter = rom.iter().enumerate();
-    while let Some((address, first_byte)) = rom_iter.next() {
-        print!("{address:04x}  {first_byte:02x} ");
-
-        // Translate the instruction to assembly.
-        let (instruction_length, text, additional_text) = match first_byte {
-            0x00 | 0x08 | 0x10 | 0x18 | 0x20 | 0x28 | 0x30 | 0x38 => (1, "NOP", ""),
-            0x01 => (3, "LXI", "B"),
-            0x02 => (1, "STAX", "B"),
-            0x03 => (1, "INX", "B"),
-            0x04 => (1, "INR", "B"),
-            0x05 => (1, "DCR", "B"),
-            0x06 => (2, "MVI", "B"),
-            0x07 => (1, "RLC", ""),
-            0x09 => (1, "DAB", "D"),
-            0x0A => (1, "LDAX", "B"),
-            0x0B => (1, "DCX", "B"),
-            0x0C => (1, "INR", "C"),
-            0x0D => (1, "DCR", "C"),
-            0x0E => (2, "MVI", "C"),
-            0x0F => (1, "RRC", ""),
-            0x11 => (3, "LXI", "D"),
-            0x12 => (1, "STAX", "D"),
-            0x13 => (1, "INX", "D"),
-            0x14 => (1, "INR", "D"),
-            0x15 => (1, "DCR", "D"),
-            0x16 => (2, "MVI", "D"),
-            0x17 => (1, "RAL", ""),
-            0x19 => (1, "DAD", "D"),
-            0x1A => (1, "LDAX", "D"),
-            0x1B => (1, "DCX", "D"),
-            0x1C => (1, "INR", "E"),
-            0x1D => (1, "DCR", "E"),
-            0x1E => (2, "MVI", "E"),
-            0x1F => (1, "RAR", ""),
-            0x21 => (3, "LXI", "H"),
-            0x22 => (3, "SHLD", ""),
-            0x23 => (1, "INX", "H"),
-            0x24 => (1, "INR", "H"),
-            0x25 => (1, "DCR", "H"),
-            0x26 => (2, "MVI", "H"),
-            0x27 => (1, "DAA", ""),
-            0x29 => (1, "DAD", "H"),
-            0x2A => (3, "LHLD", ""),
-            0x2B => (1, "DCX", "H"),
-            0x2C => (1, "INR", "L"),
-            0x2D => (1, "DCR", "L"),
-            0x2E => (2, "MVI", "L"),
-            0x2F => (1, "CMA", ""),
-            0x31 => (3, "LXI", "SP"),
-            0x32 => (3, "STA", ""),
-            0x33 => (1, "INX", "SP"),
-            0x34 => (1, "INR", "M"),
-            0x35 => (1, "DCR", "M"),
-            0x36 => (2, "MVI", "M"),
-            0x37 => (1, "STC", ""),
-            0x39 => (1, "DAD", "SP"),
-            0x3A => (3, "LDA", ""),
-            0x3B => (1, "DCX", "SP"),
-            0x3C => (1, "INR", "A"),
-            0x3D => (1, "DCR", "A"),
-            0x3E => (2, "MVI", "A"),
-            0x3F => (1, "CMC", ""),
-            0x40 => (1, "MOV", "B,B"),
-            0x41 => (1, "MOV", "B,C"),
-            0x42 => (1, "MOV", "B,D"),
-            0x43 => (1, "MOV", "B,E"),
-            0x44 => (1, "MOV", "B,H"),
-            0x45 => (1, "MOV", "B,L"),
-            0x46 => (1, "MOV", "B,M"),
-            0x47 => (1, "MOV", "B,A"),
-            0x48 => (1, "MOV", "C,B"),
-            0x49 => (1, "MOV", "C,C"),
-            0x4A => (1, "MOV", "C,D"),
-            0x4B => (1, "MOV", "C,E"),
-            0x4C => (1, "MOV", "C,H"),
-            0x4D => (1, "MOV", "C,L"),
-            0x4E => (1, "MOV", "C,M"),
-            0x4F => (1, "MOV", "C,A"),
-            0x50 => (1, "MOV", "D,B"),
-            0x51 => (1, "MOV", "D,C"),
-            0x52 => (1, "MOV", "D,D"),
-            0x53 => (1, "MOV", "D,E"),
-            0x54 => (1, "MOV", "D,H"),
-            0x55 => (1, "MOV", "D,L"),
-            0x56 => (1, "MOV", "D,M"),
-            0x57 => (1, "MOV", "D,A"),
-            0x58 => (1, "MOV", "E,B"),
-            0x59 => (1, "MOV", "E,C"),
-            0x5A => (1, "MOV", "E,D"),
-            0x5B => (1, "MOV", "E,E"),
-            0x5C => (1, "MOV", "E,H"),
-            0x5D => (1, "MOV", "E,L"),
-            0x5E => (1, "MOV", "E,M"),
-            0x5F => (1, "MOV", "E,A"),
-            0x60 => (1, "MOV", "H,B"),
-            0x61 => (1, "MOV", "H,C"),
-            0x62 => (1, "MOV", "H,D"),
-            0x63 => (1, "MOV", "H,E"),
-            0x64 => (1, "MOV", "H,H"),
-            0x65 => (1, "MOV", "H,L"),
-            0x66 => (1, "MOV", "H,M"),
-            0x67 => (1, "MOV", "H,A"),
-            0x68 => (1, "MOV", "L,B"),
-            0x69 => (1, "MOV", "L,C"),
-            0x6A => (1, "MOV", "L,D"),
-            0x6B => (1, "MOV", "L,E"),
-            0x6C => (1, "MOV", "L,H"),
-            0x6D => (1, "MOV", "L,L"),
-            0x6E => (1, "MOV", "L,M"),
-            0x6F => (1, "MOV", "L,A"),
-            0x70 => (1, "MOV", "M,B"),
-            0x71 => (1, "MOV", "M,C"),
-            0x72 => (1, "MOV", "M,D"),
-            0x73 => (1, "MOV", "M,E"),
-            0x74 => (1, "MOV", "M,H"),
-            0x75 => (1, "MOV", "M,L"),
-            0x76 => (1, "HLT", ""),
-            0x77 => (1, "MOV", "M,A"),
-            0x78 => (1, "MOV", "A,B"),
-            0x79 => (1, "MOV", "A,C"),
-            0x7A => (1, "MOV", "A,D"),
-            0x7B => (1, "MOV", "A,E"),
-            0x7C => (1, "MOV", "A,H"),
-            0x7D => (1, "MOV", "A,L"),
-            0x7E => (1, "MOV", "A,M"),
-            0x7F => (1, "MOV", "A,A"),
-            0x80 => (1, "ADD", "B"),
-            0x81 => (1, "ADD", "C"),
-            0x82 => (1, "ADD", "D"),
-            0x83 => (1, "ADD", "E"),
-            0x84 => (1, "ADD", "H"),
-            0x85 => (1, "ADD", "L"),
-            0x86 => (1, "ADD", "M"),
-            0x87 => (1, "ADD", "A"),
-            0x88 => (1, "ADC", "B"),
-            0x89 => (1, "ADC", "C"),
-            0x8A => (1, "ADC", "D"),
-            0x8B => (1, "ADC", "E"),
-            0x8C => (1, "ADC", "H"),
-            0x8D => (1, "ADC", "L"),
-            0x8E => (1, "ADC", "M"),
-            0x8F => (1, "ADC", "A"),
-            0x90 => (1, "SUB", "B"),
-            0x91 => (1, "SUB", "C"),
-            0x92 => (1, "SUB", "D"),
-            0x93 => (1, "SUB", "E"),
-            0x94 => (1, "SUB", "H"),
-            0x95 => (1, "SUB", "L"),
-            0x96 => (1, "SUB", "M"),
-            0x97 => (1, "SUB", "A"),
-            0x98 => (1, "SBB", "B"),
-            0x99 => (1, "SBB", "C"),
-            0x9A => (1, "SBB", "D"),
-            0x9B => (1, "SBB", "E"),
-            0x9C => (1, "SBB", "H"),
-            0x9D => (1, "SBB", "L"),
-            0x9E => (1, "SBB", "M"),
-            0x9F => (1, "SBB", "A"),
-            0xA0 => (1, "ANA", "B"),
-            0xA1 => (1, "ANA", "C"),
-            0xA2 => (1, "ANA", "D"),
-            0xA3 => (1, "ANA", "E"),
-            0xA4 => (1, "ANA", "H"),
-            0xA5 => (1, "ANA", "L"),
-            0xA6 => (1, "ANA", "M"),
-            0xA7 => (1, "ANA", "A"),
-            0xA8 => (1, "XRA", "B"),
-            0xA9 => (1, "XRA", "C"),
-            0xAA => (1, "XRA", "D"),
-            0xAB => (1, "XRA", "E"),
-            0xAC => (1, "XRA", "H"),
-            0xAD => (1, "XRA", "L"),
-            0xAE => (1, "XRA", "M"),
-            0xAF => (1, "XRA", "A"),
-            0xB0 => (1, "ORA", "B"),
-            0xB1 => (1, "ORA", "C"),
-            0xB2 => (1, "ORA", "D"),
-            0xB3 => (1, "ORA", "E"),
-            0xB4 => (1, "ORA", "H"),
-            0xB5 => (1, "ORA", "L"),
-            0xB6 => (1, "ORA", "M"),
-            0xB7 => (1, "ORA", "A"),
-            0xB8 => (1, "CMP", "B"),
-            0xB9 => (1, "CMP", "C"),
-            0xBA => (1, "CMP", "D"),
-            0xBB => (1, "CMP", "E"),
-            0xBC => (1, "CMP", "H"),
-            0xBD => (1, "CMP", "L"),
-            0xBE => (1, "CMP", "M"),
-            0xBF => (1, "CMP", "A"),
-            0xC0 => (1, "RNZ", ""),
-            0xC1 => (1, "POP", "B"),
-            0xC2 => (3, "JNZ", ""),
-            0xC3 | 0xCB => (3, "JMP", ""),
-            0xC4 => (3, "CNZ", ""),
-            0xC5 => (1, "PUSH", "B"),
-            0xC6 => (2, "ADI", ""),
-            0xC7 => (1, "RST", "0"),
-            0xC8 => (1, "RZ", ""),
-            0xC9 | 0xD9 => (1, "RET", ""),
-            0xCA => (3, "JZ", ""),
-            0xCC => (3, "CZ", ""),
-            0xCD | 0xDD | 0xED | 0xFD => (3, "CALL", ""),
-            0xCE => (2, "ACI", ""),
-            0xCF => (1, "RST", "1"),
-            0xD0 => (1, "RNC", ""),
-            0xD1 => (1, "POP", "D"),
-            0xD2 => (3, "JNC", ""),
-            0xD3 => (2, "OUT", ""),
-            0xD4 => (3, "CNC", ""),
-            0xD5 => (1, "PUSH", "D"),
-            0xD6 => (2, "SUI", ""),
-            0xD7 => (1, "RST", "2"),
-            0xD8 => (1, "RC", ""),
-            0xDA => (3, "JC", ""),
-            0xDB => (2, "IN", ""),
-            0xDC => (3, "CC", ""),
-            0xDE => (2, "SBI", ""),
-            0xDF => (1, "RST", "3"),
-            0xE0 => (1, "RPO", ""),
-            0xE1 => (1, "POP", "H"),
-            0xE2 => (3, "JPO", ""),
-            0xE3 => (1, "XTHL", ""),
-            0xE4 => (3, "CPO", ""),
-            0xE5 => (1, "PUSH", "H"),
-            0xE6 => (2, "ANI", ""),
-            0xE7 => (1, "RST", "4"),
-            0xE8 => (1, "RPE", ""),
-            0xE9 => (1, "PCHL", ""),
-            0xEA => (3, "JPE", ""),
-            0xEB => (1, "XCHG", ""),
-            0xEC => (3, "CPE", ""),
-            0xEE => (2, "XRI", ""),
-            0xEF => (1, "RST", "5"),
-            0xF0 => (1, "RP", ""),
-            0xF1 => (1, "POP", "PSW"),
-            0xF2 => (3, "JP", ""),
-            0xF3 => (1, "DI", ""),
-            0xF4 => (3, "CP", ""),
-            0xF5 => (1, "PUSH", "PSW"),
-            0xF6 => (2, "ORI", ""),
-            0xF7 => (1, "RST", "6"),
-            0xF8 => (1, "RM", ""),
-            0xF9 => (1, "SPHL", ""),
-            0xFA => (3, "JM", ""),
-            0xFB => (1, "EI", ""),
-            0xFC => (3, "CM", ""),
-            0xFE => (2, "CPI", ""),
-            0xFF => (1, "RST", "7"),
+    let result = match &options.output {
+        Some(path) => std::fs::write(path, bytes),
+        None => std::io::Write::write_all(&mut std::io::stdout(), &bytes),
+    };
+    if let Err(e) = result {
+        error!(
+            exitcode::IOERR,
+            "{:?}",
+            anyhow!(e).context("writing the bytes extracted")
+        );
+    }
+}
+
+/// Print the size and complexity of each function.
+fn print_metrics(args: &[String]) {
+    let (options, paths) = parse_options(args);
+    let [rom_file_path] = paths.as_slice() else {
+        error!(exitcode::USAGE, "expected a single rom file");
+    };
+    let image = load_image(rom_file_path, &options);
+    printed(metrics::print(
+        &mut std::io::stdout().lock(),
+        &analyze(&image, &options),
+        options.json,
+    ));
+}
+
+/// Print the pairs of similar functions of two roms.
+fn print_similarity(args: &[String]) {
+    let (options, paths) = parse_options(args);
+    let [first_path, second_path] = paths.as_slice() else {
+        error!(exitcode::USAGE, "expected two rom files");
+    };
+    let first = analyze(&load_image(first_path, &options), &options);
+    let second = analyze(&load_image(second_path, &options), &options);
+    printed(similarity::print(
+        &mut std::io::stdout().lock(),
+        &first,
+        &second,
+    ));
+}
+
+/// Disassemble many roms in parallel to the output directory, and print a summary of them.
+fn batch(args: &[String]) {
+    let (options, paths) = parse_options(args);
+    let Some(directory) = &options.output_dir else {
+        error!(
+            exitcode::USAGE,
+            "expected --output-dir to write the output of each rom"
+        );
+    };
+    if paths.is_empty() {
+        error!(exitcode::USAGE, "expected rom files or directories");
+    }
+    let files = rom_files(&paths);
+    let stems = output_stems(&files);
+    let named: Vec<(&String, &String)> = files.iter().zip(&stems).collect();
+
+    let workers = std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get);
+    let per_worker = files.len().div_ceil(workers).max(1);
+    let roms: Vec<batch::Rom> = std::thread::scope(|scope| {
+        // Every worker is started before waiting for any.
+        #[allow(clippy::needless_collect)]
+        let handles: Vec<_> = named
+            .chunks(per_worker)
+            .map(|chunk| {
+                let options = &options;
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|&(path, stem)| {
+                            let image = load_image(path, options);
+                            let listing = analyze(&image, options);
+                            write_formats(directory, stem, &image, &listing, options);
+                            batch::Rom::new(path.clone(), &image.bytes, &listing)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    });
+    printed(batch::print(&mut std::io::stdout().lock(), &roms));
+}
+
+/// Names of the output files of each rom, from its file name without the extension.
+///
+/// Roms of the same name, from different directories or with different extensions, get
+/// a numeric suffix in their order so that none overwrites the output of another.
+fn output_stems(files: &[String]) -> Vec<String> {
+    let mut used = std::collections::BTreeSet::new();
+    files
+        .iter()
+        .map(|path| {
+            let stem = std::path::Path::new(path)
+                .file_stem()
+                .map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+            let mut name = stem.clone();
+            for suffix in 2.. {
+                if used.insert(name.clone()) {
+                    break;
+                }
+                name = format!("{stem}-{suffix}");
+            }
+            if name != stem {
+                note!("writing the output of \"{path}\" as \"{name}\", another rom is named \"{stem}\"");
+            }
+            name
+        })
+        .collect()
+}
+
+/// Files given on the command line, with the files of each directory in name order.
+fn rom_files(paths: &[&str]) -> Vec<String> {
+    let mut files = Vec::new();
+    for &path in paths {
+        if !std::path::Path::new(path).is_dir() {
+            files.push(path.to_owned());
+            continue;
+        }
+        let entries = std::fs::read_dir(path).and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<_>>>()
+        });
+        let mut entries = entries.unwrap_or_else(|e| {
+            error!(
+                exitcode::NOINPUT,
+                "{:?}",
+                anyhow!(e).context(format!("reading the directory \"{path}\""))
+            )
+        });
+        entries.retain(|entry| entry.is_file());
+        entries.sort();
+        files.extend(
+            entries
+                .iter()
+                .map(|entry| entry.to_string_lossy().into_owned()),
+        );
+    }
+    files
+}
+
+/// Print the likely bugs found in the code.
+fn lint(args: &[String]) {
+    let (options, paths) = parse_options(args);
+    let [rom_file_path] = paths.as_slice() else {
+        error!(exitcode::USAGE, "expected a single rom file");
+    };
+    let image = load_image(rom_file_path, &options);
+    // CP/M programs run from RAM and may write over themselves.
+    let rom = options.profile.is_none();
+    let out: &mut dyn std::io::Write = &mut std::io::stdout().lock();
+    printed(
+        lint::warnings(&analyze(&image, &options), rom)
+            .iter()
+            .try_for_each(|warning| writeln!(out, "{warning}")),
+    );
+}
+
+/// Print an HTML page of an execution trace over time next to the listing.
+fn print_timeline(args: &[String]) {
+    let (options, paths) = parse_options(args);
+    let [rom_file_path] = paths.as_slice() else {
+        error!(exitcode::USAGE, "expected a single rom file");
+    };
+    let Some(path) = &options.trace else {
+        error!(exitcode::USAGE, "expected an execution trace with --trace");
+    };
+    let image = load_image(rom_file_path, &options);
+    let steps = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!(e))
+        .and_then(|text| timeline::parse(&text, &image.bytes))
+        .unwrap_or_else(|e| {
+            error!(
+                exitcode::DATAERR,
+                "{:?}",
+                e.context(format!("reading trace \"{path}\""))
+            )
+        });
+    let listing = analyze(&image, &options);
+    printed(timeline::print(
+        &mut std::io::stdout().lock(),
+        &listing,
+        &steps,
+    ));
+}
+
+/// Rename a label or symbol in the project file, after checking the new name.
+fn rename(args: &[String]) {
+    let (options, paths) = parse_options(args);
+    let [old, new, rom_file_path] = paths.as_slice() else {
+        error!(
+            exitcode::USAGE,
+            "expected the old name, the new name and a rom file"
+        );
+    };
+    let Some(path) = &options.project else {
+        error!(exitcode::USAGE, "expected a project file with --project");
+    };
+    let listing = analyze(&load_image(rom_file_path, &options), &options);
+    let Some(address) = address_of(&listing, old) else {
+        error!(exitcode::DATAERR, "no label or symbol named \"{old}\"");
+    };
+    let dialect = options.dialect.unwrap_or(asm::INTEL);
+    if let Err(e) = project::check_name(new, address, &listing, &dialect) {
+        error!(
+            exitcode::DATAERR,
+            "{:?}",
+            e.context(format!("renaming \"{old}\" to \"{new}\""))
+        );
+    }
+    let result = match std::fs::read_to_string(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        result => result,
+    }
+    .and_then(|text| std::fs::write(path, project::rename(&text, address, old, new)));
+    if let Err(e) = result {
+        error!(
+            exitcode::IOERR,
+            "{:?}",
+            anyhow!(e).context(format!("renaming in project file \"{path}\""))
+        );
+    }
+}
+
+/// Print the cycles taken to reach each instruction of a function from its entry.
+fn print_timing(args: &[String]) {
+    let (options, paths) = parse_options(args);
+    let [rom_file_path] = paths.as_slice() else {
+        error!(exitcode::USAGE, "expected a single rom file");
+    };
+    let Some(address) = options.at else {
+        error!(
+            exitcode::USAGE,
+            "expected the address of a function with --at"
+        );
+    };
+    let image = load_image(rom_file_path, &options);
+    let listing = analyze(&image, &options);
+    let Some(range) = listing.function_range(address) else {
+        error!(
+            exitcode::DATAERR,
+            "no function found at 0x{address:04x} by following the flow of execution"
+        );
+    };
+    printed(timing::print(
+        &mut std::io::stdout().lock(),
+        &listing,
+        range,
+    ));
+}
+
+/// Print the strings of a rom, adding a label for each to the project file if asked to.
+fn print_strings(args: &[String]) {
+    let (options, paths) = parse_options(args);
+    let [rom_file_path] = paths.as_slice() else {
+        error!(exitcode::USAGE, "expected a single rom file");
+    };
+    let image = load_image(rom_file_path, &options);
+    let found = strings::find_in(
+        &image.bytes,
+        options.min_length.unwrap_or(strings::MIN_LENGTH),
+        &options.charset.clone().unwrap_or_default(),
+    );
+    let out: &mut dyn std::io::Write = &mut std::io::stdout().lock();
+    printed(found.iter().try_for_each(|string| {
+        writeln!(
+            out,
+            "{:04x} {:<7} {:?}",
+            string.address,
+            string.kind.name(),
+            string.text
+        )
+    }));
+
+    if !options.emit_labels {
+        return;
+    }
+    let Some(path) = &options.project else {
+        error!(exitcode::USAGE, "expected a project file with --project");
+    };
+    let project = load_project(&options);
+    let mut lines = String::new();
+    for string in &found {
+        let Ok(address) = u16::try_from(string.address) else {
+            continue;
         };
+        if !project.labels.contains_key(&address) {
+            let _ = writeln!(lines, "label 0x{address:04x} str_{address:04x}");
+        }
+    }
+    let result = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| std::io::Write::write_all(&mut file, lines.as_bytes()));
+    if let Err(e) = result {
+        error!(
+            exitcode::IOERR,
+            "{:?}",
+            anyhow!(e).context(format!("adding labels to project file \"{path}\""))
+        );
+    }
+}
+
+/// Read a character map, exiting when it is invalid.
+fn load_charset(path: &str) -> charset::Charset {
+    let result = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!(e))
+        .and_then(|text| charset::Charset::parse(&text));
+    result.unwrap_or_else(|e| {
+        error!(
+            exitcode::CONFIG,
+            "{:?}",
+            e.context(format!("loading character map \"{path}\""))
+        )
+    })
+}
+
+/// Read the project file, which may not exist yet when labels are to be added to it.
+fn load_project(options: &Options) -> project::Project {
+    let mut project = project::Project::default();
+    let Some(path) = &options.project else {
+        return project;
+    };
+    let result = match std::fs::read_to_string(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && options.emit_labels => Ok(()),
+        Err(e) => Err(anyhow!(e)),
+        Ok(text) => project.extend(&text),
+    };
+    if let Err(e) = result {
+        error!(
+            exitcode::CONFIG,
+            "{:?}",
+            e.context(format!("loading project file \"{path}\""))
+        );
+    }
+    project
+}
+
+/// Follow the flow of execution, naming the labels after the project file.
+fn analyze(image: &input::Image, options: &Options) -> analysis::Listing {
+    analyze_from(image, &options.entry_points(), options)
+}
+
+/// Follow the flow of execution from some entry points, running the passes over the listing.
+fn analyze_from(image: &input::Image, entries: &[u16], options: &Options) -> analysis::Listing {
+    if image.bytes.len() > analysis::MEMORY_SIZE {
+        note!(
+            "only the first 64 KiB of the 0x{:x} bytes of the image are analyzed, the 8080 cannot address the rest",
+            image.bytes.len()
+        );
+    }
+    let mut listing = analysis::analyze_with_parameters(image, entries, &options.parameters);
+    // The memory below the origin is not loaded from the file.
+    if let Some(origin) = options.origin() {
+        listing.start_at(origin);
+    }
+    for pass in options.passes() {
+        match pass {
+            Pass::Cpm => cpm::annotate(&mut listing),
+            Pass::Project => load_project(options).apply(&mut listing),
+            Pass::FlagSources => conditions::annotate(&mut listing),
+            Pass::Pointers => pointers::annotate(&mut listing),
+            Pass::Patches => {
+                let patched: Vec<(u16, String)> = listing
+                    .lines
+                    .iter()
+                    .filter_map(|line| {
+                        let start = usize::from(line.address());
+                        let name = image.patch_at(start..start + line.length())?;
+                        Some((line.address(), format!("patched by {name}")))
+                    })
+                    .collect();
+                for (address, comment) in patched {
+                    listing.add_comment(address, comment);
+                }
+            }
+        }
+    }
+    if let Some(command) = &options.label_hook {
+        if let Err(e) = hook::rename(&mut listing, command) {
+            error!(exitcode::SOFTWARE, "{:?}", e.context("renaming labels"));
+        }
+    }
+    listing
+}
 
-        let mut second_byte = None;
-        if instruction_length > 1 {
-            if let Some((_, byte)) = rom_iter.next() {
-                print!("{byte:02x} ");
-                second_byte = Some(byte);
+/// Look up a machine in the built-in database extended with the given files.
+fn find_machine(name: &str, paths: &[String]) -> machines::Machine {
+    let mut database = machines::Database::builtin();
+    for path in paths {
+        let result = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!(e))
+            .and_then(|text| database.extend(&text));
+        if let Err(e) = result {
+            error!(
+                exitcode::CONFIG,
+                "{:?}",
+                e.context(format!("loading machine database \"{path}\""))
+            );
+        }
+    }
+
+    database
+        .get(name)
+        .cloned()
+        .unwrap_or_else(|| error!(exitcode::USAGE, "unknown machine \"{name}\""))
+}
+
+/// Print the tool, command line and input the listing was made with.
+fn print_provenance(
+    out: &mut dyn std::io::Write,
+    image: &input::Image,
+    options: &Options,
+) -> std::io::Result<()> {
+    let Palette { reset, gray, .. } = options.palette();
+    let command: Vec<String> = std::env::args()
+        .skip(1)
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '\'') {
+                format!("'{}'", arg.replace('\'', "'\\''"))
             } else {
-                println!();
+                arg
+            }
+        })
+        .collect();
+    writeln!(
+        out,
+        "{gray}; {} {}{reset}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    )?;
+    writeln!(
+        out,
+        "{gray}; command: {} {}{reset}",
+        env!("CARGO_PKG_NAME"),
+        command.join(" ")
+    )?;
+    for file in &image.files {
+        writeln!(
+            out,
+            "{gray}; input: {}, 0x{:x} bytes, sha256 {}{reset}",
+            file.name,
+            file.bytes.len(),
+            hash::sha256(&file.bytes)
+        )?;
+    }
+    if !is_file(image) {
+        writeln!(
+            out,
+            "{gray}; image: 0x{:x} bytes, sha256 {}{reset}",
+            image.bytes.len(),
+            hash::sha256(&image.bytes)
+        )?;
+    }
+    if options.base_file_offset.is_some() || options.origin().is_some() {
+        // The checksums above are those of the file, header included.
+        let header = options.base_file_offset.map_or_else(String::new, |length| {
+            format!(", after a header of 0x{length:x} bytes")
+        });
+        writeln!(
+            out,
+            "{gray}; load: file offset 0x{:x} at address 0x{:04x}{header}{reset}",
+            options.base_file_offset.unwrap_or(0),
+            options.origin().unwrap_or(0)
+        )?;
+    }
+    writeln!(out)
+}
+
+/// Whether the image is a single file as it was read, not moved, patched or cut.
+fn is_file(image: &input::Image) -> bool {
+    matches!(image.files.as_slice(), [file] if file.bytes == image.bytes)
+}
+
+/// Print the checksums of the whole image and of each file it was loaded from.
+///
+/// Files loaded as they were read are given with their addresses, and the others on their
+/// own, so that the checksums of every file are those of the file.
+fn print_hashes(
+    out: &mut dyn std::io::Write,
+    image: &input::Image,
+    palette: Palette,
+) -> std::io::Result<()> {
+    let Palette { reset, gray, .. } = palette;
+    let mut print_hash = |place: &str, name: &str, bytes: &[u8]| {
+        writeln!(
+            out,
+            "{gray}; {place:<9}  crc32 {:08x}  sha256 {}  {name}{reset}",
+            hash::crc32(bytes),
+            hash::sha256(bytes)
+        )
+    };
+    let range = |start: usize, bytes: &[u8]| {
+        format!(
+            "{start:04x}-{:04x}",
+            (start + bytes.len()).saturating_sub(1)
+        )
+    };
+
+    if is_file(image) {
+        let name = image.segments.first().map_or("", |s| s.name.as_str());
+        print_hash(&range(0, &image.bytes), name, &image.bytes)?;
+        return writeln!(out);
+    }
+    print_hash(&range(0, &image.bytes), "(image)", &image.bytes)?;
+    let mut loaded = Vec::new();
+    for segment in &image.segments {
+        let bytes = image.segment_bytes(segment);
+        let start = usize::from(segment.address);
+        if let Some(file) = image
+            .files
+            .iter()
+            .find(|file| file.name == segment.name && file.bytes == bytes)
+        {
+            print_hash(&range(start, bytes), &file.name, bytes)?;
+            loaded.push(&file.name);
+        }
+    }
+    for file in &image.files {
+        if !loaded.contains(&&file.name) {
+            print_hash("file", &file.name, &file.bytes)?;
+        }
+    }
+    writeln!(out)
+}
+
+/// Kind of output printed for a rom.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+enum Format {
+    #[default]
+    Listing,
+    /// Source for an assembler.
+    Asm,
+    /// Layout of `objdump -d`.
+    Objdump,
+    /// Canonical line per instruction, to checksum.
+    Digest,
+    /// Picture of the opcodes used and of what each address holds.
+    Svg,
+    /// Lines split into typed spans, for syntax highlighters.
+    Tokens,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "listing" => Some(Self::Listing),
+            "asm" => Some(Self::Asm),
+            "objdump" => Some(Self::Objdump),
+            "digest" => Some(Self::Digest),
+            "svg" => Some(Self::Svg),
+            "tokens" => Some(Self::Tokens),
+            _ => None,
+        }
+    }
+
+    /// Extension of the file written in this format to the output directory.
+    const fn extension(self) -> &'static str {
+        match self {
+            Self::Listing => "lst",
+            Self::Asm => "asm",
+            Self::Objdump => "objdump",
+            Self::Digest => "digest",
+            Self::Svg => "svg",
+            Self::Tokens => "tokens",
+        }
+    }
+}
+
+/// Knowledge of the system the rom runs on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Profile {
+    /// CP/M program, loaded at 0x100.
+    Cpm,
+}
+
+impl Profile {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cpm" => Some(Self::Cpm),
+            _ => None,
+        }
+    }
+}
+
+/// Options of the disassembly listing.
+#[derive(Default)]
+#[allow(clippy::struct_excessive_bools)]
+struct Options {
+    /// Append a description of each instruction.
+    explain: bool,
+    /// Follow arithmetic and logic instructions with how they set each flag.
+    teach: bool,
+    /// File to read when the rom is a zip archive.
+    member: Option<String>,
+    /// Known machine whose romset is being read.
+    machine: Option<String>,
+    /// Files adding to the built-in machine database.
+    machine_databases: Vec<String>,
+    /// Omit the checksums at the top of the listing.
+    no_hashes: bool,
+    input_format: input::Format,
+    /// Follow the flow of execution to separate code from data and label addresses.
+    labels: bool,
+    /// Kinds of output, the listing when none is given.
+    formats: Vec<Format>,
+    /// Syntax of the source, Intel by default.
+    dialect: Option<asm::Dialect>,
+    /// System the rom runs on.
+    profile: Option<Profile>,
+    /// Address the rom is loaded at and started from.
+    org: Option<u16>,
+    /// Bytes of header skipped at the start of the file, the rest being loaded at the origin.
+    base_file_offset: Option<usize>,
+    /// Parameters following restarts and calls inline.
+    parameters: analysis::Parameters,
+    /// File naming the labels.
+    project: Option<String>,
+    /// Characters displayed by the bytes, for strings and byte immediates.
+    charset: Option<charset::Charset>,
+    /// Shortest string reported.
+    min_length: Option<usize>,
+    /// Address to look at.
+    at: Option<u16>,
+    /// Name of the function the listing is restricted to.
+    function: Option<String>,
+    /// Address the listing is restricted to the lines around, and how far around.
+    around: Option<(u16, usize)>,
+    /// IPS or BPS patches applied to the image before anything else, in order.
+    patches: Vec<String>,
+    /// Write the changes of the patch subcommand instead of the patched image.
+    emit_patch: Option<patch::Format>,
+    /// File written instead of printing.
+    output: Option<String>,
+    /// Addresses or names of the start and end of the bytes extracted, the end of the image
+    /// when the end is empty.
+    range: Option<(String, String)>,
+    /// Size the bytes extracted are padded to.
+    pad_to: Option<usize>,
+    /// Byte the bytes extracted are padded with.
+    fill: Option<u8>,
+    /// Offset of the byte set for the bytes extracted to add up to a sum.
+    checksum: Option<(usize, u8)>,
+    /// Directory each format is written to instead of printing.
+    output_dir: Option<String>,
+    /// Offsets decoded from by the alternates subcommand.
+    offsets: Option<usize>,
+    /// Tables printed after the listing.
+    appendix: Option<appendix::Sections>,
+    /// Add a label for each string found to the project file.
+    emit_labels: bool,
+    /// Order of the symbols.
+    sort: symbols::Sort,
+    /// Print the metrics as JSON instead of a table.
+    json: bool,
+    /// Command assembling the source to check it against the image.
+    assembler: Option<String>,
+    /// Threads the linear listing is decoded on, going on past 64 KiB.
+    threads: Option<std::num::NonZeroUsize>,
+    /// Execution trace of an emulator, an address per instruction run.
+    trace: Option<String>,
+    /// Command renaming the labels and symbols once the passes have run.
+    label_hook: Option<String>,
+    /// Indent with spaces only.
+    spaces: bool,
+    /// Comment constants and addresses of data with their value in decimal.
+    decimal: bool,
+    /// Roll repeated sequences of instructions into macros in the source.
+    macros: bool,
+    /// Comment conditional instructions with the instruction setting the flag they test.
+    flag_sources: bool,
+    /// Comment the memory accessed through register pairs, and their use.
+    pointers: bool,
+    /// Passes run over the analyzed listing, in order, instead of those selected by options.
+    passes: Option<Vec<Pass>>,
+    /// Column of the operands, moving the comments along with them.
+    operand_column: Option<usize>,
+    /// Columns the lines are wrapped to.
+    width: Option<usize>,
+    /// Report every problem found in the image before printing the listing.
+    strict: bool,
+    /// Most problems reported by the strict mode.
+    max_errors: Option<usize>,
+    /// Exit with an error at an instruction cut off by the end of the image, instead of
+    /// listing its bytes as data.
+    fail_on_truncated: bool,
+    /// Instructions reported instead of listing the code when it contains them.
+    forbid: Vec<forbid::Rule>,
+    /// Start with the tool, command line and input the listing was made with.
+    provenance: bool,
+    /// Make the output identical across runs and platforms, without colors.
+    deterministic: bool,
+    /// Print the listing again whenever the files it is made from change.
+    live: bool,
+    /// URL jump and call targets link to, with `{address}` and `{name}` placeholders.
+    links: Option<String>,
+}
+
+impl Options {
+    /// Address the rom is loaded at, when not at 0.
+    fn origin(&self) -> Option<u16> {
+        self.org
+            .or_else(|| self.profile.map(|Profile::Cpm| cpm::ORIGIN))
+    }
+
+    /// Addresses execution starts from.
+    fn entry_points(&self) -> Vec<u16> {
+        vec![self.origin().unwrap_or(0)]
+    }
+
+    /// Passes run over the analyzed listing, in order.
+    ///
+    /// Without a list of passes, those off by default run when their option is given. The
+    /// CP/M pass only runs on programs loaded with the CP/M profile.
+    fn passes(&self) -> Vec<Pass> {
+        let selected = |pass: Pass| match pass {
+            Pass::FlagSources => self.flag_sources,
+            Pass::Pointers => self.pointers,
+            _ => pass.is_default(),
+        };
+        self.passes
+            .clone()
+            .unwrap_or_else(|| Pass::ALL.into_iter().filter(|&p| selected(p)).collect())
+            .into_iter()
+            .filter(|&pass| pass != Pass::Cpm || self.profile == Some(Profile::Cpm))
+            .collect()
+    }
+
+    /// Kinds of output selected.
+    fn formats(&self) -> &[Format] {
+        if self.formats.is_empty() {
+            &[Format::Listing]
+        } else {
+            &self.formats
+        }
+    }
+
+    /// URL template of the links of jump and call targets, when printing to a terminal.
+    fn links(&self) -> Option<&str> {
+        let colored = self.palette().reset == Palette::COLORS.reset;
+        let links = self.links.as_deref();
+        links.filter(|_| colored && self.output.is_none() && std::io::stdout().is_terminal())
+    }
+
+    fn palette(&self) -> Palette {
+        // Source code is read by assemblers and files by other tools, not terminals.
+        if self.deterministic || self.output_dir.is_some() || self.formats() != [Format::Listing] {
+            Palette::PLAIN
+        } else {
+            Palette::COLORS
+        }
+    }
+
+    /// Columns of the output, adjusted from the default `layout`.
+    fn layout(&self, layout: Layout) -> Layout {
+        let layout = Layout {
+            spaces: self.spaces || layout.spaces,
+            ..layout
+        };
+        self.operand_column
+            .map_or(layout, |column| layout.with_operand_column(column))
+    }
+}
+
+/// Read the rom file, or the romset of the selected machine.
+/// Exit code of a rom that could not be loaded: a failure to read it, or a file read that
+/// holds no rom.
+const fn load_error_code(error: &DisasmError) -> exitcode::ExitCode {
+    match error {
+        DisasmError::Io { .. }
+        | DisasmError::Zip {
+            source: zip::result::ZipError::Io(_),
+            ..
+        } => exitcode::IOERR,
+        DisasmError::Member(_) => exitcode::NOINPUT,
+        _ => exitcode::DATAERR,
+    }
+}
+
+fn load_image(rom_file_path: &str, options: &Options) -> input::Image {
+    let rom = options.machine.as_ref().map_or_else(
+        || {
+            input::read_rom(
+                rom_file_path,
+                options.member.as_deref(),
+                options.input_format,
+            )
+        },
+        |name| {
+            input::read_machine(
+                rom_file_path,
+                &find_machine(name, &options.machine_databases),
+            )
+        },
+    );
+    let mut image = match rom {
+        Ok(r) => r,
+        Err(e) => {
+            let code = load_error_code(&e);
+            error!(code, "{:?}", anyhow!(e));
+        }
+    };
+    if let Some(length) = options.base_file_offset {
+        image = image
+            .without_header(length)
+            .unwrap_or_else(|e| error!(exitcode::DATAERR, "{:?}", anyhow!(e)));
+    }
+    for path in &options.patches {
+        let bytes = std::fs::read(path)
+            .map_err(|e| anyhow!(e))
+            .and_then(|patch| patch::apply(&image.bytes, &patch).map_err(|e| anyhow!(e)))
+            .unwrap_or_else(|e| {
                 error!(
                     exitcode::DATAERR,
                     "{:?}",
-                    anyhow!("instruction incomplete")
-                        .context("reading second byte of instruction \"{first_byte:02x}\"")
+                    e.context(format!("applying patch \"{path}\""))
                 )
-            }
+            });
+        let name = std::path::Path::new(path)
+            .file_name()
+            .map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned());
+        image = image.patched_with(&name, bytes);
+    }
+    match options.origin() {
+        None => image,
+        Some(origin) => image
+            .moved_to(origin)
+            .unwrap_or_else(|e| error!(exitcode::DATAERR, "{:?}", anyhow!(e))),
+    }
+}
+
+/// Suggest loading the rom elsewhere when its jumps and calls land in it far more often at
+/// another origin than at 0, and often enough to tell.
+fn suggest_origin(image: &input::Image) {
+    let candidates = origin::rank(&image.bytes);
+    let (Some(best), Some(zero)) = (
+        candidates.first(),
+        candidates.iter().find(|candidate| candidate.origin == 0),
+    ) else {
+        return;
+    };
+    if best.inside >= MIN_ORIGIN_REFERENCES && best.inside > 2 * zero.inside {
+        note!(
+            "{} of {} jumps and calls land in the rom when loaded at 0x{:04x}, against {} \
+             at 0x0000; try --org 0x{:04x}",
+            best.inside,
+            best.references,
+            best.origin,
+            zero.inside,
+            best.origin
+        );
+    }
+}
+
+fn disassemble(rom_file_path: &str, options: &Options) {
+    if options.live {
+        watch(rom_file_path, options);
+    }
+    // Read the file into a vector.
+    let image = load_image(rom_file_path, options);
+    if options.origin().is_none() && options.machine.is_none() {
+        suggest_origin(&image);
+    }
+
+    let Some(directory) = &options.output_dir else {
+        let [format] = options.formats() else {
+            error!(
+                exitcode::USAGE,
+                "expected --output-dir to write more than one format"
+            );
+        };
+        let result = print(
+            &mut std::io::stdout().lock(),
+            &image,
+            *format,
+            None,
+            options,
+        );
+        printed(result);
+        return;
+    };
+
+    // Every format is made from the same analysis.
+    let listing = analyze(&image, options);
+    let stem = match std::path::Path::new(rom_file_path).file_stem() {
+        Some(stem) if rom_file_path != "-" => stem.to_string_lossy().into_owned(),
+        _ => "stdin".to_owned(),
+    };
+    write_formats(directory, &stem, &image, &listing, options);
+}
+
+/// Write each format given to a file of the output directory named after `stem`.
+fn write_formats(
+    directory: &str,
+    stem: &str,
+    image: &input::Image,
+    listing: &analysis::Listing,
+    options: &Options,
+) {
+    for &format in options.formats() {
+        let path = std::path::Path::new(directory).join(format!("{stem}.{}", format.extension()));
+        let result = std::fs::create_dir_all(directory)
+            .and_then(|()| std::fs::File::create(&path))
+            .and_then(|file| {
+                let mut file = std::io::BufWriter::new(file);
+                print(&mut file, image, format, Some(listing), options)?;
+                std::io::Write::flush(&mut file)
+            });
+        if let Err(e) = result {
+            error!(
+                exitcode::IOERR,
+                "{:?}",
+                anyhow!(e).context(format!("writing \"{}\"", path.display()))
+            );
         }
+    }
+}
 
-        let mut third_byte = None;
-        if instruction_length > 2 {
-            if let Some((_, byte)) = rom_iter.next() {
-                print!("{byte:02x} ");
-                third_byte = Some(byte);
-            } else {
-                println!();
+/// Print the listing again whenever the rom, the project file or a patch changes, until
+/// interrupted.
+///
+/// Each listing is printed by running the tool again without --live, so that an error in
+/// a file being edited is shown until it is fixed instead of ending the session.
+fn watch(rom_file_path: &str, options: &Options) -> ! {
+    if rom_file_path == "-" {
+        error!(
+            exitcode::USAGE,
+            "expected a rom file to watch, not the standard input"
+        );
+    }
+    let program = std::env::current_exe().unwrap_or_else(|e| {
+        error!(
+            exitcode::OSERR,
+            "{:?}",
+            anyhow!(e).context("finding the program to run again")
+        )
+    });
+    let args: Vec<String> = std::env::args().skip(1).filter(|a| a != "--live").collect();
+    let paths: Vec<&str> = std::iter::once(rom_file_path)
+        .chain(options.project.as_deref())
+        .chain(options.patches.iter().map(String::as_str))
+        .collect();
+
+    let mut printed = None;
+    loop {
+        let modified: Vec<Option<std::time::SystemTime>> = paths
+            .iter()
+            .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect();
+        if printed.as_ref() != Some(&modified) {
+            // Clear the terminal and move to its top.
+            print!("\x1b[2J\x1b[H");
+            let _ = std::io::Write::flush(&mut std::io::stdout());
+            if let Err(e) = std::process::Command::new(&program).args(&args).status() {
                 error!(
-                    exitcode::DATAERR,
+                    exitcode::OSERR,
                     "{:?}",
-                    anyhow!("instruction incomplete")
-                        .context("reading third byte of instruction \"{first_byte:02x}\"")
-                )
+                    anyhow!(e).context("printing the listing")
+                );
+            }
+            printed = Some(modified);
+        }
+        std::thread::sleep(LIVE_POLL_INTERVAL);
+    }
+}
+
+/// Print the rom in a format, from the `shared` analysis when given.
+fn print(
+    out: &mut dyn std::io::Write,
+    image: &input::Image,
+    format: Format,
+    shared: Option<&analysis::Listing>,
+    options: &Options,
+) -> std::io::Result<()> {
+    // A header would make the picture unreadable.
+    if options.provenance && format != Format::Svg {
+        print_provenance(out, image, options)?;
+    }
+    let analyzed = || {
+        let listing = shared.map_or_else(|| Cow::Owned(analyze(image, options)), Cow::Borrowed);
+        excerpt(listing, options)
+    };
+    match format {
+        Format::Listing => {
+            if !options.no_hashes {
+                print_hashes(out, image, options.palette())?;
             }
+            let listing = options.labels.then(analyzed);
+            print_listing(out, image, listing.as_deref(), options)?;
+            if options.labels && options.function.is_none() && options.around.is_none() {
+                print_segments(out, image, options)?;
+            }
+            Ok(())
         }
+        Format::Asm => {
+            let render = if options.macros {
+                asm::render_with_macros
+            } else {
+                asm::render
+            };
+            let source = render(
+                &analyzed(),
+                &options.dialect.unwrap_or(asm::INTEL),
+                &options.layout(Layout::SOURCE),
+            );
+            out.write_all(source.as_bytes())
+        }
+        Format::Objdump => {
+            let name = image.segments.first().map_or("", |s| s.name.as_str());
+            objdump::print(out, name, &analyzed())
+        }
+        Format::Digest => digest::print(out, &analyzed()),
+        Format::Svg => heatmap::print(out, &analyzed()),
+        Format::Tokens => print_tokens(out, &analyzed()),
+    }
+}
 
-        // Print padding for shorter instructions.
-        for _ in 0..3 - instruction_length {
-            print!("   ");
+/// Print a JSON object per line of the listing, with its text and the kind and byte offsets
+/// of its tokens.
+fn print_tokens(out: &mut dyn std::io::Write, listing: &analysis::Listing) -> std::io::Result<()> {
+    for line in tokens::tokenize(listing) {
+        let spans: Vec<String> = line
+            .tokens
+            .iter()
+            .map(|token| {
+                format!(
+                    "{{\"kind\":\"{}\",\"start\":{},\"end\":{}}}",
+                    token.kind.name(),
+                    token.range.start,
+                    token.range.end
+                )
+            })
+            .collect();
+        writeln!(
+            out,
+            "{{\"address\":{},\"text\":\"{}\",\"tokens\":[{}]}}",
+            line.address,
+            metrics::escape(&line.text),
+            spans.join(",")
+        )?;
+    }
+    Ok(())
+}
+
+/// Print each segment of the project file after the listing, in its own section.
+///
+/// A segment is analyzed on its own from its first byte, loaded over the image, and its
+/// labels are prefixed with its name, since segments sharing an address would otherwise
+/// get the same ones.
+fn print_segments(
+    out: &mut dyn std::io::Write,
+    image: &input::Image,
+    options: &Options,
+) -> std::io::Result<()> {
+    let Palette { reset, gray, .. } = options.palette();
+    let project = load_project(options);
+    for segment in &project.segments {
+        let path = options
+            .project
+            .as_deref()
+            .and_then(|project| std::path::Path::new(project).parent())
+            .map_or_else(
+                || segment.path.clone().into(),
+                |dir| dir.join(&segment.path),
+            );
+        let bytes = std::fs::read(&path).unwrap_or_else(|e| {
+            error!(
+                exitcode::IOERR,
+                "{:?}",
+                anyhow!(e).context(format!(
+                    "reading segment \"{}\" from \"{}\"",
+                    segment.name,
+                    path.display()
+                ))
+            )
+        });
+        let start = usize::from(segment.address);
+        let end = start + bytes.len();
+        if end > 0x10000 {
+            error!(
+                exitcode::DATAERR,
+                "segment \"{}\" does not fit in memory at 0x{start:04x}", segment.name
+            );
         }
 
-        let additional_bytes_text = {
-            match instruction_length {
-                1 => String::new(),
-                2 => format!("{COLOR_PURPLE}#0x{:02x}{COLOR_RESET}", second_byte.unwrap()),
-                3 => format!(
-                    "{COLOR_BLUE}${:02x}{:02x}{COLOR_RESET}",
-                    third_byte.unwrap(),
-                    second_byte.unwrap()
-                ),
-                _ => unreachable!(),
-            }
+        let mut memory = image.bytes.clone();
+        if memory.len() < end {
+            memory.resize(end, 0xFF);
+        }
+        memory[start..end].copy_from_slice(&bytes);
+        let loaded = input::Image {
+            bytes: memory,
+            segments: vec![input::Segment {
+                name: segment.path.clone(),
+                address: segment.address,
+                length: bytes.len(),
+            }],
+            patched: Vec::new(),
+            files: vec![input::File {
+                name: segment.path.clone(),
+                bytes: bytes.clone(),
+            }],
         };
+        let mut listing = analyze_from(&loaded, &[segment.address], options).slice(start..end);
+        for label in listing.labels.values_mut() {
+            label.name = format!("{}_{}", segment.name, label.name);
+        }
 
-        let comma = if !additional_text.is_empty() && !additional_bytes_text.is_empty() {
-            ","
-        } else {
-            ""
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{gray}; segment {} loaded at {start:04x}-{:04x} from {}{reset}",
+            segment.name,
+            end.saturating_sub(1),
+            segment.path
+        )?;
+        writeln!(out)?;
+        print_analyzed_listing(out, &loaded, &listing, options)?;
+    }
+    Ok(())
+}
+
+/// Address of a label, or else of a symbol, with this name.
+fn address_of(listing: &analysis::Listing, name: &str) -> Option<u16> {
+    listing
+        .labels
+        .iter()
+        .find(|(_, label)| label.name == name)
+        .map(|(&address, _)| address)
+        .or_else(|| {
+            let mut symbols = listing.symbols.iter();
+            symbols.find(|(_, symbol)| *symbol == name).map(|(&a, _)| a)
+        })
+}
+
+/// Part of the listing selected with --function or --around, commented with where its labels
+/// are referred to from, or the whole listing when neither is given.
+fn excerpt<'a>(
+    listing: Cow<'a, analysis::Listing>,
+    options: &Options,
+) -> Cow<'a, analysis::Listing> {
+    let range = if let Some(name) = &options.function {
+        let Some(address) = address_of(&listing, name) else {
+            error!(exitcode::DATAERR, "no label or symbol named \"{name}\"");
         };
+        listing.function_range(address).unwrap_or_else(|| {
+            error!(
+                exitcode::DATAERR,
+                "no function found at {name} (0x{address:04x}) by following the flow of execution"
+            )
+        })
+    } else if let Some((address, distance)) = options.around {
+        let address = usize::from(address);
+        address.saturating_sub(distance)..address + distance + 1
+    } else {
+        return listing;
+    };
 
-        let color = match text {
-            "NOP" => COLOR_GRAY,
-            _ => COLOR_RED,
+    let references = listing.references();
+    let mut excerpt = listing.slice(range);
+    let labels: Vec<u16> = excerpt.labels.keys().copied().collect();
+    for address in labels {
+        let from: Vec<String> = references
+            .get(&address)
+            .into_iter()
+            .flatten()
+            .map(|a| format!("0x{a:04x}"))
+            .collect();
+        if !from.is_empty() {
+            excerpt.add_comment(address, format!("referred to from {}", from.join(", ")));
+        }
+    }
+    Cow::Owned(excerpt)
+}
+
+/// Print the analyzed `listing`, or every byte of the image in order without one.
+fn print_listing(
+    out: &mut dyn std::io::Write,
+    image: &input::Image,
+    listing: Option<&analysis::Listing>,
+    options: &Options,
+) -> std::io::Result<()> {
+    if options.strict {
+        check(image, options);
+    }
+    if !options.forbid.is_empty() {
+        check_forbidden(image, options);
+    }
+    if let Some(listing) = listing {
+        return print_analyzed_listing(out, image, listing, options);
+    }
+
+    let padding = padding::find(image);
+    let (address, bytes) = match options.threads {
+        Some(threads) => print_parallel_sweep(out, image, &padding, threads, options)?,
+        None => print_sweep(out, image, &padding, options)?,
+    };
+
+    let Some(bytes) = bytes else {
+        return Ok(());
+    };
+    if !options.fail_on_truncated {
+        let comment = Some("truncated instruction");
+        return print_data(out, image, address, bytes, comment, options);
+    }
+    write!(out, "{address:04x}  ")?;
+    for byte in bytes {
+        write!(out, "{byte:02x} ")?;
+    }
+    writeln!(out)?;
+    out.flush()?;
+    let position = ["second", "third"][bytes.len() - 1];
+    error!(
+        exitcode::DATAERR,
+        "{:?}",
+        anyhow!("instruction incomplete").context(format!(
+            "reading {position} byte of instruction \"{:02x}\"",
+            bytes[0]
+        ))
+    )
+}
+
+/// Print the instructions of the image one after the other, summarizing the padding.
+///
+/// Returns where the decoding stopped, with the bytes of the instruction cut off there by
+/// the end of the image, if any.
+fn print_sweep<'a>(
+    out: &mut dyn std::io::Write,
+    image: &'a input::Image,
+    padding: &[padding::Padding],
+    options: &Options,
+) -> std::io::Result<(usize, Option<&'a [u8]>)> {
+    // Going on past the 64 KiB the 8080 can address, for dumps of several roms.
+    let mut address = usize::from(options.origin().unwrap_or(0));
+    loop {
+        // Summarize filler bytes instead of decoding them.
+        if let Some(block) = padding.iter().find(|p| p.range.contains(&address)) {
+            let length = block.range.end - address;
+            print_padding(out, address, length, block.byte, options.palette())?;
+            address = block.range.end;
+            continue;
+        }
+        let bytes = match decode::decode_from(&image.bytes, address).next() {
+            None => return Ok((address, None)),
+            Some(Err(_)) => return Ok((address, Some(&image.bytes[address..]))),
+            Some(Ok(decoded)) => decoded.bytes,
         };
+        let patch = image.patch_at(address..address + bytes.len());
+        print_instruction(out, address, bytes, options, None, patch)?;
+        address += bytes.len();
+    }
+}
+
+/// Lines printed by each thread of `print_parallel_sweep` at a time, bounding the output
+/// held in memory.
+const LINES_PER_THREAD: usize = 0x4000;
 
-        print!("   {color}{text}{COLOR_RESET}");
-        println!("\t{additional_text}{comma}{additional_bytes_text}");
+/// Line of the listing printed by `print_parallel_sweep`.
+enum Swept<'a> {
+    Instruction(&'a decode::DecodedLine),
+    /// Padding from an address to the end of its block.
+    Padding(usize, &'a padding::Padding),
+}
+
+/// Same as `print_sweep`, decoding and formatting on several threads.
+fn print_parallel_sweep<'a>(
+    out: &mut dyn std::io::Write,
+    image: &'a input::Image,
+    padding: &[padding::Padding],
+    threads: std::num::NonZeroUsize,
+    options: &Options,
+) -> std::io::Result<(usize, Option<&'a [u8]>)> {
+    let lines = decode::disassemble_parallel(&image.bytes, threads);
+    // Padding is made of one byte instructions, so the decoding is back in step at its end,
+    // as it is at the origin past the 0xFF filling the memory below.
+    let mut skipped = usize::from(options.origin().unwrap_or(0));
+    let mut swept = Vec::with_capacity(lines.len());
+    for line in &lines {
+        if line.address < skipped {
+            continue;
+        }
+        match padding.iter().find(|p| p.range.contains(&line.address)) {
+            Some(block) => {
+                swept.push(Swept::Padding(line.address, block));
+                skipped = block.range.end;
+            }
+            None => swept.push(Swept::Instruction(line)),
+        }
+    }
+
+    let print = |out: &mut Vec<u8>, swept: &Swept| match *swept {
+        Swept::Instruction(line) => {
+            let (address, bytes) = (line.address, &*line.bytes);
+            let patch = image.patch_at(address..address + bytes.len());
+            print_instruction(out, address, bytes, options, None, patch)
+        }
+        Swept::Padding(address, block) => {
+            let length = block.range.end - address;
+            print_padding(out, address, length, block.byte, options.palette())
+        }
+    };
+    for batch in swept.chunks(LINES_PER_THREAD * threads.get()) {
+        let printed: Vec<std::io::Result<Vec<u8>>> = std::thread::scope(|scope| {
+            // Every thread is started before waiting for any.
+            #[allow(clippy::needless_collect)]
+            let handles: Vec<_> = batch
+                .chunks(LINES_PER_THREAD)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let mut text = Vec::new();
+                        for swept in chunk {
+                            print(&mut text, swept)?;
+                        }
+                        Ok(text)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+                })
+                .collect()
+        });
+        for text in printed {
+            out.write_all(&text?)?;
+        }
+    }
+    out.flush()?;
+
+    let end = lines
+        .last()
+        .map_or(0, |line| line.address + line.bytes.len())
+        .max(skipped);
+    let rest = &image.bytes[end.min(image.bytes.len())..];
+    Ok((end, (!rest.is_empty()).then_some(rest)))
+}
+
+/// Report the problems found by following the flow of execution, exiting if there are any.
+fn check(image: &input::Image, options: &Options) {
+    let problems =
+        analysis::analyze_with_parameters(image, &options.entry_points(), &options.parameters)
+            .problems;
+    report_problems(&problems, options);
+}
+
+/// Report the forbidden instructions of the code, exiting if there are any.
+fn check_forbidden(image: &input::Image, options: &Options) {
+    let listing =
+        analysis::analyze_with_parameters(image, &options.entry_points(), &options.parameters);
+    report_problems(&forbid::violations(&listing, &options.forbid), options);
+}
+
+/// Report up to --max-errors problems and exit, if there are any.
+fn report_problems(problems: &[impl std::fmt::Display], options: &Options) {
+    let max_errors = options.max_errors.unwrap_or(MAX_ERRORS);
+    if problems.is_empty() {
+        return;
+    }
+
+    for problem in problems.iter().take(max_errors) {
+        report!("{problem}");
+    }
+    if problems.len() > max_errors {
+        let rest = problems.len() - max_errors;
+        report!(
+            "{rest} more problem{} not shown",
+            if rest == 1 { "" } else { "s" }
+        );
+    }
+    std::process::exit(exitcode::DATAERR);
+}
+
+/// Print the listing of the code found by following the flow of execution.
+fn print_analyzed_listing(
+    out: &mut dyn std::io::Write,
+    image: &input::Image,
+    listing: &analysis::Listing,
+    options: &Options,
+) -> std::io::Result<()> {
+    let Palette { reset, bold, .. } = options.palette();
+
+    for line in &listing.lines {
+        let address = usize::from(line.address());
+        if let Some(label) = listing.labels.get(&line.address()) {
+            writeln!(out, "{bold}{}:{reset}", label.name)?;
+        }
+
+        match line {
+            Line::Instruction { bytes, .. } => {
+                let patch = image.patch_at(address..address + bytes.len());
+                print_instruction(out, address, bytes, options, Some(listing), patch)?;
+            }
+            Line::Data { bytes, .. } => {
+                let comment = listing.comments.get(&line.address());
+                print_data(
+                    out,
+                    image,
+                    address,
+                    bytes,
+                    comment.map(String::as_str),
+                    options,
+                )?;
+            }
+            Line::Padding { length, byte, .. } => {
+                print_padding(out, address, *length, *byte, options.palette())?;
+            }
+        }
+    }
+
+    if let Some(sections) = options.appendix {
+        appendix::print(out, listing, sections, bold, reset)?;
+    }
+    Ok(())
+}
+
+/// Print bytes of data as `DB` lines, the first one followed by a comment.
+fn print_data(
+    out: &mut dyn std::io::Write,
+    image: &input::Image,
+    address: usize,
+    bytes: &[u8],
+    mut comment: Option<&str>,
+    options: &Options,
+) -> std::io::Result<()> {
+    let Palette {
+        reset, red, purple, ..
+    } = options.palette();
+    let layout = options.layout(Layout::LISTING);
+    // Values like "#0x00," take 6 columns.
+    let per_line = options.width.map_or(bytes.len(), |width| {
+        (width.saturating_sub(layout.operand_column) / 6).max(1)
+    });
+    for (index, chunk) in bytes.chunks(per_line).enumerate() {
+        let start = address + index * per_line;
+        let patch = image.patch_at(start..start + chunk.len());
+        let mut text = format!("{start:04x}{} ", gutter(patch));
+        layout.pad(&mut text, layout.mnemonic_column);
+        let _ = write!(text, "{red}DB{reset}");
+        layout.pad(&mut text, layout.operand_column);
+        let values: Vec<String> = chunk
+            .iter()
+            .map(|&byte| {
+                let mut value = String::new();
+                // Writing to a string never fails.
+                let _ = Syntax::LISTING.write_byte(byte, &mut value);
+                value
+            })
+            .collect();
+        let _ = write!(text, "{purple}{}{reset}", values.join(","));
+        match comment.take() {
+            Some(comment) => print_with_comment(out, text, comment, options, layout)?,
+            None => writeln!(out, "{text}")?,
+        }
+    }
+    Ok(())
+}
+
+/// Narrowest room for a comment beside an instruction when wrapping to a width.
+const MIN_COMMENT_WIDTH: usize = 20;
+
+/// Print a line followed by a comment, continued on the next lines at the same column when
+/// it does not fit in the width.
+fn print_with_comment(
+    out: &mut dyn std::io::Write,
+    mut line: String,
+    comment: &str,
+    options: &Options,
+    layout: Layout,
+) -> std::io::Result<()> {
+    let Palette { reset, gray, .. } = options.palette();
+    layout.pad(&mut line, layout.comment_column);
+    let mut column = layout::width(&line);
+    let Some(width) = options
+        .width
+        .filter(|&width| column + 2 + comment.chars().count() > width)
+    else {
+        return writeln!(out, "{line}{gray}; {comment}{reset}");
+    };
+
+    // Comments squeezed beside the instruction start below it instead.
+    if column + MIN_COMMENT_WIDTH > width {
+        writeln!(out, "{}", line.trim_end())?;
+        column = layout.mnemonic_column;
+        line.clear();
+        layout.pad(&mut line, column);
+    }
+    for piece in layout::wrap(comment, width.saturating_sub(column + 2)) {
+        writeln!(out, "{line}{gray}; {piece}{reset}")?;
+        line.clear();
+        layout.pad(&mut line, column);
+    }
+    Ok(())
+}
+
+fn print_padding(
+    out: &mut dyn std::io::Write,
+    address: usize,
+    length: usize,
+    byte: u8,
+    palette: Palette,
+) -> std::io::Result<()> {
+    let Palette { reset, gray, .. } = palette;
+    writeln!(
+        out,
+        "{address:04x}  {gray}; 0x{length:04x} bytes of 0x{byte:02X} padding{reset}"
+    )
+}
+
+/// Mark following the address of the lines changed by a patch.
+const fn gutter(patch: Option<&str>) -> char {
+    if patch.is_some() {
+        '*'
+    } else {
+        ' '
+    }
+}
+
+/// Print a line with the address, bytes and assembly of an instruction.
+///
+/// Addresses with a label are referred to by its name. Instructions changed by a patch are
+/// marked, and commented with its name unless the listing already does.
+fn print_instruction(
+    out: &mut dyn std::io::Write,
+    address: usize,
+    bytes: &[u8],
+    options: &Options,
+    listing: Option<&analysis::Listing>,
+    patch: Option<&str>,
+) -> std::io::Result<()> {
+    let layout = options.layout(Layout::LISTING);
+    let Palette {
+        reset,
+        red,
+        blue,
+        purple,
+        gray,
+        ..
+    } = options.palette();
+    let syntax = Syntax::LISTING;
+    // Writing to a string never fails.
+    let mut line = format!("{address:04x}{}", gutter(patch));
+    for byte in bytes {
+        let _ = write!(line, " {byte:02x}");
+    }
+
+    // Translate the instruction to assembly.
+    let opcode = opcodes::lookup(bytes[0]);
+    let text = opcode.mnemonic;
+    let additional_text = opcode.registers;
+
+    let immediate = match *bytes {
+        [_, low, high] => Some(u16::from_le_bytes([low, high])),
+        [_, low] => Some(u16::from(low)),
+        _ => None,
+    };
+    let name = immediate
+        .filter(|_| bytes.len() == 3)
+        .and_then(|value| listing?.operand_name(&opcode, value));
+    let mut operand = String::new();
+    // Writing to a string never fails.
+    let _ = match (bytes, name) {
+        (&[_, value], _) => syntax.write_byte(value, &mut operand),
+        (_, Some(name)) => operand.write_str(name),
+        (&[_, low, high], None) => syntax.write_word(u16::from_le_bytes([low, high]), &mut operand),
+        _ => Ok(()),
+    };
+    let mut additional_bytes_text = match bytes.len() {
+        2 => format!("{purple}{operand}{reset}"),
+        3 => format!("{blue}{operand}{reset}"),
+        _ => String::new(),
+    };
+    if let (Some(url), Some(target)) = (options.links(), immediate) {
+        if opcode.immediate == opcodes::Immediate::Address && !opcode.has_data_immediate() {
+            let address = format!("{target:04x}");
+            #[allow(clippy::literal_string_with_formatting_args)]
+            let url = url
+                .replace("{address}", &address)
+                .replace("{name}", name.unwrap_or(&address));
+            // OSC 8 escape sequences around the text of a hyperlink.
+            additional_bytes_text =
+                format!("\x1b]8;;{url}\x1b\\{additional_bytes_text}\x1b]8;;\x1b\\");
+        }
+    }
+
+    let comma = if !additional_text.is_empty() && !additional_bytes_text.is_empty() {
+        ","
+    } else {
+        ""
+    };
+
+    let color = match text {
+        "NOP" => gray,
+        _ => red,
+    };
+
+    layout.pad(&mut line, layout.mnemonic_column);
+    let _ = write!(line, "{color}{text}{reset}");
+    if !additional_text.is_empty() || !additional_bytes_text.is_empty() {
+        layout.pad(&mut line, layout.operand_column);
+        let _ = write!(line, "{additional_text}{comma}{additional_bytes_text}");
+    }
+
+    let comments = comments(address, bytes, options, listing, patch, name);
+    if comments.is_empty() {
+        writeln!(out, "{line}")?;
+    } else {
+        print_with_comment(out, line, &comments.join("; "), options, layout)?;
+    }
+
+    if options.teach {
+        for (flag, rule) in explain::flag_rules(&opcode) {
+            let mut line = String::new();
+            layout.pad(&mut line, layout.comment_column);
+            writeln!(out, "{line}{gray};   {flag:<2} {rule}{reset}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Comments of an instruction: those of the listing or its patch, then the values and
+/// explanations asked for.
+fn comments(
+    address: usize,
+    bytes: &[u8],
+    options: &Options,
+    listing: Option<&analysis::Listing>,
+    patch: Option<&str>,
+    name: Option<&str>,
+) -> Vec<String> {
+    let opcode = opcodes::lookup(bytes[0]);
+    let immediate = match *bytes {
+        [_, low, high] => Some(u16::from_le_bytes([low, high])),
+        [_, low] => Some(u16::from(low)),
+        _ => None,
+    };
+    let mut comments = Vec::new();
+    // Addresses of the image always fit in 16 bits.
+    let address16 = u16::try_from(address).unwrap_or(u16::MAX);
+    match (listing, patch) {
+        (Some(listing), _) => comments.extend(listing.comments.get(&address16).cloned()),
+        (None, Some(name)) => comments.push(format!("patched by {name}")),
+        (None, None) => {}
+    }
+    match (immediate, opcode.immediate) {
+        (Some(value), opcodes::Immediate::Byte) if options.decimal => {
+            comments.push(format!("={value}"));
+        }
+        (Some(value), _) if opcode.has_data_immediate() && options.decimal => {
+            comments.push(format!("={value} / 0x{value:04X}"));
+        }
+        (Some(value), _) if opcode.has_data_immediate() && name.is_some() => {
+            comments.push(format!("0x{value:04x}"));
+        }
+        _ => {}
+    }
+    if let (Some(charset), [_, value]) = (&options.charset, bytes) {
+        if let Some(character) = charset.get(*value) {
+            comments.push(format!("{character:?}"));
+        }
+    }
+    if options.explain {
+        comments.push(explain::describe(&opcode, immediate));
     }
+    comments
 }
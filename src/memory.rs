@@ -0,0 +1,35 @@
+//! Memory as the CPU sees it, for analyzing code where it lives instead of in a copy.
+//!
+//! Emulators keep their memory in banks switched in and out, mirrors and devices, rather
+//! than in a single flat array. Implementing [`MemoryView`] for them lets
+//! [`analysis::analyze_view`] and [`decode::decode_at`] read their live memory directly.
+
+#[cfg(doc)]
+use crate::{analysis, decode};
+
+/// Bytes the CPU reads at each address.
+pub trait MemoryView {
+    /// Byte read at an address, through whatever banks or mirrors are mapped there.
+    fn read(&self, address: u16) -> u8;
+}
+
+/// A rom loaded at address 0. Addresses past its end read as 0xFF, like a data bus with
+/// nothing driving it.
+impl MemoryView for [u8] {
+    fn read(&self, address: u16) -> u8 {
+        self.get(usize::from(address)).copied().unwrap_or(0xFF)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl MemoryView for alloc::vec::Vec<u8> {
+    fn read(&self, address: u16) -> u8 {
+        self.as_slice().read(address)
+    }
+}
+
+impl<T: MemoryView + ?Sized> MemoryView for &T {
+    fn read(&self, address: u16) -> u8 {
+        (**self).read(address)
+    }
+}
@@ -0,0 +1,147 @@
+//! Size and complexity of each function, to choose which to reverse first.
+
+use std::collections::BTreeSet;
+
+use intel_8080_disassembler::analysis::{self, Line, Listing};
+use intel_8080_disassembler::opcodes::{self, Immediate};
+
+/// Metrics of a function.
+struct Metrics {
+    address: u16,
+    name: String,
+    size: usize,
+    instructions: usize,
+    /// Conditional jumps, calls and returns, plus one.
+    complexity: usize,
+    /// Most loops nested in each other.
+    nesting: usize,
+    /// Functions calling this one.
+    fan_in: usize,
+    /// Functions called by this one.
+    fan_out: usize,
+}
+
+/// Print the metrics of each function as a table, or as a JSON array.
+pub fn print(out: &mut dyn std::io::Write, listing: &Listing, json: bool) -> std::io::Result<()> {
+    let metrics = measure(listing);
+    if json {
+        writeln!(out, "[")?;
+        for (index, m) in metrics.iter().enumerate() {
+            let separator = if index + 1 < metrics.len() { "," } else { "" };
+            writeln!(
+                out,
+                "{{\"address\":{},\"name\":\"{}\",\"size\":{},\"instructions\":{},\
+                 \"complexity\":{},\"nesting\":{},\"fan_in\":{},\"fan_out\":{}}}{separator}",
+                m.address,
+                escape(&m.name),
+                m.size,
+                m.instructions,
+                m.complexity,
+                m.nesting,
+                m.fan_in,
+                m.fan_out
+            )?;
+        }
+        return writeln!(out, "]");
+    }
+
+    writeln!(
+        out,
+        "address  size  instr  complexity  nesting  fan-in  fan-out  name"
+    )?;
+    for m in &metrics {
+        writeln!(
+            out,
+            "{:04x}     {:<5} {:<6} {:<11} {:<8} {:<7} {:<8} {}",
+            m.address, m.size, m.instructions, m.complexity, m.nesting, m.fan_in, m.fan_out, m.name
+        )?;
+    }
+    Ok(())
+}
+
+fn measure(listing: &Listing) -> Vec<Metrics> {
+    let mut metrics = Vec::new();
+    let mut callees = Vec::new();
+    for &address in &listing.functions {
+        let Some(range) = listing.function_range(address) else {
+            continue;
+        };
+        let instructions: Vec<(u16, &[u8])> = listing
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                Line::Instruction { address, bytes } if range.contains(&usize::from(*address)) => {
+                    Some((*address, bytes.as_slice()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let mut called = BTreeSet::new();
+        // First and last address of each loop, from a jump back to its start.
+        let mut loops: Vec<(u16, u16)> = Vec::new();
+        for &(at, bytes) in &instructions {
+            let opcode = opcodes::lookup(bytes[0]);
+            match *bytes {
+                // CALL and the conditional calls.
+                [_, low, high]
+                    if opcode.immediate == Immediate::Address
+                        && opcode.mnemonic.starts_with('C') =>
+                {
+                    called.insert(u16::from_le_bytes([low, high]));
+                }
+                _ if opcode.mnemonic == "RST" => {
+                    called.insert(opcode.registers.parse::<u16>().unwrap_or_default() * 8);
+                }
+                _ => {}
+            }
+            for target in analysis::successors(at, bytes) {
+                if target <= at && range.contains(&usize::from(target)) {
+                    loops.push((target, at));
+                }
+            }
+        }
+        let nesting = loops
+            .iter()
+            .map(|&(start, end)| {
+                loops
+                    .iter()
+                    .filter(|&&(outer_start, outer_end)| outer_start <= start && end <= outer_end)
+                    .map(|&(outer_start, _)| outer_start)
+                    .collect::<BTreeSet<u16>>()
+                    .len()
+            })
+            .max()
+            .unwrap_or(0);
+
+        metrics.push(Metrics {
+            address,
+            name: listing
+                .name(address)
+                .map_or_else(|| format!("0x{address:04x}"), str::to_owned),
+            size: range.len(),
+            instructions: instructions.len(),
+            complexity: 1 + instructions
+                .iter()
+                .filter(|(_, bytes)| opcodes::lookup(bytes[0]).is_conditional())
+                .count(),
+            nesting,
+            fan_in: 0,
+            fan_out: called.len(),
+        });
+        callees.push(called);
+    }
+
+    for function in &mut metrics {
+        function.fan_in = callees
+            .iter()
+            .filter(|called| called.contains(&function.address))
+            .count();
+    }
+    metrics
+}
+
+/// Escape the quotes and backslashes of a JSON string.
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,89 @@
+//! Output in the layout of `objdump -d`, for tools built around it.
+
+use intel_8080_disassembler::analysis::{Line, Listing};
+use intel_8080_disassembler::opcodes;
+
+/// Width of the column of bytes, as for the longest instructions of x86.
+const BYTES_WIDTH: usize = 21;
+
+/// Print the listing like `objdump -d` prints a raw binary, with a symbol for each label.
+pub fn print(
+    out: &mut dyn std::io::Write,
+    file_name: &str,
+    listing: &Listing,
+) -> std::io::Result<()> {
+    writeln!(out)?;
+    writeln!(out, "{file_name}:     file format binary")?;
+    writeln!(out)?;
+    writeln!(out)?;
+    writeln!(out, "Disassembly of section .data:")?;
+
+    for (index, line) in listing.lines.iter().enumerate() {
+        let address = line.address();
+        let symbol = listing.labels.get(&address).map_or_else(
+            || (index == 0).then(|| ".data".to_owned()),
+            |label| Some(label.name.clone()),
+        );
+        if let Some(symbol) = symbol {
+            writeln!(out)?;
+            writeln!(out, "{address:08x} <{symbol}>:")?;
+        }
+
+        match line {
+            Line::Instruction { bytes, .. } => {
+                writeln!(
+                    out,
+                    "{address:>4x}:\t{:BYTES_WIDTH$}\t{}",
+                    hex(bytes),
+                    instruction(bytes, listing)
+                )?;
+            }
+            Line::Data { bytes, .. } => {
+                let values: Vec<String> = bytes.iter().map(|b| format!("0x{b:x}")).collect();
+                writeln!(
+                    out,
+                    "{address:>4x}:\t{:BYTES_WIDTH$}\t.byte {}",
+                    hex(bytes),
+                    values.join(",")
+                )?;
+            }
+            // objdump skips blocks of zeros the same way.
+            Line::Padding { .. } => writeln!(out, "\t...")?,
+        }
+    }
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    bytes.join(" ")
+}
+
+/// Assembly in lowercase, with the symbol of the addresses that have one.
+fn instruction(bytes: &[u8], listing: &Listing) -> String {
+    let opcode = opcodes::lookup(bytes[0]);
+    let mut operands: Vec<String> = opcode
+        .registers
+        .split(',')
+        .filter(|r| !r.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect();
+    match *bytes {
+        [_, low, high] => {
+            let value = u16::from_le_bytes([low, high]);
+            let symbol = listing
+                .operand_name(&opcode, value)
+                .map_or_else(String::new, |name| format!(" <{name}>"));
+            operands.push(format!("0x{value:x}{symbol}"));
+        }
+        [_, value] => operands.push(format!("0x{value:x}")),
+        _ => {}
+    }
+
+    let mnemonic = opcode.mnemonic.to_ascii_lowercase();
+    if operands.is_empty() {
+        mnemonic
+    } else {
+        format!("{mnemonic:<6} {}", operands.join(","))
+    }
+}
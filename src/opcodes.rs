@@ -0,0 +1,316 @@
+//! Reference data for every opcode of the Intel 8080.
+
+use core::fmt;
+
+#[cfg(feature = "alloc")]
+use alloc::{format, string::String};
+
+/// Kind of immediate data following the opcode byte.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Immediate {
+    /// The instruction is a single byte.
+    Empty,
+    /// One byte of data.
+    Byte,
+    /// Two bytes of data, little endian.
+    Word,
+    /// Two bytes holding a memory address, little endian.
+    Address,
+    /// One byte holding an I/O port number.
+    Port,
+}
+
+impl Immediate {
+    /// Number of bytes taken by the immediate data.
+    #[must_use]
+    pub const fn length(self) -> usize {
+        match self {
+            Self::Empty => 0,
+            Self::Byte | Self::Port => 1,
+            Self::Word | Self::Address => 2,
+        }
+    }
+
+    /// Placeholder used for the immediate in reference tables.
+    #[must_use]
+    pub const fn placeholder(self) -> &'static str {
+        match self {
+            Self::Empty => "",
+            Self::Byte => "d8",
+            Self::Word => "d16",
+            Self::Address => "a16",
+            Self::Port => "p8",
+        }
+    }
+}
+
+/// Number of clock states an instruction takes.
+///
+/// Conditional calls and returns are faster when the condition is not met.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Cycles {
+    pub taken: u8,
+    pub not_taken: u8,
+}
+
+impl Cycles {
+    const fn fixed(cycles: u8) -> Self {
+        Self {
+            taken: cycles,
+            not_taken: cycles,
+        }
+    }
+
+    const fn conditional(taken: u8, not_taken: u8) -> Self {
+        Self { taken, not_taken }
+    }
+}
+
+impl fmt::Display for Cycles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.taken == self.not_taken {
+            write!(f, "{}", self.taken)
+        } else {
+            write!(f, "{}/{}", self.taken, self.not_taken)
+        }
+    }
+}
+
+/// Set of condition flags, using the bit layout of the PSW register.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Flags(u8);
+
+impl Flags {
+    pub const NONE: Self = Self(0);
+    pub const S: Self = Self(0x80);
+    pub const Z: Self = Self(0x40);
+    pub const AC: Self = Self(0x10);
+    pub const P: Self = Self(0x04);
+    pub const CY: Self = Self(0x01);
+    pub const SZAP: Self = Self(0xD4);
+    pub const ALL: Self = Self(0xD5);
+
+    /// Every flag paired with its single letter name, in PSW order.
+    pub const NAMED: [(Self, char); 5] = [
+        (Self::S, 'S'),
+        (Self::Z, 'Z'),
+        (Self::AC, 'A'),
+        (Self::P, 'P'),
+        (Self::CY, 'C'),
+    ];
+
+    /// Bits of the flags in the PSW register.
+    #[must_use]
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl fmt::Display for Flags {
+    /// Print the flags as "SZAPC", with a dash in place of unaffected ones.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (flag, name) in Self::NAMED {
+            let c = if self.contains(flag) { name } else { '-' };
+            write!(f, "{c}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything known about a single opcode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Opcode {
+    pub mnemonic: &'static str,
+    /// Registers or vector encoded in the opcode itself, e.g. "B,C" or "7".
+    pub registers: &'static str,
+    pub immediate: Immediate,
+    pub cycles: Cycles,
+    /// Flags modified by the instruction.
+    pub flags: Flags,
+}
+
+impl Opcode {
+    /// Length of the whole instruction in bytes.
+    #[must_use]
+    pub const fn length(&self) -> usize {
+        1 + self.immediate.length()
+    }
+
+    /// Operands as written in reference tables, e.g. "B,d16".
+    #[cfg(feature = "alloc")]
+    #[must_use]
+    pub fn operands(&self) -> String {
+        let placeholder = self.immediate.placeholder();
+        let comma = if !self.registers.is_empty() && !placeholder.is_empty() {
+            ","
+        } else {
+            ""
+        };
+        format!("{}{comma}{placeholder}", self.registers)
+    }
+
+    /// Whether the instruction is a jump, call or return depending on a condition flag.
+    #[must_use]
+    pub fn is_conditional(&self) -> bool {
+        // Conditional jumps take the same time either way, unlike calls and returns.
+        self.cycles.taken != self.cycles.not_taken
+            || (self.mnemonic.starts_with('J') && self.mnemonic != "JMP")
+    }
+
+    /// Whether the 16-bit immediate is a constant or an address read or written, rather
+    /// than the target of a jump or call.
+    #[must_use]
+    pub const fn has_data_immediate(&self) -> bool {
+        match self.immediate {
+            Immediate::Word => true,
+            // LDA, STA, LHLD and SHLD, the others being jumps and calls.
+            Immediate::Address => !matches!(self.mnemonic.as_bytes()[0], b'J' | b'C'),
+            Immediate::Empty | Immediate::Byte | Immediate::Port => false,
+        }
+    }
+}
+
+const fn op(
+    mnemonic: &'static str,
+    registers: &'static str,
+    immediate: Immediate,
+    cycles: Cycles,
+    flags: Flags,
+) -> Opcode {
+    Opcode {
+        mnemonic,
+        registers,
+        immediate,
+        cycles,
+        flags,
+    }
+}
+
+/// Reference data of every opcode, indexed by the opcode.
+///
+/// Undocumented opcodes hold the instruction the 8080 executes for them. The table is
+/// generated by `build.rs` from `data/opcodes.csv`.
+pub static OPCODES: [Opcode; 256] = include!(concat!(env!("OUT_DIR"), "/opcodes.rs"));
+
+/// Look up the reference data of an opcode.
+///
+/// Undocumented opcodes are reported as the instruction the 8080 executes for them.
+#[must_use]
+pub const fn lookup(opcode: u8) -> Opcode {
+    OPCODES[opcode as usize]
+}
+
+/// Whether the opcode is an undocumented alias of another instruction.
+#[must_use]
+pub const fn is_undocumented(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        0x08 | 0x10 | 0x18 | 0x20 | 0x28 | 0x30 | 0x38 | 0xCB | 0xD9 | 0xDD | 0xED | 0xFD
+    )
+}
+
+/// Documented opcode of the same instruction, which is the one assemblers produce.
+///
+/// Undocumented aliases map to the instruction they behave like, and every other opcode to
+/// itself.
+#[must_use]
+pub const fn canonical_opcode(opcode: u8) -> u8 {
+    match opcode {
+        0x08 | 0x10 | 0x18 | 0x20 | 0x28 | 0x30 | 0x38 => 0x00,
+        0xCB => 0xC3,
+        0xD9 => 0xC9,
+        0xDD | 0xED | 0xFD => 0xCD,
+        _ => opcode,
+    }
+}
+
+/// One line description of what a mnemonic does.
+#[must_use]
+pub fn summary(mnemonic: &str) -> Option<&'static str> {
+    Some(match mnemonic {
+        "ACI" => "add immediate to A with carry",
+        "ADC" => "add register or memory to A with carry",
+        "ADD" => "add register or memory to A",
+        "ADI" => "add immediate to A",
+        "ANA" => "logical AND register or memory with A",
+        "ANI" => "logical AND immediate with A",
+        "CALL" => "call subroutine",
+        "CC" => "call subroutine if carry",
+        "CM" => "call subroutine if minus",
+        "CMA" => "complement A",
+        "CMC" => "complement carry",
+        "CMP" => "compare register or memory with A",
+        "CNC" => "call subroutine if no carry",
+        "CNZ" => "call subroutine if not zero",
+        "CP" => "call subroutine if plus",
+        "CPE" => "call subroutine if parity even",
+        "CPI" => "compare immediate with A",
+        "CPO" => "call subroutine if parity odd",
+        "CZ" => "call subroutine if zero",
+        "DAA" => "decimal adjust A",
+        "DAD" => "add register pair to HL",
+        "DCR" => "decrement register or memory",
+        "DCX" => "decrement register pair",
+        "DI" => "disable interrupts",
+        "EI" => "enable interrupts",
+        "HLT" => "halt until an interrupt",
+        "IN" => "read A from input port",
+        "INR" => "increment register or memory",
+        "INX" => "increment register pair",
+        "JC" => "jump if carry",
+        "JM" => "jump if minus",
+        "JMP" => "jump unconditionally",
+        "JNC" => "jump if no carry",
+        "JNZ" => "jump if not zero",
+        "JP" => "jump if plus",
+        "JPE" => "jump if parity even",
+        "JPO" => "jump if parity odd",
+        "JZ" => "jump if zero",
+        "LDA" => "load A from memory",
+        "LDAX" => "load A indirectly through register pair",
+        "LHLD" => "load HL from memory",
+        "LXI" => "load immediate into register pair",
+        "MOV" => "move register or memory to register or memory",
+        "MVI" => "move immediate to register or memory",
+        "NOP" => "no operation",
+        "ORA" => "logical OR register or memory with A",
+        "ORI" => "logical OR immediate with A",
+        "OUT" => "write A to output port",
+        "PCHL" => "jump to address in HL",
+        "POP" => "pop register pair from the stack",
+        "PUSH" => "push register pair on the stack",
+        "RAL" => "rotate A left through carry",
+        "RAR" => "rotate A right through carry",
+        "RC" => "return if carry",
+        "RET" => "return from subroutine",
+        "RLC" => "rotate A left",
+        "RM" => "return if minus",
+        "RNC" => "return if no carry",
+        "RNZ" => "return if not zero",
+        "RP" => "return if plus",
+        "RPE" => "return if parity even",
+        "RPO" => "return if parity odd",
+        "RRC" => "rotate A right",
+        "RST" => "call restart vector",
+        "RZ" => "return if zero",
+        "SBB" => "subtract register or memory from A with borrow",
+        "SBI" => "subtract immediate from A with borrow",
+        "SHLD" => "store HL to memory",
+        "SPHL" => "move HL to SP",
+        "STA" => "store A to memory",
+        "STAX" => "store A indirectly through register pair",
+        "STC" => "set carry",
+        "SUB" => "subtract register or memory from A",
+        "SUI" => "subtract immediate from A",
+        "XCHG" => "exchange DE with HL",
+        "XRA" => "logical XOR register or memory with A",
+        "XRI" => "logical XOR immediate with A",
+        "XTHL" => "exchange top of stack with HL",
+        _ => return None,
+    })
+}
@@ -0,0 +1,57 @@
+//! Guessing the address a rom is loaded at from the addresses its code jumps to.
+
+use crate::decode;
+use crate::instruction::Instruction;
+
+/// Addresses roms are commonly loaded at: the reset vector, the CP/M program area, and the
+/// bases of the memory boards and monitor roms of the era.
+pub const CANDIDATES: [u16; 12] = [
+    0x0000, 0x0100, 0x0800, 0x1000, 0x2000, 0x4000, 0x8000, 0xC000, 0xD000, 0xE000, 0xF000, 0xF800,
+];
+
+/// Address a rom may be loaded at, with how well its code agrees.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Candidate {
+    pub origin: u16,
+    /// Jumps and calls to an address inside the rom when loaded there.
+    pub inside: usize,
+    /// Jumps and calls found in the rom.
+    pub references: usize,
+}
+
+/// Score each of the `CANDIDATES` the rom fits at, best first.
+///
+/// Code jumps and calls within itself far more often than elsewhere, so the origin at which
+/// most absolute jump and call targets land inside the rom is likely the right one. The rom
+/// is decoded linearly, and the targets decoded from data only add noise spread evenly over
+/// the candidates. Candidates with the same score keep their order, lowest address first.
+#[must_use]
+pub fn rank(rom: &[u8]) -> Vec<Candidate> {
+    let targets: Vec<u16> = decode::disassemble_all(rom, 0)
+        .into_iter()
+        .filter_map(|line| match line.instruction {
+            Instruction::Jmp(target)
+            | Instruction::Jcc(_, target)
+            | Instruction::Call(target)
+            | Instruction::Ccc(_, target) => Some(target),
+            _ => None,
+        })
+        .collect();
+    let mut candidates: Vec<Candidate> = CANDIDATES
+        .into_iter()
+        .filter(|&origin| usize::from(origin) + rom.len() <= 0x10000)
+        .map(|origin| {
+            let range = usize::from(origin)..usize::from(origin) + rom.len();
+            Candidate {
+                origin,
+                inside: targets
+                    .iter()
+                    .filter(|&&target| range.contains(&usize::from(target)))
+                    .count(),
+                references: targets.len(),
+            }
+        })
+        .collect();
+    candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.inside));
+    candidates
+}
@@ -0,0 +1,63 @@
+//! Detection of the filler bytes left at the end of rom regions.
+
+use std::ops::Range;
+
+use crate::input::Image;
+
+/// Shortest run of filler bytes reported as padding.
+const MIN_LENGTH: usize = 16;
+
+/// Block of identical filler bytes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Padding {
+    pub range: Range<usize>,
+    pub byte: u8,
+}
+
+/// Find the runs of 0x00 or 0xFF ending each segment, and the gaps between segments.
+#[must_use]
+pub fn find(image: &Image) -> Vec<Padding> {
+    let mut regions: Vec<Range<usize>> = image
+        .segments
+        .iter()
+        .map(|s| usize::from(s.address)..usize::from(s.address) + s.length)
+        .collect();
+    regions.sort_by_key(|r| r.start);
+
+    // Bytes not loaded from any file are regions of their own.
+    let mut gaps = Vec::new();
+    let mut covered = 0;
+    for region in &regions {
+        if region.start > covered {
+            gaps.push(covered..region.start);
+        }
+        covered = covered.max(region.end);
+    }
+    if covered < image.bytes.len() {
+        gaps.push(covered..image.bytes.len());
+    }
+    regions.extend(gaps);
+    regions.sort_by_key(|r| r.start);
+
+    regions
+        .into_iter()
+        .filter_map(|region| trailing_fill(&image.bytes, region))
+        .collect()
+}
+
+fn trailing_fill(bytes: &[u8], region: Range<usize>) -> Option<Padding> {
+    let last = *bytes.get(region.clone()).and_then(<[u8]>::last)?;
+    if last != 0x00 && last != 0xFF {
+        return None;
+    }
+
+    let length = bytes[region.clone()]
+        .iter()
+        .rev()
+        .take_while(|&&b| b == last)
+        .count();
+    (length >= MIN_LENGTH).then(|| Padding {
+        range: region.end - length..region.end,
+        byte: last,
+    })
+}
@@ -0,0 +1,81 @@
+//! Passes annotating the listing once the flow of execution has been followed.
+
+/// Pass run over the analyzed listing.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pass {
+    /// CP/M system addresses, BDOS calls and file control blocks.
+    Cpm,
+    /// Names and comments of the project file.
+    Project,
+    /// Instructions setting the flags tested by conditions.
+    FlagSources,
+    /// Memory reached through register pairs.
+    Pointers,
+    /// Lines changed by the patches applied to the image.
+    Patches,
+}
+
+impl Pass {
+    /// Every pass, in the order they run by default.
+    pub const ALL: [Self; 5] = [
+        Self::Cpm,
+        Self::Project,
+        Self::FlagSources,
+        Self::Pointers,
+        Self::Patches,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Cpm => "cpm",
+            Self::Project => "project",
+            Self::FlagSources => "flag-sources",
+            Self::Pointers => "pointers",
+            Self::Patches => "patches",
+        }
+    }
+
+    const fn description(self) -> &'static str {
+        match self {
+            Self::Cpm => "name CP/M addresses and comment BDOS calls, with --profile cpm",
+            Self::Project => "name and comment addresses after the project file",
+            Self::FlagSources => "comment conditions with the instruction setting their flag",
+            Self::Pointers => "comment accesses through register pairs with what they reach",
+            Self::Patches => "comment the lines changed by --apply-patch",
+        }
+    }
+
+    /// Whether the pass runs without a list of passes, rather than only with its option.
+    pub const fn is_default(self) -> bool {
+        matches!(self, Self::Cpm | Self::Project | Self::Patches)
+    }
+
+    /// Parse a list of pass names separated by commas, in the order they run, or `none`.
+    pub fn from_names(names: &str) -> Option<Vec<Self>> {
+        if names == "none" {
+            return Some(Vec::new());
+        }
+        let mut passes = Vec::new();
+        for name in names.split(',') {
+            let pass = Self::ALL.into_iter().find(|pass| pass.name() == name)?;
+            if passes.contains(&pass) {
+                return None;
+            }
+            passes.push(pass);
+        }
+        Some(passes)
+    }
+}
+
+/// Print the name and description of every pass, in the order they run by default.
+pub fn list(out: &mut dyn std::io::Write) -> std::io::Result<()> {
+    for pass in Pass::ALL {
+        let default = if pass.is_default() {
+            ""
+        } else {
+            " (off by default)"
+        };
+        writeln!(out, "{:<14}{}{default}", pass.name(), pass.description())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,530 @@
+//! Changes made to a rom, as patch files that can be shared without the rom itself.
+
+use std::fmt::Write;
+
+use crate::error::DisasmError;
+
+/// Run of consecutive bytes changed by a patch.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Change {
+    pub offset: usize,
+    /// Bytes before the patch.
+    pub original: Vec<u8>,
+    /// Bytes after the patch.
+    pub patched: Vec<u8>,
+}
+
+/// Format of a patch file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// International Patching System, understood by most rom patchers.
+    Ips,
+    /// Lines like `0100: 3e 00 -> af 00`, for reading and reviewing.
+    Text,
+}
+
+impl Format {
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ips" => Some(Self::Ips),
+            "text" => Some(Self::Text),
+            _ => None,
+        }
+    }
+}
+
+/// Apply an IPS or BPS patch to a rom, recognized by its header.
+///
+/// # Errors
+///
+/// Fails when the patch is in neither format or is cut off, and when a BPS patch was made
+/// for another rom.
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, DisasmError> {
+    let (format, patched) = if let Some(records) = patch.strip_prefix(b"PATCH") {
+        ("IPS", apply_ips(rom, records))
+    } else if patch.starts_with(b"BPS1") {
+        ("BPS", apply_bps(rom, patch))
+    } else {
+        return Err(DisasmError::Patch(
+            "expected an IPS or BPS patch".to_owned(),
+        ));
+    };
+    patched.map_err(|message| DisasmError::Patch(format!("{format} patch {message}")))
+}
+
+/// Apply IPS records, failing with what is wrong with them.
+fn apply_ips(rom: &[u8], mut records: &[u8]) -> Result<Vec<u8>, String> {
+    let mut rom = rom.to_vec();
+    let mut take = |count: usize| -> Result<&[u8], String> {
+        if records.len() < count {
+            return Err("cut off".to_owned());
+        }
+        let (taken, rest) = records.split_at(count);
+        records = rest;
+        Ok(taken)
+    };
+    loop {
+        let offset = take(3)?;
+        if offset == b"EOF" {
+            break;
+        }
+        let offset =
+            usize::from(offset[0]) << 16 | usize::from(offset[1]) << 8 | usize::from(offset[2]);
+        let length = take(2)?;
+        let length = usize::from(u16::from_be_bytes([length[0], length[1]]));
+        let bytes = if length == 0 {
+            // Run of a single byte.
+            let run = take(3)?;
+            vec![run[2]; usize::from(u16::from_be_bytes([run[0], run[1]]))]
+        } else {
+            take(length)?.to_vec()
+        };
+        if rom.len() < offset + bytes.len() {
+            rom.resize(offset + bytes.len(), 0);
+        }
+        rom[offset..offset + bytes.len()].copy_from_slice(&bytes);
+    }
+    // Some patchers follow the end with the length to truncate the rom to.
+    if let [high, middle, low] = *records {
+        rom.truncate(usize::from(high) << 16 | usize::from(middle) << 8 | usize::from(low));
+    }
+    Ok(rom)
+}
+
+/// Largest rom a BPS patch may make, far beyond the 64 KiB of the 8080, so that a damaged
+/// or hostile patch cannot exhaust memory.
+const MAX_BPS_TARGET: usize = 16 << 20;
+
+/// What is wrong with a BPS patch holding a number that does not fit in memory.
+const TOO_LARGE: &str = "holds a number too large";
+
+/// Reader of the variable length numbers of BPS patches.
+struct BpsReader<'a> {
+    patch: &'a [u8],
+    position: usize,
+}
+
+impl BpsReader<'_> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self.patch.get(self.position).ok_or("cut off")?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn number(&mut self) -> Result<usize, String> {
+        let mut number: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.byte()?;
+            number = usize::from(byte & 0x7F)
+                .checked_mul(shift)
+                .and_then(|n| n.checked_add(number))
+                .ok_or(TOO_LARGE)?;
+            if byte & 0x80 != 0 {
+                return Ok(number);
+            }
+            shift = shift.checked_shl(7).ok_or(TOO_LARGE)?;
+            number = number.checked_add(shift).ok_or(TOO_LARGE)?;
+        }
+    }
+
+    /// Offset moved by a signed amount.
+    fn relative(&mut self, offset: usize) -> Result<usize, String> {
+        let delta = self.number()?;
+        let moved = if delta & 1 == 0 {
+            offset.checked_add(delta >> 1)
+        } else {
+            offset.checked_sub(delta >> 1)
+        };
+        moved.ok_or_else(|| "moves an offset out of range".to_owned())
+    }
+}
+
+/// Apply a BPS patch, failing with what is wrong with it.
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let Some(footer_start) = patch.len().checked_sub(12).filter(|&start| start >= 4) else {
+        return Err("cut off".to_owned());
+    };
+    let crc =
+        |at: usize| u32::from_le_bytes([patch[at], patch[at + 1], patch[at + 2], patch[at + 3]]);
+    if crc32fast::hash(&patch[..patch.len() - 4]) != crc(footer_start + 8) {
+        return Err("damaged, its checksum does not match".to_owned());
+    }
+    if crc32fast::hash(rom) != crc(footer_start) {
+        return Err("made for another rom, its checksum does not match".to_owned());
+    }
+
+    let mut reader = BpsReader {
+        patch: &patch[..footer_start],
+        position: 4,
+    };
+    let source_length = reader.number()?;
+    let target_length = reader.number()?;
+    let metadata_length = reader.number()?;
+    reader.position = reader
+        .position
+        .checked_add(metadata_length)
+        .filter(|&position| position <= footer_start)
+        .ok_or("cut off")?;
+    if source_length != rom.len() {
+        return Err(format!("made for a rom of {source_length} bytes"));
+    }
+    if target_length > MAX_BPS_TARGET {
+        return Err(format!(
+            "makes a rom of {target_length} bytes, more than {MAX_BPS_TARGET}"
+        ));
+    }
+
+    let mut target = Vec::with_capacity(target_length);
+    let (mut source_offset, mut target_offset) = (0, 0);
+    while reader.position < footer_start {
+        let command = reader.number()?;
+        let length = (command >> 2) + 1;
+        if length > target_length - target.len() {
+            return Err("writes past the end of the rom it makes".to_owned());
+        }
+        // Bytes of the rom from an offset, failing when they run past its end.
+        let read = |start: usize| {
+            start
+                .checked_add(length)
+                .and_then(|end| rom.get(start..end))
+                .ok_or("reads past the rom")
+        };
+        match command & 3 {
+            // Bytes of the rom at the same offset.
+            0 => target.extend_from_slice(read(target.len())?),
+            // Bytes of the patch.
+            1 => {
+                for _ in 0..length {
+                    target.push(reader.byte()?);
+                }
+            }
+            // Bytes of the rom elsewhere.
+            2 => {
+                source_offset = reader.relative(source_offset)?;
+                target.extend_from_slice(read(source_offset)?);
+                // The bytes were read, so their end is in the rom.
+                source_offset += length;
+            }
+            // Bytes written earlier, possibly overlapping the ones being written.
+            _ => {
+                target_offset = reader.relative(target_offset)?;
+                for _ in 0..length {
+                    let byte = *target.get(target_offset).ok_or("reads past the output")?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_length || crc32fast::hash(&target) != crc(footer_start + 4) {
+        return Err("gives a rom that does not match its checksum".to_owned());
+    }
+    Ok(target)
+}
+
+/// Runs of bytes that differ between a rom and its patched copy of the same length.
+#[must_use]
+pub fn changes(original: &[u8], patched: &[u8]) -> Vec<Change> {
+    let mut changes: Vec<Change> = Vec::new();
+    for (offset, (&before, &after)) in original.iter().zip(patched).enumerate() {
+        if before == after {
+            continue;
+        }
+        match changes.last_mut() {
+            Some(change) if change.offset + change.patched.len() == offset => {
+                change.original.push(before);
+                change.patched.push(after);
+            }
+            _ => changes.push(Change {
+                offset,
+                original: vec![before],
+                patched: vec![after],
+            }),
+        }
+    }
+    changes
+}
+
+/// Offset that IPS readers take for the end of the file.
+const IPS_EOF: usize = 0x45_4F46;
+
+/// Write changes as an IPS patch.
+///
+/// `original` is the rom the changes apply to. A change at the offset spelling "EOF" starts
+/// one byte earlier, rewriting that byte with its original value.
+///
+/// # Errors
+///
+/// Fails when a change lies beyond the 16 MiB that IPS offsets reach.
+pub fn to_ips(changes: &[Change], original: &[u8]) -> Result<Vec<u8>, DisasmError> {
+    let mut ips = b"PATCH".to_vec();
+    for change in changes {
+        let (offset, bytes) = if change.offset == IPS_EOF {
+            let mut bytes = vec![original[IPS_EOF - 1]];
+            bytes.extend_from_slice(&change.patched);
+            (IPS_EOF - 1, bytes)
+        } else {
+            (change.offset, change.patched.clone())
+        };
+        if offset >= 1 << 24 {
+            return Err(DisasmError::Patch(format!(
+                "offset 0x{offset:x} is beyond the reach of IPS"
+            )));
+        }
+        let chunk_length = usize::from(u16::MAX);
+        for (index, chunk) in bytes.chunks(chunk_length).enumerate() {
+            let [_, high, middle, low] = u32::try_from(offset + index * chunk_length)
+                .unwrap_or(u32::MAX)
+                .to_be_bytes();
+            ips.extend_from_slice(&[high, middle, low]);
+            // Chunks are at most 0xFFFF bytes long.
+            let length = u16::try_from(chunk.len()).unwrap_or(u16::MAX);
+            ips.extend_from_slice(&length.to_be_bytes());
+            ips.extend_from_slice(chunk);
+        }
+    }
+    ips.extend_from_slice(b"EOF");
+    Ok(ips)
+}
+
+/// Write changes as lines like `0100: 3e 00 -> af 00`.
+#[must_use]
+pub fn to_text(changes: &[Change]) -> String {
+    let hex = |bytes: &[u8]| {
+        let bytes: Vec<String> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        bytes.join(" ")
+    };
+    let mut text = String::new();
+    for change in changes {
+        // Writing to a string never fails.
+        let _ = writeln!(
+            text,
+            "{:04x}: {} -> {}",
+            change.offset,
+            hex(&change.original),
+            hex(&change.patched)
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Variable length number of a BPS patch.
+    fn number(mut value: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let low = u8::try_from(value & 0x7F).unwrap();
+            value >>= 7;
+            if value == 0 {
+                bytes.push(0x80 | low);
+                return bytes;
+            }
+            bytes.push(low);
+            value -= 1;
+        }
+    }
+
+    /// BPS patch of `commands` from `source` to `target`, with the checksums of both.
+    fn bps(source: &[u8], target: &[u8], commands: &[u8]) -> Vec<u8> {
+        let mut patch = b"BPS1".to_vec();
+        patch.extend(number(source.len()));
+        patch.extend(number(target.len()));
+        patch.extend(number(0));
+        patch.extend_from_slice(commands);
+        patch.extend(crc32fast::hash(source).to_le_bytes());
+        patch.extend(crc32fast::hash(target).to_le_bytes());
+        patch.extend(crc32fast::hash(&patch).to_le_bytes());
+        patch
+    }
+
+    /// Command of a BPS patch, with the action 0 to 3 that reads from the source, reads from
+    /// the patch, copies from the source or copies from the target.
+    fn command(action: usize, length: usize) -> Vec<u8> {
+        number((length - 1) << 2 | action)
+    }
+
+    #[test]
+    fn ips_records_replace_and_append_bytes() {
+        let patch = b"PATCH\x00\x00\x01\x00\x02\xAA\xBB\x00\x00\x05\x00\x01\xCCEOF";
+        assert_eq!(
+            apply(&[0, 1, 2, 3], patch).unwrap(),
+            [0, 0xAA, 0xBB, 3, 0, 0xCC]
+        );
+    }
+
+    #[test]
+    fn ips_run_records_repeat_a_byte() {
+        // Three 0xEE from offset 1, given by a length of 0.
+        let patch = b"PATCH\x00\x00\x01\x00\x00\x00\x03\xEEEOF";
+        assert_eq!(apply(&[0; 5], patch).unwrap(), [0, 0xEE, 0xEE, 0xEE, 0]);
+    }
+
+    #[test]
+    fn ips_trailer_truncates_the_rom() {
+        let patch = b"PATCH\x00\x00\x00\x00\x01\xFFEOF\x00\x00\x02";
+        assert_eq!(apply(&[1, 2, 3, 4], patch).unwrap(), [0xFF, 2]);
+    }
+
+    #[test]
+    fn ips_cut_off_fails() {
+        for patch in [
+            &b"PATCH\x00\x00\x01\x00\x02\xAA"[..],
+            b"PATCH",
+            b"PATCH\x00\x00",
+        ] {
+            assert!(matches!(
+                apply(&[0; 4], patch),
+                Err(DisasmError::Patch(message)) if message == "IPS patch cut off"
+            ));
+        }
+        assert!(apply(&[0; 4], b"IPS?").is_err());
+    }
+
+    #[test]
+    fn ips_written_from_changes_applies_back() {
+        let original = vec![0x11; 0x50_0000];
+        let mut patched = original.clone();
+        patched[2] = 0x22;
+        patched[IPS_EOF] = 0x33;
+        let ips = to_ips(&changes(&original, &patched), &original).unwrap();
+        assert_eq!(apply(&original, &ips).unwrap(), patched);
+    }
+
+    #[test]
+    fn bps_numbers_are_read_back() {
+        for value in [0, 1, 0x7F, 0x80, 0x4000, 0x20_4080, usize::from(u16::MAX)] {
+            let encoded = number(value);
+            let mut reader = BpsReader {
+                patch: &encoded,
+                position: 0,
+            };
+            assert_eq!(reader.number(), Ok(value));
+            assert_eq!(reader.position, encoded.len());
+        }
+        // 0x00 carries on to the next byte, which adds 0x80 for it.
+        let mut reader = BpsReader {
+            patch: &[0x00, 0x80],
+            position: 0,
+        };
+        assert_eq!(reader.number(), Ok(0x80));
+        let mut reader = BpsReader {
+            patch: &[0x00],
+            position: 0,
+        };
+        assert!(reader.number().is_err());
+        let mut reader = BpsReader {
+            patch: &[0x7F; 16],
+            position: 0,
+        };
+        assert_eq!(reader.number(), Err(TOO_LARGE.to_owned()));
+    }
+
+    #[test]
+    fn bps_commands_read_and_copy_from_the_source_and_target() {
+        let source = [1, 2, 3, 4, 5, 6];
+        let target = [1, 2, 9, 5, 6, 6, 6, 6, 3];
+        let mut commands = Vec::new();
+        // 1, 2 from the source at the same offset.
+        commands.extend(command(0, 2));
+        // 9 from the patch.
+        commands.extend(command(1, 1));
+        commands.push(9);
+        // 5, 6 from offset 4 of the source, 4 ahead.
+        commands.extend(command(2, 2));
+        commands.extend(number(4 << 1));
+        // 6, 6, 6 from offset 4 of the target, overlapping the bytes being written.
+        commands.extend(command(3, 3));
+        commands.extend(number(4 << 1));
+        // 3 from offset 2 of the source, 4 back from where the last copy ended.
+        commands.extend(command(2, 1));
+        commands.extend(number(4 << 1 | 1));
+        let patch = bps(&source, &target, &commands);
+        assert_eq!(apply(&source, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn bps_checksums_are_checked() {
+        let source = [1, 2, 3];
+        let target = [1, 2, 3, 4];
+        let mut commands = command(0, 3);
+        commands.extend(command(1, 1));
+        commands.push(4);
+        let patch = bps(&source, &target, &commands);
+        assert_eq!(apply(&source, &patch).unwrap(), target);
+
+        let failure = |rom: &[u8], patch: &[u8]| match apply(rom, patch) {
+            Err(DisasmError::Patch(message)) => message,
+            result => panic!("{result:?}"),
+        };
+        assert_eq!(
+            failure(&[1, 2, 4], &patch),
+            "BPS patch made for another rom, its checksum does not match"
+        );
+        // The byte written by the last command, before the three checksums.
+        let mut damaged = patch;
+        let last = damaged.len() - 13;
+        damaged[last] = 5;
+        assert_eq!(
+            failure(&source, &damaged),
+            "BPS patch damaged, its checksum does not match"
+        );
+        let wrong = bps(&source, &[1, 2, 3, 5], &commands);
+        assert_eq!(
+            failure(&source, &wrong),
+            "BPS patch gives a rom that does not match its checksum"
+        );
+        assert_eq!(failure(&source, b"BPS1"), "BPS patch cut off");
+    }
+
+    #[test]
+    fn bps_patches_cut_off_or_too_large_fail() {
+        let failure = |rom: &[u8], patch: &[u8]| match apply(rom, patch) {
+            Err(DisasmError::Patch(message)) => message,
+            result => panic!("{result:?}"),
+        };
+        let source = [1, 2, 3];
+
+        // Three bytes of the patch announced, one given.
+        let mut commands = command(1, 3);
+        commands.push(4);
+        let patch = bps(&source, &[4, 5, 6], &commands);
+        assert_eq!(failure(&source, &patch), "BPS patch cut off");
+
+        // Metadata running past the commands.
+        let mut patch = b"BPS1".to_vec();
+        patch.extend(number(source.len()));
+        patch.extend(number(1));
+        patch.extend(number(usize::MAX >> 8));
+        patch.extend(crc32fast::hash(&source).to_le_bytes());
+        patch.extend(crc32fast::hash(&[1]).to_le_bytes());
+        patch.extend(crc32fast::hash(&patch).to_le_bytes());
+        assert_eq!(failure(&source, &patch), "BPS patch cut off");
+
+        // A rom of 2^60 bytes.
+        let mut patch = b"BPS1".to_vec();
+        patch.extend(number(source.len()));
+        patch.extend(number(1 << 60));
+        patch.extend(number(0));
+        patch.extend(crc32fast::hash(&source).to_le_bytes());
+        patch.extend(crc32fast::hash(&[]).to_le_bytes());
+        patch.extend(crc32fast::hash(&patch).to_le_bytes());
+        assert!(failure(&source, &patch).starts_with("BPS patch makes a rom of"));
+
+        // Commands longer than the rom they make, or reading past the end of memory.
+        let target = [1, 2, 3];
+        for commands in [command(0, 4), number(usize::MAX - 3), {
+            let mut commands = command(2, 1);
+            commands.extend(number(usize::MAX - 1));
+            commands
+        }] {
+            let patch = bps(&source, &target, &commands);
+            assert!(apply(&source, &patch).is_err());
+        }
+    }
+}
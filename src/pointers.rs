@@ -0,0 +1,166 @@
+//! Register pairs used as pointers or counters, and the memory walked through them.
+
+use std::collections::BTreeMap;
+
+use crate::analysis::{LabelKind, Line, Listing};
+use crate::opcodes::{self, Immediate};
+
+/// Register pairs, by the name of their high register as written in operands, with their
+/// low register and their name.
+const PAIRS: [(&str, &str, &str); 3] = [("B", "C", "BC"), ("D", "E", "DE"), ("H", "L", "HL")];
+
+/// Contents of a register pair loaded by LXI.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Loaded {
+    /// Value given by the LXI.
+    base: u16,
+    /// Address of the LXI.
+    at: u16,
+    /// Name of the pair loaded by the LXI, which XCHG may have moved since.
+    pair_name: &'static str,
+    /// Added to the base by INX and DCX since, unknown after a label, as in a loop walking
+    /// the memory.
+    offset: Option<u16>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Use {
+    Pointer,
+    Counter,
+}
+
+/// Comment the memory accesses through register pairs loaded by LXI with what they reach.
+///
+/// Accesses are commented like `HL -> table+3`, or `HL walks table` in loops, and each LXI
+/// with whether the pair is used as a pointer or as a counter. Pairs are followed through INX, DCX and XCHG in the straight-line code after the LXI.
+/// A label keeps what the pairs point into but not where, and calls and writes to either
+/// half of a pair forget it. A pair is a counter when it is decremented and tested with
+/// `MOV A` and `ORA` of its halves.
+pub fn annotate(listing: &mut Listing) {
+    let mut comments = Vec::new();
+    let mut uses = BTreeMap::new();
+    let mut pairs: [Option<Loaded>; 3] = [None; 3];
+    for (index, line) in listing.lines.iter().enumerate() {
+        let Line::Instruction { address, bytes } = line else {
+            pairs = [None; 3];
+            continue;
+        };
+        match listing.labels.get(address).map(|label| label.kind) {
+            Some(LabelKind::Function) => pairs = [None; 3],
+            Some(_) => {
+                for pair in pairs.iter_mut().flatten() {
+                    pair.offset = None;
+                }
+            }
+            None => {}
+        }
+
+        let opcode = opcodes::lookup(bytes[0]);
+        let registers: Vec<&str> = opcode.registers.split(',').collect();
+        let pair = PAIRS
+            .iter()
+            .position(|(high, _, _)| registers.first() == Some(high));
+        let through = match (opcode.mnemonic, pair) {
+            ("LDAX" | "STAX", Some(pair)) => Some(pair),
+            _ if registers.contains(&"M") => Some(2),
+            _ => None,
+        };
+        if let Some((pair, loaded)) = through.and_then(|pair| pairs[pair].map(|l| (pair, l))) {
+            let name = listing
+                .name(loaded.base)
+                .map_or_else(|| format!("0x{:04x}", loaded.base), str::to_owned);
+            let comment = match loaded.offset {
+                Some(0) => format!("{} -> {name}", PAIRS[pair].2),
+                Some(offset) => format!("{} -> {name}+{offset}", PAIRS[pair].2),
+                None => format!("{} walks {name}", PAIRS[pair].2),
+            };
+            comments.push((*address, comment));
+            uses.entry(loaded.at)
+                .or_insert((Use::Pointer, loaded.pair_name));
+        }
+
+        if let (Some(pair), "DCX") = (pair, opcode.mnemonic) {
+            let counter = pairs[pair].filter(|_| is_tested(listing, index + 1, PAIRS[pair]));
+            if let Some(loaded) = counter {
+                uses.insert(loaded.at, (Use::Counter, loaded.pair_name));
+            }
+        }
+        update(&mut pairs, *address, bytes);
+    }
+
+    for (address, (kind, name)) in uses {
+        let comment = match kind {
+            Use::Pointer => format!("{name} pointer"),
+            Use::Counter => format!("{name} counter"),
+        };
+        comments.push((address, comment));
+    }
+    comments.sort_by_key(|(address, _)| *address);
+    for (address, comment) in comments {
+        listing.add_comment(address, comment);
+    }
+}
+
+/// Follow the register pairs through an instruction.
+fn update(pairs: &mut [Option<Loaded>; 3], address: u16, bytes: &[u8]) {
+    let opcode = opcodes::lookup(bytes[0]);
+    let registers: Vec<&str> = opcode.registers.split(',').collect();
+    let pair = PAIRS
+        .iter()
+        .position(|(high, _, _)| registers.first() == Some(high));
+    match (opcode.mnemonic, pair) {
+        ("LXI", Some(pair)) => {
+            pairs[pair] = Some(Loaded {
+                base: u16::from_le_bytes([bytes[1], bytes[2]]),
+                at: address,
+                pair_name: PAIRS[pair].2,
+                offset: Some(0),
+            });
+        }
+        ("INX" | "DCX", Some(pair)) => {
+            if let Some(loaded) = &mut pairs[pair] {
+                let step = if opcode.mnemonic == "INX" {
+                    1
+                } else {
+                    u16::MAX
+                };
+                loaded.offset = loaded.offset.map(|o| o.wrapping_add(step));
+            }
+        }
+        ("XCHG", _) => pairs.swap(1, 2),
+        ("MOV" | "MVI" | "INR" | "DCR" | "POP" | "DAD" | "LHLD" | "XTHL", _) => {
+            // The register written, or H for those writing HL.
+            let written = match opcode.mnemonic {
+                "DAD" | "LHLD" | "XTHL" => "H",
+                _ => registers[0],
+            };
+            for (pair, (high, low, _)) in PAIRS.iter().enumerate() {
+                if written == *high || written == *low {
+                    pairs[pair] = None;
+                }
+            }
+        }
+        ("JMP" | "RET" | "PCHL" | "RST", _) => *pairs = [None; 3],
+        // CALL and the conditional calls.
+        (mnemonic, _) if mnemonic.starts_with('C') && opcode.immediate == Immediate::Address => {
+            *pairs = [None; 3];
+        }
+        _ => {}
+    }
+}
+
+/// Whether the lines from `index` on test a pair for zero, with `MOV A` of one half and
+/// `ORA` of the other.
+fn is_tested(listing: &Listing, index: usize, (high, low, _): (&str, &str, &str)) -> bool {
+    let instruction = |index: usize| match listing.lines.get(index) {
+        Some(Line::Instruction { bytes, .. }) => Some(opcodes::lookup(bytes[0])),
+        _ => None,
+    };
+    let (Some(first), Some(second)) = (instruction(index), instruction(index + 1)) else {
+        return false;
+    };
+    let moved = first.mnemonic == "MOV"
+        && (first.registers == format!("A,{high}") || first.registers == format!("A,{low}"));
+    let ored = second.mnemonic == "ORA" && (second.registers == high || second.registers == low);
+    moved && ored && first.registers[2..] != *second.registers
+}
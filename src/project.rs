@@ -0,0 +1,339 @@
+//! Project files recording what the user knows about a rom, such as the names of labels
+//! and the layout of its tables.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::analysis::{Label, LabelKind, Line, Listing};
+use crate::asm::Dialect;
+use crate::encode::parse_number;
+use crate::instruction::{Register, RegisterPair};
+use crate::opcodes;
+
+/// Knowledge about a rom, read from project files.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Project {
+    /// Names given to addresses.
+    pub labels: BTreeMap<u16, String>,
+    /// Layouts of records by name.
+    pub records: BTreeMap<String, Vec<Field>>,
+    /// Tables of records laid over the data.
+    pub overlays: Vec<Overlay>,
+    /// Code loaded over the memory at times, in the order they were declared.
+    pub segments: Vec<Segment>,
+}
+
+/// Field of a record.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Field {
+    pub name: String,
+    pub kind: FieldKind,
+}
+
+/// Type of a field, giving its size.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FieldKind {
+    Byte,
+    Word,
+    /// Address of something else in memory.
+    Pointer,
+    /// Any number of bytes.
+    Bytes(usize),
+}
+
+impl FieldKind {
+    #[must_use]
+    pub const fn size(self) -> usize {
+        match self {
+            Self::Byte => 1,
+            Self::Word | Self::Pointer => 2,
+            Self::Bytes(size) => size,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "byte" => Some(Self::Byte),
+            "word" => Some(Self::Word),
+            "ptr" => Some(Self::Pointer),
+            size => size.parse().ok().map(Self::Bytes),
+        }
+    }
+}
+
+/// Consecutive records starting at an address.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Overlay {
+    pub address: u16,
+    /// Name of the record.
+    pub record: String,
+    pub count: usize,
+}
+
+/// Code loaded from another file over part of the memory at times, such as a CP/M overlay
+/// sharing its address with others.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Segment {
+    pub name: String,
+    pub address: u16,
+    /// Path of the file, relative to the project file.
+    pub path: String,
+}
+
+impl Project {
+    /// Add the directives of a project file, replacing earlier ones for the same address.
+    ///
+    /// Each line holds a directive:
+    ///
+    /// - `label ADDRESS NAME` names an address.
+    /// - `record NAME FIELD:TYPE...` defines the layout of a record, with fields of type
+    ///   `byte`, `word`, `ptr` or a number of bytes.
+    /// - `overlay ADDRESS RECORD [COUNT]` lays COUNT records, 1 by default, over the data
+    ///   at an address.
+    /// - `segment NAME ADDRESS FILE` declares code loaded from a file at an address at
+    ///   times, replacing an earlier segment of the same name.
+    ///
+    /// Empty lines and lines starting with `#` are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Fails on lines that are not a valid directive; the directives before them are kept.
+    pub fn extend(&mut self, text: &str) -> anyhow::Result<()> {
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.parse_directive(line)
+                .with_context(|| format!("parsing line {}", line_index + 1))?;
+        }
+        Ok(())
+    }
+
+    fn parse_directive(&mut self, line: &str) -> anyhow::Result<()> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            ["label", address, name] => {
+                let address = parse_number(address)
+                    .with_context(|| format!("parsing address \"{address}\""))?;
+                self.labels.insert(address, (*name).to_owned());
+            }
+            ["label", ..] => bail!("expected \"label ADDRESS NAME\""),
+            ["record", name, fields @ ..] if !fields.is_empty() => {
+                let fields = fields
+                    .iter()
+                    .map(|field| {
+                        let (name, kind) = field
+                            .split_once(':')
+                            .ok_or_else(|| anyhow!("expected \"FIELD:TYPE\", found \"{field}\""))?;
+                        let kind = FieldKind::from_name(kind).ok_or_else(|| {
+                            anyhow!("expected byte, word, ptr or a size, found \"{kind}\"")
+                        })?;
+                        Ok(Field {
+                            name: name.to_owned(),
+                            kind,
+                        })
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                self.records.insert((*name).to_owned(), fields);
+            }
+            ["record", ..] => bail!("expected \"record NAME FIELD:TYPE...\""),
+            ["overlay", address, record, count @ ..] if count.len() <= 1 => {
+                let address = parse_number(address)
+                    .with_context(|| format!("parsing address \"{address}\""))?;
+                if !self.records.contains_key(*record) {
+                    bail!("unknown record \"{record}\"");
+                }
+                let count = match count {
+                    [count] => count
+                        .parse()
+                        .with_context(|| format!("parsing count \"{count}\""))?,
+                    _ => 1,
+                };
+                self.overlays.push(Overlay {
+                    address,
+                    record: (*record).to_owned(),
+                    count,
+                });
+            }
+            ["overlay", ..] => bail!("expected \"overlay ADDRESS RECORD [COUNT]\""),
+            ["segment", name, address, path] => {
+                let address = parse_number(address)
+                    .with_context(|| format!("parsing address \"{address}\""))?;
+                let segment = Segment {
+                    name: (*name).to_owned(),
+                    address,
+                    path: (*path).to_owned(),
+                };
+                match self.segments.iter_mut().find(|s| s.name == segment.name) {
+                    Some(existing) => *existing = segment,
+                    None => self.segments.push(segment),
+                }
+            }
+            ["segment", ..] => bail!("expected \"segment NAME ADDRESS FILE\""),
+            [directive, ..] => return Err(anyhow!("unknown directive \"{directive}\"")),
+            [] => {}
+        }
+        Ok(())
+    }
+
+    /// Give the names of the project to the labels of a listing, and lay its records over
+    /// the data.
+    ///
+    /// Named addresses without a label get one when a line of the listing starts there, and
+    /// are otherwise known as symbols, such as the addresses of RAM and devices.
+    /// Each field of a record is commented like `enemy[2].x`; records that would cover
+    /// code are left out.
+    pub fn apply(&self, listing: &mut Listing) {
+        self.apply_labels(listing);
+        for overlay in &self.overlays {
+            let fields = &self.records[&overlay.record];
+            let size: usize = fields.iter().map(|f| f.kind.size()).sum();
+            for index in 0..overlay.count {
+                let Ok(address) = u16::try_from(usize::from(overlay.address) + index * size) else {
+                    break;
+                };
+                let names: Vec<(String, usize)> = fields
+                    .iter()
+                    .map(|f| {
+                        (
+                            format!("{}[{index}].{}", overlay.record, f.name),
+                            f.kind.size(),
+                        )
+                    })
+                    .collect();
+                let names: Vec<(&str, usize)> =
+                    names.iter().map(|(n, size)| (n.as_str(), *size)).collect();
+                listing.overlay(address, &names);
+            }
+        }
+    }
+
+    fn apply_labels(&self, listing: &mut Listing) {
+        for (&address, name) in &self.labels {
+            if let Some(label) = listing.labels.get_mut(&address) {
+                label.name.clone_from(name);
+                continue;
+            }
+            let Ok(index) = listing.lines.binary_search_by_key(&address, Line::address) else {
+                listing.symbols.insert(address, name.clone());
+                continue;
+            };
+            let kind = match listing.lines[index] {
+                Line::Instruction { .. } => LabelKind::Jump,
+                Line::Data { .. } | Line::Padding { .. } => LabelKind::Data,
+            };
+            listing.labels.insert(
+                address,
+                Label {
+                    name: name.clone(),
+                    kind,
+                },
+            );
+        }
+    }
+}
+
+/// Check that `name` can be given to the label or symbol at `address` of a listing.
+///
+/// The name must be made of letters, digits and underscores, not start with a digit, not be
+/// read by the assemblers of `dialect` as a mnemonic, register or directive, whatever its
+/// case, and not be given to another address already.
+///
+/// # Errors
+///
+/// Fails with the reason the name cannot be given.
+pub fn check_name(
+    name: &str,
+    address: u16,
+    listing: &Listing,
+    dialect: &Dialect,
+) -> anyhow::Result<()> {
+    let is_valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_valid {
+        bail!("\"{name}\" is not a valid name");
+    }
+    let upper = name.to_ascii_uppercase();
+    if opcodes::summary(&upper).is_some() {
+        bail!("\"{name}\" is a mnemonic");
+    }
+    if Register::from_name(&upper).is_some() || RegisterPair::from_name(&upper).is_some() {
+        bail!("\"{name}\" is a register");
+    }
+    let directives = [
+        dialect.origin_directive,
+        dialect.equate_directive,
+        dialect.byte_directive,
+        dialect.macro_directive,
+        dialect.end_macro_directive,
+    ];
+    let is_directive = |directive: &&str| directive.eq_ignore_ascii_case(name);
+    if directives.iter().chain(dialect.footer).any(is_directive) {
+        bail!("\"{name}\" is a directive of {}", dialect.name);
+    }
+    let names = listing
+        .labels
+        .iter()
+        .map(|(address, label)| (address, &label.name))
+        .chain(&listing.symbols);
+    for (&other, other_name) in names {
+        if other != address && other_name == name {
+            bail!("\"{name}\" already names 0x{other:04x}");
+        }
+    }
+    Ok(())
+}
+
+/// Rewrite the text of a project file to name `address` `new` instead of `old`.
+///
+/// The label directive of the address is rewritten, or one is added when the name was
+/// found by the analysis, and every mention of `old` as a word in the comments of the file
+/// is replaced.
+#[must_use]
+pub fn rename(text: &str, address: u16, old: &str, new: &str) -> String {
+    let mut renamed = String::new();
+    let mut labeled = false;
+    for line in text.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            ["label", label_address, _] if parse_number(label_address).ok() == Some(address) => {
+                // Writing to a string never fails.
+                let _ = write!(renamed, "label {label_address} {new}");
+                labeled = true;
+            }
+            _ if line.trim_start().starts_with('#') => {
+                renamed.push_str(&replace_word(line, old, new));
+            }
+            _ => renamed.push_str(line),
+        }
+        renamed.push('\n');
+    }
+    if !labeled {
+        let _ = writeln!(renamed, "label 0x{address:04x} {new}");
+    }
+    renamed
+}
+
+/// Replace the occurrences of `old` in `text` that are whole words, not parts of a longer
+/// name.
+fn replace_word(text: &str, old: &str, new: &str) -> String {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut replaced = String::new();
+    let mut rest = text;
+    while let Some(index) = rest.find(old) {
+        let before = rest[..index].chars().next_back();
+        let after = rest[index + old.len()..].chars().next();
+        replaced.push_str(&rest[..index]);
+        if before.is_some_and(is_name_char) || after.is_some_and(is_name_char) {
+            replaced.push_str(old);
+        } else {
+            replaced.push_str(new);
+        }
+        rest = &rest[index + old.len()..];
+    }
+    replaced.push_str(rest);
+    replaced
+}
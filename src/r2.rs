@@ -0,0 +1,141 @@
+//! Line based JSON protocol letting radare2 scripts use this disassembler as a backend.
+//!
+//! Each request is a line with an address and the bytes found there in hexadecimal, like
+//! `0100 cd0500`. The answer is a line with a JSON object describing the instruction at that
+//! address, with the field names radare2 uses in the output of `aoj`.
+
+use std::fmt::Write;
+use std::io::BufRead;
+
+use intel_8080_disassembler::opcodes::{self, Immediate, Opcode};
+
+/// Answer the requests read from stdin until it is closed.
+pub fn serve() -> std::io::Result<()> {
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        println!("{}", answer(&line));
+    }
+    Ok(())
+}
+
+/// JSON answer to a single request.
+fn answer(request: &str) -> String {
+    let mut words = request.split_whitespace();
+    let address = words.next().and_then(|a| {
+        let a = a.strip_prefix("0x").unwrap_or(a);
+        u16::from_str_radix(a, 16).ok()
+    });
+    let bytes: Option<Vec<u8>> = words.next().and_then(|hex| {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|b| u8::from_str_radix(b, 16).ok())
+            })
+            .collect()
+    });
+    let (Some(address), Some(bytes)) = (address, bytes) else {
+        return error("expected an address and bytes in hexadecimal");
+    };
+    let Some(&first_byte) = bytes.first() else {
+        return error("expected an address and bytes in hexadecimal");
+    };
+
+    let opcode = opcodes::lookup(first_byte);
+    let Some(bytes) = bytes.get(..opcode.length()) else {
+        return error(&format!(
+            "{} needs {} bytes",
+            opcode.mnemonic,
+            opcode.length()
+        ));
+    };
+    let immediate = match *bytes {
+        [_, low, high] => Some(u16::from_le_bytes([low, high])),
+        [_, value] => Some(u16::from(value)),
+        _ => None,
+    };
+    let hex: String = bytes.iter().fold(String::new(), |mut hex, byte| {
+        // Writing to a string never fails.
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    });
+
+    let (kind, jump) = classify(&opcode, immediate);
+    let next = usize::from(address) + bytes.len();
+    let mut json = format!(
+        "{{\"addr\":{address},\"size\":{},\"bytes\":\"{hex}\",\"disasm\":\"{}\",\
+         \"mnemonic\":\"{}\",\"type\":\"{kind}\",\"cycles\":{}",
+        bytes.len(),
+        disasm(&opcode, immediate),
+        opcode.mnemonic.to_ascii_lowercase(),
+        opcode.cycles.taken
+    );
+    if let Some(jump) = jump {
+        let _ = write!(json, ",\"jump\":{jump}");
+        if kind.starts_with('c') {
+            let _ = write!(json, ",\"fail\":{next}");
+        }
+    }
+    json.push('}');
+    json
+}
+
+fn error(message: &str) -> String {
+    // Messages never contain quotes or backslashes.
+    format!("{{\"error\":\"{message}\"}}")
+}
+
+/// Assembly in the lowercase style of radare2, like "lxi sp, 0x2400".
+fn disasm(opcode: &Opcode, immediate: Option<u16>) -> String {
+    let mut operands: Vec<String> = opcode
+        .registers
+        .split(',')
+        .filter(|r| !r.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect();
+    match (opcode.immediate, immediate) {
+        (Immediate::Word | Immediate::Address, Some(value)) => {
+            operands.push(format!("0x{value:04x}"));
+        }
+        (_, Some(value)) => operands.push(format!("0x{value:02x}")),
+        _ => {}
+    }
+
+    let mnemonic = opcode.mnemonic.to_ascii_lowercase();
+    if operands.is_empty() {
+        mnemonic
+    } else {
+        format!("{mnemonic} {}", operands.join(", "))
+    }
+}
+
+/// Type of the instruction for radare2, with the address it transfers control to.
+fn classify(opcode: &Opcode, immediate: Option<u16>) -> (&'static str, Option<u16>) {
+    let conditional = opcode.cycles.taken != opcode.cycles.not_taken
+        || matches!(
+            opcode.mnemonic,
+            "JNZ" | "JZ" | "JNC" | "JC" | "JPO" | "JPE" | "JP" | "JM"
+        );
+    match opcode.mnemonic {
+        "JMP" => ("jmp", immediate),
+        "CALL" => ("call", immediate),
+        "RET" => ("ret", None),
+        "PCHL" => ("ujmp", None),
+        "RST" => {
+            let vector = opcode.registers.parse::<u16>().unwrap_or_default() * 8;
+            ("swi", Some(vector))
+        }
+        "NOP" => ("nop", None),
+        "HLT" => ("trap", None),
+        "PUSH" => ("push", None),
+        "POP" => ("pop", None),
+        "IN" | "OUT" => ("io", None),
+        mnemonic if conditional && mnemonic.starts_with('J') => ("cjmp", immediate),
+        mnemonic if conditional && mnemonic.starts_with('C') => ("ccall", immediate),
+        mnemonic if conditional && mnemonic.starts_with('R') => ("cret", None),
+        _ => ("other", None),
+    }
+}
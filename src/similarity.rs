@@ -0,0 +1,118 @@
+//! Functions of two roms with similar code, to port annotations between games sharing an
+//! engine.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+
+use intel_8080_disassembler::analysis::{Line, Listing};
+use intel_8080_disassembler::opcodes;
+
+/// Instructions in each sequence compared.
+const NGRAM: usize = 4;
+
+/// Score from which two functions are reported as a match.
+const MIN_SCORE: f64 = 0.5;
+
+/// Function reduced to what is compared.
+struct Function {
+    address: u16,
+    name: String,
+    /// Hashes of the sequences of NGRAM instructions found in the function.
+    ngrams: BTreeSet<u64>,
+}
+
+/// Print the pairs of similar functions of two roms, best first, with their score.
+///
+/// Functions are compared by the sequences of opcodes they contain, leaving the
+/// immediates out, since addresses move from one build to another and constants are often
+/// tuned. The score is the share of sequences found in both functions, and each function
+/// is matched at most once, best scores first. Functions shorter than a sequence are left
+/// out, as tiny helpers look alike in every rom.
+pub fn print(out: &mut dyn std::io::Write, a: &Listing, b: &Listing) -> std::io::Result<()> {
+    let a = functions(a);
+    let b = functions(b);
+    let mut scores = Vec::new();
+    for (i, first) in a.iter().enumerate() {
+        for (j, second) in b.iter().enumerate() {
+            let score = score(&first.ngrams, &second.ngrams);
+            if score >= MIN_SCORE {
+                scores.push((score, i, j));
+            }
+        }
+    }
+    scores.sort_by(|x, y| y.0.total_cmp(&x.0).then((x.1, x.2).cmp(&(y.1, y.2))));
+
+    let mut matched_a = vec![false; a.len()];
+    let mut matched_b = vec![false; b.len()];
+    let mut pairs = 0;
+    writeln!(
+        out,
+        "score  a     b     name in a                 name in b"
+    )?;
+    for (score, i, j) in scores {
+        if matched_a[i] || matched_b[j] {
+            continue;
+        }
+        matched_a[i] = true;
+        matched_b[j] = true;
+        pairs += 1;
+        writeln!(
+            out,
+            "{score:.2}   {:04x}  {:04x}  {:<25} {}",
+            a[i].address, b[j].address, a[i].name, b[j].name
+        )?;
+    }
+    writeln!(
+        out,
+        "{pairs} of {} functions matched, against {} in the second rom",
+        a.len(),
+        b.len()
+    )
+}
+
+/// Functions of a listing long enough to be compared.
+fn functions(listing: &Listing) -> Vec<Function> {
+    let mut functions = Vec::new();
+    for &address in &listing.functions {
+        let Some(range) = listing.function_range(address) else {
+            continue;
+        };
+        let opcodes: Vec<u8> = listing
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                Line::Instruction { address, bytes } if range.contains(&usize::from(*address)) => {
+                    Some(opcodes::canonical_opcode(bytes[0]))
+                }
+                _ => None,
+            })
+            .collect();
+        if opcodes.len() < NGRAM {
+            continue;
+        }
+        let ngrams = opcodes
+            .windows(NGRAM)
+            .map(|ngram| {
+                let mut hasher = DefaultHasher::new();
+                ngram.hash(&mut hasher);
+                hasher.finish()
+            })
+            .collect();
+        functions.push(Function {
+            address,
+            name: listing
+                .name(address)
+                .map_or_else(|| format!("0x{address:04x}"), str::to_owned),
+            ngrams,
+        });
+    }
+    functions
+}
+
+/// Sequences found in both functions, out of those found in either.
+#[allow(clippy::cast_precision_loss)]
+fn score(a: &BTreeSet<u64>, b: &BTreeSet<u64>) -> f64 {
+    let shared = a.intersection(b).count();
+    shared as f64 / (a.len() + b.len() - shared) as f64
+}
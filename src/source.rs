@@ -0,0 +1,284 @@
+//! Assembly of whole source files, such as those written by `--format asm` or by hand.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context};
+
+use crate::encode::{self, parse_number};
+
+/// Statement of a source file that assembles to bytes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Statement {
+    /// Number of the line in the source, from 1.
+    pub line: usize,
+    pub address: u16,
+    /// Text of the line, without its label and comment.
+    pub text: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Registers and register pairs, which are written as they are in operands.
+const REGISTERS: [&str; 10] = ["A", "B", "C", "D", "E", "H", "L", "M", "SP", "PSW"];
+
+/// Assemble a source file in Intel syntax.
+///
+/// Besides instructions, the source may hold labels, with or without a colon, and the
+/// directives `ORG`, `EQU`, `DB`, `DW`, `DS` and `END`, or their zasm spellings `DEFB`,
+/// `DEFM`, `DEFW` and `DEFS`. Directives starting with a dot, like zasm's `.8080`, are
+/// ignored. Operands are numbers as read by [`encode::encode`], symbols, `$` for the
+/// address of the statement, or sums and differences of those. Symbols are not case
+/// sensitive. The space reserved by `DS` is filled with zeros.
+///
+/// # Errors
+///
+/// Fails on the first line that does not assemble, naming it.
+pub fn assemble(source: &str) -> anyhow::Result<Vec<Statement>> {
+    let mut assembler = Assembler {
+        symbols: HashMap::new(),
+        address: 0,
+        last_pass: false,
+        ended: false,
+    };
+    // The first pass finds the address of each label, taking unknown symbols for 0.
+    assembler.pass(source)?;
+    assembler.last_pass = true;
+    assembler.pass(source)
+}
+
+struct Assembler {
+    symbols: HashMap<String, u16>,
+    address: u16,
+    last_pass: bool,
+    ended: bool,
+}
+
+impl Assembler {
+    fn pass(&mut self, source: &str) -> anyhow::Result<Vec<Statement>> {
+        self.address = 0;
+        self.ended = false;
+        let mut statements = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let statement = self
+                .line(line)
+                .with_context(|| format!("assembling line {}", index + 1))?;
+            if let Some((text, bytes)) = statement {
+                let length = u16::try_from(bytes.len()).context("statement too long")?;
+                statements.push(Statement {
+                    line: index + 1,
+                    address: self.address,
+                    text,
+                    bytes,
+                });
+                self.address = self.address.wrapping_add(length);
+            }
+            if self.ended {
+                break;
+            }
+        }
+        Ok(statements)
+    }
+
+    /// Text and bytes of a line, if it assembles to any.
+    fn line(&mut self, line: &str) -> anyhow::Result<Option<(String, Vec<u8>)>> {
+        let code = until_unquoted(line, ';').trim_end();
+        let (first, rest) = split_word(code.trim_start());
+        if first.is_empty() {
+            return Ok(None);
+        }
+        let (second, after) = split_word(rest);
+        if second.eq_ignore_ascii_case("EQU") {
+            let value = self.evaluate(after)?;
+            self.define(first, value)?;
+            return Ok(None);
+        }
+
+        let statement = if let Some(label) = first.strip_suffix(':') {
+            self.define(label, self.address)?;
+            rest
+        } else if !code.starts_with(char::is_whitespace) && !is_keyword(first) {
+            self.define(first, self.address)?;
+            rest
+        } else {
+            code.trim_start()
+        };
+        let (keyword, operands) = split_word(statement);
+        let operands = split_operands(operands);
+        let bytes = match keyword.to_ascii_uppercase().as_str() {
+            "" => return Ok(None),
+            keyword if keyword.starts_with('.') => return Ok(None),
+            "ORG" => {
+                self.address = self.evaluate_now(single(&operands)?)?;
+                return Ok(None);
+            }
+            "END" => {
+                self.ended = true;
+                return Ok(None);
+            }
+            "DB" | "DEFB" | "DEFM" => self.bytes(&operands)?,
+            "DW" | "DEFW" => {
+                let mut bytes = Vec::new();
+                for operand in &operands {
+                    bytes.extend_from_slice(&self.evaluate(operand)?.to_le_bytes());
+                }
+                bytes
+            }
+            "DS" | "DEFS" => vec![0; usize::from(self.evaluate_now(single(&operands)?)?)],
+            _ => {
+                let mut written = Vec::new();
+                for operand in &operands {
+                    if REGISTERS.iter().any(|r| r.eq_ignore_ascii_case(operand)) {
+                        written.push((*operand).to_owned());
+                    } else {
+                        written.push(format!("0x{:x}", self.evaluate(operand)?));
+                    }
+                }
+                encode::encode(&format!("{keyword} {}", written.join(",")))?
+            }
+        };
+        Ok((!bytes.is_empty()).then(|| (statement.to_owned(), bytes)))
+    }
+
+    fn define(&mut self, name: &str, value: u16) -> anyhow::Result<()> {
+        let name = name.to_ascii_uppercase();
+        // Every symbol is defined again by the last pass, with its final value.
+        if self.symbols.insert(name.clone(), value).is_some() && !self.last_pass {
+            bail!("\"{name}\" defined twice");
+        }
+        Ok(())
+    }
+
+    /// Bytes of the operands of `DB`, which may be strings.
+    fn bytes(&self, operands: &[&str]) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        for operand in operands {
+            let string = ['\'', '"'].into_iter().find_map(|quote| {
+                operand
+                    .strip_prefix(quote)
+                    .and_then(|o| o.strip_suffix(quote))
+            });
+            match string {
+                Some(string) if operand.len() >= 2 => {
+                    if !string.is_ascii() {
+                        bail!("expected ASCII characters in {operand}");
+                    }
+                    bytes.extend_from_slice(string.as_bytes());
+                }
+                _ => {
+                    let value = self.evaluate(operand)?;
+                    let Ok(byte) = u8::try_from(value) else {
+                        bail!("{operand} does not fit in a byte");
+                    };
+                    bytes.push(byte);
+                }
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn evaluate(&self, expression: &str) -> anyhow::Result<u16> {
+        self.value(expression, self.last_pass)
+    }
+
+    /// Value of an expression that the addresses of the following lines depend on, which
+    /// cannot refer to symbols defined later.
+    fn evaluate_now(&self, expression: &str) -> anyhow::Result<u16> {
+        self.value(expression, true)
+    }
+
+    fn value(&self, expression: &str, strict: bool) -> anyhow::Result<u16> {
+        let mut value: u16 = 0;
+        let mut rest = expression.trim();
+        let mut negative = false;
+        if let Some(negated) = rest.strip_prefix('-') {
+            negative = true;
+            rest = negated;
+        }
+        loop {
+            let end = operator_position(rest).unwrap_or(rest.len());
+            let term = self.term(rest[..end].trim(), strict)?;
+            value = if negative {
+                value.wrapping_sub(term)
+            } else {
+                value.wrapping_add(term)
+            };
+            let Some(operator) = rest[end..].chars().next() else {
+                return Ok(value);
+            };
+            negative = operator == '-';
+            rest = &rest[end + 1..];
+        }
+    }
+
+    fn term(&self, term: &str, strict: bool) -> anyhow::Result<u16> {
+        if term == "$" {
+            return Ok(self.address);
+        }
+        if term.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '$' | '\'' | '#')) {
+            return parse_number(term).with_context(|| format!("parsing number \"{term}\""));
+        }
+        if term.is_empty() {
+            bail!("expected a value");
+        }
+        match self.symbols.get(&term.to_ascii_uppercase()) {
+            Some(&value) => Ok(value),
+            None if !strict => Ok(0),
+            None => bail!("undefined symbol \"{term}\""),
+        }
+    }
+}
+
+fn is_keyword(word: &str) -> bool {
+    let word = word.to_ascii_uppercase();
+    matches!(
+        word.as_str(),
+        "ORG" | "END" | "DB" | "DEFB" | "DEFM" | "DW" | "DEFW" | "DS" | "DEFS"
+    ) || word.starts_with('.')
+        || crate::opcodes::summary(&word).is_some()
+}
+
+fn single<'a>(operands: &[&'a str]) -> anyhow::Result<&'a str> {
+    match *operands {
+        [operand] => Ok(operand),
+        _ => bail!("expected a single operand"),
+    }
+}
+
+/// First word of a text, and the rest of it.
+fn split_word(text: &str) -> (&str, &str) {
+    text.split_once(char::is_whitespace)
+        .map_or((text, ""), |(word, rest)| (word, rest.trim_start()))
+}
+
+/// Operands separated by commas outside of quotes.
+fn split_operands(text: &str) -> Vec<&str> {
+    let mut operands = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let operand = until_unquoted(rest, ',');
+        operands.push(operand.trim());
+        rest = rest.get(operand.len() + 1..).unwrap_or("").trim_start();
+    }
+    operands
+}
+
+/// Text up to the first occurrence of a character outside of quotes.
+fn until_unquoted(text: &str, end: char) -> &str {
+    &text[..unquoted_position(text, |c| c == end).unwrap_or(text.len())]
+}
+
+fn operator_position(expression: &str) -> Option<usize> {
+    unquoted_position(expression, |c| matches!(c, '+' | '-'))
+}
+
+fn unquoted_position(text: &str, is_wanted: impl Fn(char) -> bool) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if is_wanted(c) => return Some(index),
+            Some(_) | None => {}
+        }
+    }
+    None
+}
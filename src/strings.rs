@@ -0,0 +1,111 @@
+//! Detection of text in a rom, in the encodings common on 8080 systems.
+
+use crate::charset::Charset;
+
+/// How the end of a string is marked.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StringKind {
+    /// Run of printable characters, possibly followed by a NUL.
+    Ascii,
+    /// Terminated by `$`, as printed by the CP/M BDOS function 9.
+    Dollar,
+    /// Preceded by its length in a byte.
+    LengthPrefixed,
+    /// Last character marked by setting its high bit.
+    HighBit,
+}
+
+impl StringKind {
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Ascii => "ascii",
+            Self::Dollar => "dollar",
+            Self::LengthPrefixed => "length",
+            Self::HighBit => "highbit",
+        }
+    }
+}
+
+/// String found in a rom.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Found {
+    /// Offset of the first byte, including the length prefix.
+    pub address: usize,
+    /// Bytes taken, including the length prefix and the terminator.
+    pub length: usize,
+    /// Characters, without the markers of the end.
+    pub text: String,
+    pub kind: StringKind,
+}
+
+/// Shortest string reported when no other minimum is given.
+pub const MIN_LENGTH: usize = 4;
+
+/// Find the strings of at least `min_length` ASCII characters in a rom.
+#[must_use]
+pub fn find(rom: &[u8], min_length: usize) -> Vec<Found> {
+    find_in(rom, min_length, &Charset::ascii())
+}
+
+/// Find the strings of at least `min_length` characters of a character set in a rom.
+#[must_use]
+pub fn find_in(rom: &[u8], min_length: usize, charset: &Charset) -> Vec<Found> {
+    let is_printable = |byte| charset.get(byte).is_some();
+    let min_length = min_length.max(1);
+    let mut found = Vec::new();
+    let mut start = 0;
+    while start < rom.len() {
+        let run = rom[start..]
+            .iter()
+            .position(|&b| !is_printable(b))
+            .unwrap_or(rom.len() - start);
+        if run == 0 {
+            start += 1;
+            continue;
+        }
+
+        let text = &rom[start..start + run];
+        let next = rom.get(start + run).copied();
+        let prefix = start.checked_sub(1).map(|p| (p, usize::from(rom[p])));
+
+        let dollar = text.iter().position(|&b| charset.get(b) == Some('$'));
+        let prefix = prefix.filter(|&(_, length)| length >= min_length && length <= run);
+        let high_bit = next.is_some_and(|b| b >= 0x80 && is_printable(b & 0x7F));
+        let (address, characters, length, kind) = match (dollar, prefix) {
+            // Text after the "$" is looked at again as another string.
+            (Some(dollar), _) => (start, dollar, dollar + 1, StringKind::Dollar),
+            (None, Some((address, characters))) => (
+                address,
+                characters,
+                characters + 1,
+                StringKind::LengthPrefixed,
+            ),
+            (None, None) if high_bit => (start, run + 1, run + 1, StringKind::HighBit),
+            (None, None) => {
+                let terminator = usize::from(next == Some(0));
+                (start, run, run + terminator, StringKind::Ascii)
+            }
+        };
+
+        if characters >= min_length {
+            let first = if kind == StringKind::LengthPrefixed {
+                address + 1
+            } else {
+                address
+            };
+            let text = rom[first..first + characters]
+                .iter()
+                .filter_map(|&b| charset.get(b).or_else(|| charset.get(b & 0x7F)))
+                .collect();
+            found.push(Found {
+                address,
+                length,
+                text,
+                kind,
+            });
+        }
+        start = address + length.max(1);
+    }
+    found
+}
@@ -0,0 +1,67 @@
+//! Listing of the labels found by the analysis, in the manner of `nm`.
+
+use intel_8080_disassembler::analysis::{LabelKind, Listing};
+
+/// Order of the symbols.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Sort {
+    #[default]
+    Address,
+    Name,
+}
+
+impl Sort {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "address" => Some(Self::Address),
+            "name" => Some(Self::Name),
+            _ => None,
+        }
+    }
+}
+
+/// Print the address, size, kind and name of each label.
+///
+/// The size of a symbol runs up to the next one, or to the end of the image.
+pub fn print(
+    out: &mut dyn std::io::Write,
+    listing: &Listing,
+    image_length: usize,
+    sort: Sort,
+) -> std::io::Result<()> {
+    let addresses: Vec<usize> = listing.labels.keys().map(|&a| usize::from(a)).collect();
+    let mut symbols: Vec<(usize, usize, &str, &str)> = listing
+        .labels
+        .iter()
+        .enumerate()
+        .map(|(index, (&address, label))| {
+            let address = usize::from(address);
+            let end = addresses.get(index + 1).copied().unwrap_or(image_length);
+            let kind = match label.kind {
+                LabelKind::Function if is_restart_vector(address) => "vector",
+                LabelKind::Function => "function",
+                LabelKind::Jump => "jump",
+                LabelKind::Data => "data",
+            };
+            (
+                address,
+                end.saturating_sub(address),
+                kind,
+                label.name.as_str(),
+            )
+        })
+        .collect();
+    if sort == Sort::Name {
+        symbols.sort_by_key(|&(_, _, _, name)| name);
+    }
+
+    for (address, size, kind, name) in symbols {
+        writeln!(out, "{address:04x} {size:04x} {kind:<8} {name}")?;
+    }
+    Ok(())
+}
+
+/// Whether the address is the target of a restart instruction.
+const fn is_restart_vector(address: usize) -> bool {
+    address.is_multiple_of(8) && address <= 0x38
+}
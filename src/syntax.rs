@@ -0,0 +1,188 @@
+//! Spelling of the plain listing, configurable to match the listings of other tools.
+
+use core::fmt::{self, Write};
+
+use crate::decode::decode;
+use crate::opcodes::{self, Immediate};
+
+/// Way a hexadecimal number is written.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HexStyle {
+    /// "0FFH", with a leading zero when the number starts with a letter.
+    Suffix,
+    /// "$FF".
+    Dollar,
+    /// "0xFF".
+    Prefix,
+}
+
+/// Way the lines of a plain listing are written.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Syntax {
+    /// Write mnemonics and registers in uppercase, like `MOV A,B`, or lowercase.
+    pub uppercase_mnemonics: bool,
+    /// Way byte immediates and ports are written.
+    pub byte_style: HexStyle,
+    /// Way 16-bit immediates and addresses are written.
+    pub word_style: HexStyle,
+    /// Write the digits of numbers, and the `H` suffix, in uppercase.
+    pub uppercase_digits: bool,
+    /// Written before byte immediates and ports, like `#`.
+    pub immediate_prefix: &'static str,
+    /// Columns the mnemonic is padded to when operands follow it.
+    pub mnemonic_width: usize,
+    /// Write the bytes of each instruction between its address and its assembly.
+    pub bytes: bool,
+}
+
+impl Syntax {
+    /// Spelling of [`decode::decode_into`](crate::decode::decode_into), like
+    /// `0003  cd 08 00  CALL  $0008` and `MVI   A,#0x41`.
+    pub const LISTING: Self = Self {
+        uppercase_mnemonics: true,
+        byte_style: HexStyle::Prefix,
+        word_style: HexStyle::Dollar,
+        uppercase_digits: false,
+        immediate_prefix: "#",
+        mnemonic_width: 6,
+        bytes: true,
+    };
+
+    /// Write the listing of a rom loaded at address 0 to `out`, one instruction per line.
+    ///
+    /// An instruction cut off by the end of the rom is written with the bytes that are
+    /// left and marked as truncated.
+    ///
+    /// # Errors
+    ///
+    /// Fails when `out` does.
+    pub fn decode_into(&self, rom: &[u8], out: &mut impl Write) -> fmt::Result {
+        for result in decode(rom) {
+            let (address, bytes) = match &result {
+                Ok(decoded) => (decoded.address, decoded.bytes),
+                Err(error) => (error.address(), error.bytes()),
+            };
+
+            write!(out, "{address:04x} ")?;
+            if self.bytes {
+                for byte in bytes {
+                    write!(out, " {byte:02x}")?;
+                }
+                for _ in bytes.len()..3 {
+                    out.write_str("   ")?;
+                }
+            }
+            out.write_str("  ")?;
+            match result {
+                Ok(decoded) => self.write_instruction(decoded.bytes, out)?,
+                Err(_) => out.write_str("; truncated instruction")?,
+            }
+            out.write_char('\n')?;
+        }
+        Ok(())
+    }
+
+    /// Write the assembly of a complete instruction, without allocating.
+    ///
+    /// # Errors
+    ///
+    /// Fails when `out` does.
+    pub fn write_instruction(&self, bytes: &[u8], out: &mut impl Write) -> fmt::Result {
+        let opcode = opcodes::lookup(bytes[0]);
+        self.write_name(opcode.mnemonic, out)?;
+        let has_operands = !opcode.registers.is_empty() || opcode.immediate != Immediate::Empty;
+        if !has_operands {
+            return Ok(());
+        }
+
+        for _ in opcode.mnemonic.len()..self.mnemonic_width {
+            out.write_char(' ')?;
+        }
+        self.write_name(opcode.registers, out)?;
+        if !opcode.registers.is_empty() && opcode.immediate != Immediate::Empty {
+            out.write_char(',')?;
+        }
+        match *bytes {
+            [_, low, high] => self.write_word(u16::from_le_bytes([low, high]), out),
+            [_, value] => self.write_byte(value, out),
+            _ => Ok(()),
+        }
+    }
+
+    /// Column the mnemonic of a line of the listing starts at, counting from 0, after the
+    /// address and the room for the bytes of the longest instruction.
+    #[must_use]
+    pub const fn mnemonic_column(&self) -> usize {
+        if self.bytes {
+            16
+        } else {
+            7
+        }
+    }
+
+    /// Write a 16-bit immediate or address, like `$0008`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when `out` does.
+    pub fn write_word(&self, value: u16, out: &mut impl Write) -> fmt::Result {
+        self.write_hex(self.word_style, value, 4, out)
+    }
+
+    /// Write a byte immediate or port, like `#0x41`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when `out` does.
+    pub fn write_byte(&self, value: u8, out: &mut impl Write) -> fmt::Result {
+        out.write_str(self.immediate_prefix)?;
+        self.write_hex(self.byte_style, u16::from(value), 2, out)
+    }
+
+    /// Write a mnemonic or registers in the case of the syntax.
+    fn write_name(&self, name: &str, out: &mut impl Write) -> fmt::Result {
+        if self.uppercase_mnemonics {
+            return out.write_str(name);
+        }
+        name.chars()
+            .try_for_each(|c| out.write_char(c.to_ascii_lowercase()))
+    }
+
+    /// Write a number in hexadecimal, using at least `digits` digits.
+    fn write_hex(
+        &self,
+        style: HexStyle,
+        value: u16,
+        digits: usize,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        match style {
+            HexStyle::Suffix => {
+                // Assemblers read a number starting with a letter as a name.
+                let significant = (u16::BITS - value.leading_zeros()).div_ceil(4) as usize;
+                let leading_digit = value >> (4 * (digits.max(significant) - 1));
+                if leading_digit >= 0xA {
+                    out.write_char('0')?;
+                }
+            }
+            HexStyle::Dollar => out.write_char('$')?,
+            HexStyle::Prefix => out.write_str("0x")?,
+        }
+        if self.uppercase_digits {
+            write!(out, "{value:0digits$X}")?;
+        } else {
+            write!(out, "{value:0digits$x}")?;
+        }
+        match (style, self.uppercase_digits) {
+            (HexStyle::Suffix, true) => out.write_char('H'),
+            (HexStyle::Suffix, false) => out.write_char('h'),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for Syntax {
+    fn default() -> Self {
+        Self::LISTING
+    }
+}
@@ -0,0 +1,79 @@
+//! Rendering of the opcode reference table.
+
+use intel_8080_disassembler::opcodes;
+
+/// Output style of the reference table.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Style {
+    /// Aligned columns for reading on the terminal.
+    Text,
+    Csv,
+    Markdown,
+}
+
+impl Style {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Self::Text),
+            "csv" => Some(Self::Csv),
+            "markdown" | "md" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+}
+
+const HEADER: [&str; 6] = ["Hex", "Mnemonic", "Operands", "Length", "Cycles", "Flags"];
+
+/// Print one row for each of the 256 opcodes.
+pub fn print(out: &mut dyn std::io::Write, style: Style) -> std::io::Result<()> {
+    print_row(out, style, HEADER.map(String::from))?;
+    if style == Style::Markdown {
+        writeln!(
+            out,
+            "|-----|----------|----------|--------|--------|-------|"
+        )?;
+    }
+
+    for byte in 0..=u8::MAX {
+        let opcode = opcodes::lookup(byte);
+        print_row(
+            out,
+            style,
+            [
+                format!("{byte:02X}"),
+                opcode.mnemonic.to_owned(),
+                opcode.operands(),
+                opcode.length().to_string(),
+                opcode.cycles.to_string(),
+                opcode.flags.to_string(),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn print_row(
+    out: &mut dyn std::io::Write,
+    style: Style,
+    [hex, mnemonic, operands, length, cycles, flags]: [String; 6],
+) -> std::io::Result<()> {
+    match style {
+        Style::Text => writeln!(
+            out,
+            "{hex:<5}{mnemonic:<10}{operands:<10}{length:<8}{cycles:<8}{flags}"
+        ),
+        Style::Csv => {
+            // Operands like "B,d16" contain the separator.
+            let operands = if operands.contains(',') {
+                format!("\"{operands}\"")
+            } else {
+                operands
+            };
+            writeln!(out, "{hex},{mnemonic},{operands},{length},{cycles},{flags}")
+        }
+        Style::Markdown => writeln!(
+            out,
+            "| {hex:<3} | {mnemonic:<8} | {operands:<8} | {length:<6} | {cycles:<6} | {flags:<5} |"
+        ),
+    }
+}
@@ -0,0 +1,351 @@
+//! Page showing an execution trace over time next to the listing, to find what the code was
+//! doing at any moment of a run.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::ops::Range;
+
+use anyhow::{bail, Context};
+use intel_8080_disassembler::analysis::Listing;
+use intel_8080_disassembler::tokens::{self, Kind};
+use intel_8080_disassembler::{decode, opcodes};
+
+/// Width of the timeline in pixels, at most, as traces run for millions of cycles.
+const MAX_WIDTH: u64 = 20_000;
+/// Width of the timeline in pixels, at least.
+const MIN_WIDTH: u64 = 800;
+/// Cycles per pixel of the timeline, unless that makes it too narrow or too wide.
+const CYCLES_PER_PIXEL: u64 = 10;
+/// Height of the lane of a function in the timeline, in pixels.
+const LANE: usize = 16;
+
+/// Instruction run by the emulator, with the cycle at which it started.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Step {
+    pub address: u16,
+    pub cycle: u64,
+}
+
+/// Read a trace, a line per instruction run: its address in hexadecimal and, optionally,
+/// the cycle at which it started in decimal, like `01a3 1520`.
+///
+/// The cycles left out follow from those of the instruction before, taking conditional
+/// calls and returns to be taken when the next instruction is not the one after them.
+/// Instructions are read from the image. Empty lines and lines starting with `#` are
+/// ignored.
+pub fn parse(text: &str, image: &[u8]) -> anyhow::Result<Vec<Step>> {
+    let mut steps: Vec<Step> = Vec::new();
+    for (line_index, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let step = parse_step(trimmed, steps.last(), image)
+            .with_context(|| format!("parsing line {}", line_index + 1))?;
+        steps.push(step);
+    }
+    Ok(steps)
+}
+
+fn parse_step(line: &str, previous: Option<&Step>, image: &[u8]) -> anyhow::Result<Step> {
+    let mut fields = line.split_whitespace();
+    let address = fields.next().unwrap_or_default();
+    let address = u16::from_str_radix(address.trim_start_matches("0x"), 16)
+        .with_context(|| format!("parsing address \"{address}\""))?;
+    let cycle = match (fields.next(), previous) {
+        (Some(cycle), _) => cycle
+            .parse()
+            .with_context(|| format!("parsing cycle \"{cycle}\""))?,
+        (None, None) => 0,
+        (None, Some(previous)) => {
+            previous.cycle + u64::from(taken(previous.address, address, image))
+        }
+    };
+    if fields.next().is_some() {
+        bail!("expected an address and a cycle");
+    }
+    if previous.is_some_and(|previous| cycle < previous.cycle) {
+        bail!("cycle {cycle} is before the cycle of the instruction before");
+    }
+    Ok(Step { address, cycle })
+}
+
+/// Cycles the instruction at `address` took, given the address run after it.
+fn taken(address: u16, next: u16, image: &[u8]) -> u8 {
+    let bytes = decode::decode_at(image, address);
+    let cycles = opcodes::lookup(bytes[0]).cycles;
+    let length = u16::try_from(bytes.len()).unwrap_or_default();
+    if next == address.wrapping_add(length) {
+        cycles.not_taken
+    } else {
+        cycles.taken
+    }
+}
+
+/// Print an HTML page with the trace as lanes of the functions over time, above the listing.
+///
+/// Clicking the timeline, or moving its slider, shows the instruction running at that cycle
+/// and its function, and highlights it in the listing, where each instruction is given with
+/// the times it ran.
+pub fn print(
+    out: &mut dyn std::io::Write,
+    listing: &Listing,
+    steps: &[Step],
+) -> std::io::Result<()> {
+    let functions: Vec<(u16, Range<usize>)> = listing
+        .functions
+        .iter()
+        .filter_map(|&function| Some((function, listing.function_range(function)?)))
+        .collect();
+
+    let mut runs = 0;
+    let mut counts: BTreeMap<u16, usize> = BTreeMap::new();
+    // Function of each step, and the lanes of the functions run, by address.
+    let mut owners = Vec::with_capacity(steps.len());
+    let mut lanes: BTreeMap<Option<u16>, usize> = BTreeMap::new();
+    for step in steps {
+        *counts.entry(step.address).or_default() += 1;
+        let owner = owner(&functions, step.address);
+        if owners.last() != Some(&owner) {
+            runs += 1;
+        }
+        owners.push(owner);
+        lanes.insert(owner, 0);
+    }
+    for (index, lane) in lanes.values_mut().enumerate() {
+        *lane = index;
+    }
+    let names: Vec<String> = lanes
+        .keys()
+        .map(|owner| {
+            owner.map_or_else(
+                || "(outside functions)".to_owned(),
+                |function| {
+                    listing
+                        .name(function)
+                        .map_or_else(|| format!("0x{function:04x}"), str::to_owned)
+                },
+            )
+        })
+        .collect();
+    let lanes: Vec<usize> = owners.iter().map(|owner| lanes[owner]).collect();
+    let end = steps.last().map_or(0, |last| last.cycle + 1);
+    let width = (end / CYCLES_PER_PIXEL).clamp(MIN_WIDTH, MAX_WIDTH);
+
+    writeln!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">"
+    )?;
+    writeln!(
+        out,
+        "<title>Timeline</title>\n<style>{STYLE}</style>\n</head>\n<body>"
+    )?;
+    writeln!(
+        out,
+        "<p>{} instructions run over {end} cycles, in {runs} runs of {} functions.</p>",
+        steps.len(),
+        names.len()
+    )?;
+    writeln!(out, "<div class=\"timeline\">\n<div class=\"names\">")?;
+    for name in &names {
+        writeln!(out, "<div>{}</div>", escape(name))?;
+    }
+    writeln!(out, "</div>\n<div class=\"scroll\">")?;
+    print_lanes(out, steps, &lanes, names.len(), end, width)?;
+    writeln!(out, "</div>\n</div>")?;
+    writeln!(
+        out,
+        "<input id=\"slider\" type=\"range\" min=\"0\" max=\"{}\" value=\"0\">",
+        end.saturating_sub(1)
+    )?;
+    writeln!(
+        out,
+        "<p id=\"status\">Click the timeline to see what ran then.</p>"
+    )?;
+
+    print_listing(out, listing, &counts)?;
+
+    let mut data = String::new();
+    for (step, lane) in steps.iter().zip(&lanes) {
+        // Writing to a string never fails.
+        let _ = write!(data, "[{},{},{lane}],", step.cycle, step.address);
+    }
+    let quoted: Vec<String> = names.iter().map(|name| format!("{name:?}")).collect();
+    writeln!(
+        out,
+        "<script>\nconst END = {end};\nconst WIDTH = {width};\nconst STEPS = [{data}];\nconst LANES = [{}];\n{SCRIPT}</script>",
+        quoted.join(",").replace("</", "<\\/")
+    )?;
+    writeln!(out, "</body>\n</html>")
+}
+
+/// Print the timeline, a lane per function with a rectangle for each time it ran.
+fn print_lanes(
+    out: &mut dyn std::io::Write,
+    steps: &[Step],
+    lanes: &[usize],
+    count: usize,
+    end: u64,
+    width: u64,
+) -> std::io::Result<()> {
+    let x = |cycle: u64| cycle * width / end.max(1);
+    let height = count.max(1) * LANE;
+    writeln!(
+        out,
+        "<svg id=\"lanes\" width=\"{width}\" height=\"{height}\" xmlns=\"http://www.w3.org/2000/svg\">"
+    )?;
+    // Runs of a lane closer than a pixel are drawn as one rectangle.
+    let mut drawn: BTreeMap<usize, (u64, u64)> = BTreeMap::new();
+    let mut start = 0;
+    for index in 1..=steps.len() {
+        if index < steps.len() && lanes[index] == lanes[start] {
+            continue;
+        }
+        let lane = lanes[start];
+        let (left, right) = (
+            x(steps[start].cycle),
+            x(steps.get(index).map_or(end, |s| s.cycle)),
+        );
+        match drawn.get_mut(&lane) {
+            Some((_, last)) if left <= *last + 1 => *last = right.max(*last),
+            _ => {
+                if let Some((left, right)) = drawn.insert(lane, (left, right)) {
+                    print_run(out, lane, left, right)?;
+                }
+            }
+        }
+        start = index;
+    }
+    for (lane, (left, right)) in drawn {
+        print_run(out, lane, left, right)?;
+    }
+    writeln!(
+        out,
+        "<line id=\"cursor\" x1=\"0\" y1=\"0\" x2=\"0\" y2=\"{height}\" stroke=\"#000000\"/>\n</svg>"
+    )
+}
+
+/// Function whose code holds an address, the one starting last when they overlap.
+fn owner(functions: &[(u16, Range<usize>)], address: u16) -> Option<u16> {
+    functions
+        .iter()
+        .filter(|(_, range)| range.contains(&usize::from(address)))
+        .map(|&(function, _)| function)
+        .max()
+}
+
+fn print_run(
+    out: &mut dyn std::io::Write,
+    lane: usize,
+    left: u64,
+    right: u64,
+) -> std::io::Result<()> {
+    writeln!(
+        out,
+        "<rect x=\"{left}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#d9534f\"/>",
+        lane * LANE + 2,
+        (right - left).max(1),
+        LANE - 4
+    )
+}
+
+/// Print the listing as a table, a row per label and line, with the times each instruction
+/// ran, its tokens colored by their kind.
+fn print_listing(
+    out: &mut dyn std::io::Write,
+    listing: &Listing,
+    counts: &BTreeMap<u16, usize>,
+) -> std::io::Result<()> {
+    writeln!(out, "<table class=\"listing\">")?;
+    for line in tokens::tokenize(listing) {
+        let mut html = String::new();
+        let mut end = 0;
+        for token in &line.tokens {
+            // Writing to a string never fails.
+            let _ = write!(
+                html,
+                "{}<span class=\"{}\">{}</span>",
+                escape(&line.text[end..token.range.start]),
+                token.kind.name(),
+                escape(line.slice(token))
+            );
+            end = token.range.end;
+        }
+        html.push_str(&escape(&line.text[end..]));
+
+        let address = line.address;
+        // Label lines only hold the name.
+        if line.tokens.first().map(|token| token.kind) != Some(Kind::Address) {
+            writeln!(out, "<tr><td></td><td>{html}</td></tr>")?;
+            continue;
+        }
+        let count = counts
+            .get(&address)
+            .map_or_else(String::new, usize::to_string);
+        writeln!(
+            out,
+            "<tr id=\"a{address}\"><td>{count}</td><td>{html}</td></tr>"
+        )?;
+    }
+    writeln!(out, "</table>")
+}
+
+/// Text escaped to be written in HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const STYLE: &str = "
+body { font-family: monospace; font-size: 12px; }
+.timeline { display: flex; border: 1px solid #cccccc; }
+.names div { height: 16px; line-height: 16px; padding: 0 8px; white-space: nowrap; }
+.scroll { overflow-x: auto; }
+#slider { width: 100%; }
+.listing td { padding: 0 12px 0 0; white-space: pre; }
+.listing td:nth-child(1) { color: #888888; text-align: right; }
+.address, .bytes, .comment { color: #888888; }
+.mnemonic { color: #c9302c; }
+.label { color: #2a5db0; }
+.immediate { color: #8e44ad; }
+.current { background: #ffe08a; }
+";
+
+/// Find the step running at a cycle, by bisection, and show it.
+const SCRIPT: &str = "
+const lanes = document.getElementById('lanes');
+const slider = document.getElementById('slider');
+let current = null;
+function show(cycle) {
+  let low = 0, high = STEPS.length - 1;
+  if (high < 0) return;
+  while (low < high) {
+    const middle = (low + high + 1) >> 1;
+    if (STEPS[middle][0] <= cycle) low = middle; else high = middle - 1;
+  }
+  const [start, address, lane] = STEPS[low];
+  const x = start * WIDTH / END;
+  const cursor = document.getElementById('cursor');
+  cursor.setAttribute('x1', x);
+  cursor.setAttribute('x2', x);
+  slider.value = cycle;
+  const hex = address.toString(16).padStart(4, '0');
+  const row = document.getElementById('a' + address);
+  const text = row ? row.cells[1].textContent : '(not in the listing)';
+  document.getElementById('status').textContent =
+    'cycle ' + cycle + ': ' + hex + '  ' + text + '  in ' + LANES[lane];
+  if (current) current.classList.remove('current');
+  current = row;
+  if (row) {
+    row.classList.add('current');
+    row.scrollIntoView({ block: 'center' });
+  }
+}
+lanes.addEventListener('click', event => {
+  const box = lanes.getBoundingClientRect();
+  show(Math.floor((event.clientX - box.left) * END / WIDTH));
+});
+slider.addEventListener('input', () => show(Number(slider.value)));
+";
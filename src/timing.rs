@@ -0,0 +1,124 @@
+//! Cycles taken to reach each instruction of a routine, for code timed against the display.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use intel_8080_disassembler::analysis::{Line, Listing};
+use intel_8080_disassembler::{decode, opcodes};
+
+/// Fewest and most cycles taken.
+type Span = (u32, u32);
+
+/// Print the instructions in `range`, the function starting at its first address, with the
+/// cycles taken to reach each of them from the entry of the function.
+///
+/// Calls count the cycles of the call instruction only, not those of the subroutine. Where
+/// paths of different lengths meet, the fewest and most cycles are given. Loops are counted
+/// once, and each jump back gives the cycles of an iteration.
+pub fn print(
+    out: &mut dyn std::io::Write,
+    listing: &Listing,
+    range: Range<usize>,
+) -> std::io::Result<()> {
+    let instructions: Vec<(u16, &[u8])> = listing
+        .lines
+        .iter()
+        .filter_map(|line| match line {
+            Line::Instruction { address, bytes } if range.contains(&usize::from(*address)) => {
+                Some((*address, bytes.as_slice()))
+            }
+            _ => None,
+        })
+        .collect();
+    let Some(&(entry, _)) = instructions.first() else {
+        return Ok(());
+    };
+
+    let name = listing
+        .name(entry)
+        .map_or_else(|| format!("0x{entry:04x}"), str::to_owned);
+    writeln!(
+        out,
+        "; cycles from the entry of {name}, without the subroutines it calls"
+    )?;
+    let mut arrivals: BTreeMap<u16, Span> = BTreeMap::from([(entry, (0, 0))]);
+    for &(address, bytes) in &instructions {
+        let opcode = opcodes::lookup(bytes[0]);
+        let arrival = arrivals.get(&address).copied();
+        let mut text = String::new();
+        // Writing to a string never fails.
+        let _ = decode::write_instruction(bytes, &mut text);
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        let mut line = format!(
+            "{address:04x}  {:<9} {text:<16} {:<6} {}",
+            hex.join(" "),
+            opcode.cycles.to_string(),
+            arrival.map_or_else(|| "?".to_owned(), span)
+        );
+
+        let Some((first, last)) = arrival else {
+            writeln!(out, "{line}")?;
+            continue;
+        };
+        for (target, (fewest, most)) in successors(address, bytes) {
+            let reached = (first + fewest, last + most);
+            if target > address {
+                let merged = arrivals.entry(target).or_insert(reached);
+                *merged = (merged.0.min(reached.0), merged.1.max(reached.1));
+            } else if let Some(&(start_first, start_last)) = arrivals.get(&target) {
+                let iteration = (
+                    reached.0.saturating_sub(start_last),
+                    reached.1.saturating_sub(start_first),
+                );
+                line = format!(
+                    "{line}  ; loops back to 0x{target:04x}, {} cycles per iteration",
+                    span(iteration)
+                );
+            }
+        }
+        writeln!(out, "{line}")?;
+    }
+    Ok(())
+}
+
+fn span((fewest, most): Span) -> String {
+    if fewest == most {
+        fewest.to_string()
+    } else {
+        format!("{fewest}-{most}")
+    }
+}
+
+/// Addresses execution goes to after an instruction, with the cycles it takes to get there.
+///
+/// Calls continue after the call, while jumps out of the function and returns lead
+/// nowhere.
+fn successors(address: u16, bytes: &[u8]) -> Vec<(u16, Span)> {
+    let opcode = opcodes::lookup(bytes[0]);
+    let cycles = opcode.cycles;
+    let next = address.wrapping_add(u16::try_from(bytes.len()).unwrap_or(u16::MAX));
+    let target = match *bytes {
+        [_, low, high] => Some(u16::from_le_bytes([low, high])),
+        _ => None,
+    };
+    let fixed = |c: u8| (u32::from(c), u32::from(c));
+    match (opcode.mnemonic, target) {
+        ("JMP", Some(target)) => vec![(target, fixed(cycles.taken))],
+        ("RET" | "PCHL", _) => Vec::new(),
+        (mnemonic, Some(target)) if mnemonic.starts_with('J') => vec![
+            (target, fixed(cycles.taken)),
+            (next, fixed(cycles.not_taken)),
+        ],
+        // Conditional returns only continue when the condition is not met.
+        (mnemonic, _) if mnemonic.starts_with('R') && cycles.taken != cycles.not_taken => {
+            vec![(next, fixed(cycles.not_taken))]
+        }
+        _ => vec![(
+            next,
+            (
+                u32::from(cycles.not_taken.min(cycles.taken)),
+                u32::from(cycles.taken.max(cycles.not_taken)),
+            ),
+        )],
+    }
+}
@@ -0,0 +1,232 @@
+//! Lines of the listing split into typed spans, the one place syntax highlighters and
+//! renderers learn what each part of a line is.
+
+use std::ops::Range;
+
+use crate::analysis::{Line, Listing};
+use crate::layout::Layout;
+use crate::opcodes::{self, Immediate};
+use crate::syntax::Syntax;
+
+/// What a span of a line holds.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Kind {
+    Address,
+    /// Bytes of an instruction or of data, in hexadecimal.
+    Bytes,
+    /// Name of an address, where it is defined or referred to.
+    Label,
+    Mnemonic,
+    /// Register or register pair.
+    Register,
+    /// Number, such as data, an address without a name, a port or a restart vector.
+    Immediate,
+    Comment,
+}
+
+impl Kind {
+    /// Name of the kind, like `mnemonic`.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Address => "address",
+            Self::Bytes => "bytes",
+            Self::Label => "label",
+            Self::Mnemonic => "mnemonic",
+            Self::Register => "register",
+            Self::Immediate => "immediate",
+            Self::Comment => "comment",
+        }
+    }
+}
+
+/// Span of a line, from the byte offset of its start in the text to that of its end.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Token {
+    pub kind: Kind,
+    pub range: Range<usize>,
+}
+
+/// Line of text with its tokens, in order. The spaces, commas and colons between them are
+/// not tokens.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenLine {
+    /// Address of the line, or of the label it defines.
+    pub address: u16,
+    pub text: String,
+    pub tokens: Vec<Token>,
+}
+
+impl TokenLine {
+    fn new(address: u16) -> Self {
+        Self {
+            address,
+            ..Self::default()
+        }
+    }
+
+    /// Append a token to the text.
+    fn push(&mut self, kind: Kind, text: &str) {
+        let start = self.text.len();
+        self.text.push_str(text);
+        self.tokens.push(Token {
+            kind,
+            range: start..self.text.len(),
+        });
+    }
+
+    /// Append text between the tokens.
+    fn separate(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    /// Pad the text with spaces up to a column of the listing.
+    fn pad(&mut self, column: usize) {
+        Layout::LISTING.pad(&mut self.text, column);
+    }
+
+    /// Text of a token of the line.
+    #[must_use]
+    pub fn slice(&self, token: &Token) -> &str {
+        &self.text[token.range.clone()]
+    }
+}
+
+/// Lines of a listing: a line per label, like `sub_0030:`, and one per line of the
+/// listing, like `0030  3e 07     MVI   A,#0x07`, ending with its comment if it has one.
+///
+/// Fields are aligned in the columns of [`Layout::LISTING`], and addresses with a name are
+/// referred to by it.
+#[must_use]
+pub fn tokenize(listing: &Listing) -> Vec<TokenLine> {
+    let mut lines = Vec::with_capacity(listing.lines.len() + listing.labels.len());
+    for line in &listing.lines {
+        let address = line.address();
+        if let Some(label) = listing.labels.get(&address) {
+            let mut tokens = TokenLine::new(address);
+            tokens.push(Kind::Label, &label.name);
+            tokens.separate(":");
+            lines.push(tokens);
+        }
+        let mut tokens = match line {
+            Line::Instruction { bytes, .. } => instruction(address, bytes, Some(listing)),
+            Line::Data { bytes, .. } => data(address, bytes),
+            Line::Padding { length, byte, .. } => {
+                let mut tokens = TokenLine::new(address);
+                tokens.push(Kind::Address, &format!("{address:04x}"));
+                tokens.separate("  ");
+                tokens.push(
+                    Kind::Comment,
+                    &format!("; 0x{length:04x} bytes of 0x{byte:02X} padding"),
+                );
+                tokens
+            }
+        };
+        let value = match line {
+            Line::Instruction { bytes, .. } => named_value(listing, bytes),
+            _ => None,
+        };
+        let comments: Vec<String> = [listing.comments.get(&address).cloned(), value]
+            .into_iter()
+            .flatten()
+            .collect();
+        if !comments.is_empty() {
+            tokens.pad(Layout::LISTING.comment_column);
+            tokens.push(Kind::Comment, &format!("; {}", comments.join("; ")));
+        }
+        lines.push(tokens);
+    }
+    lines
+}
+
+/// Value of a data immediate written as a name, which is given in the comment.
+fn named_value(listing: &Listing, bytes: &[u8]) -> Option<String> {
+    let &[opcode, low, high] = bytes else {
+        return None;
+    };
+    let opcode = opcodes::lookup(opcode);
+    let value = u16::from_le_bytes([low, high]);
+    (opcode.has_data_immediate() && listing.operand_name(&opcode, value).is_some())
+        .then(|| format!("0x{value:04x}"))
+}
+
+/// Line of an instruction decoded on its own, without names for the addresses, spelled
+/// like [`decode::write_instruction`](crate::decode::write_instruction).
+#[must_use]
+pub fn tokenize_instruction(address: u16, bytes: &[u8]) -> TokenLine {
+    instruction(address, bytes, None)
+}
+
+fn instruction(address: u16, bytes: &[u8], listing: Option<&Listing>) -> TokenLine {
+    let syntax = Syntax::LISTING;
+    let mut tokens = TokenLine::new(address);
+    tokens.push(Kind::Address, &format!("{address:04x}"));
+    tokens.separate("  ");
+    let hex: Vec<String> = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    tokens.push(Kind::Bytes, &hex.join(" "));
+    tokens.pad(Layout::LISTING.mnemonic_column);
+    let opcode = opcodes::lookup(bytes[0]);
+    tokens.push(Kind::Mnemonic, opcode.mnemonic);
+    if opcode.registers.is_empty() && opcode.immediate == Immediate::Empty {
+        return tokens;
+    }
+    tokens.pad(Layout::LISTING.operand_column);
+
+    for (index, register) in opcode.registers.split_terminator(',').enumerate() {
+        if index > 0 {
+            tokens.separate(",");
+        }
+        // Restart vectors are written like registers in the table.
+        if register.bytes().all(|b| b.is_ascii_digit()) {
+            tokens.push(Kind::Immediate, register);
+        } else {
+            tokens.push(Kind::Register, register);
+        }
+    }
+    if opcode.immediate == Immediate::Empty {
+        return tokens;
+    }
+    if !opcode.registers.is_empty() {
+        tokens.separate(",");
+    }
+    let mut text = String::new();
+    // Writing to a string never fails.
+    let _ = match *bytes {
+        [_, low, high] => {
+            let value = u16::from_le_bytes([low, high]);
+            match listing.and_then(|listing| listing.operand_name(&opcode, value)) {
+                Some(name) => {
+                    tokens.push(Kind::Label, name);
+                    return tokens;
+                }
+                None => syntax.write_word(value, &mut text),
+            }
+        }
+        [_, value] => syntax.write_byte(value, &mut text),
+        _ => Ok(()),
+    };
+    tokens.push(Kind::Immediate, &text);
+    tokens
+}
+
+fn data(address: u16, bytes: &[u8]) -> TokenLine {
+    let syntax = Syntax::LISTING;
+    let mut tokens = TokenLine::new(address);
+    tokens.push(Kind::Address, &format!("{address:04x}"));
+    tokens.pad(Layout::LISTING.mnemonic_column);
+    tokens.push(Kind::Mnemonic, "DB");
+    tokens.pad(Layout::LISTING.operand_column);
+    for (index, &byte) in bytes.iter().enumerate() {
+        if index > 0 {
+            tokens.separate(",");
+        }
+        let mut text = String::new();
+        // Writing to a string never fails.
+        let _ = syntax.write_byte(byte, &mut text);
+        tokens.push(Kind::Immediate, &text);
+    }
+    tokens
+}
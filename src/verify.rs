@@ -0,0 +1,89 @@
+//! Check that the generated source assembles back to the original bytes.
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context};
+use intel_8080_disassembler::layout::Layout;
+use intel_8080_disassembler::{analysis, asm, input};
+
+/// Outcome of assembling the generated source.
+pub enum Verdict {
+    Identical,
+    /// The assembled bytes differ, starting at the given offset.
+    Different {
+        first_offset: usize,
+        count: usize,
+        assembled_length: usize,
+    },
+}
+
+/// Assemble the source generated for `image` with a user provided assembler command.
+///
+/// In `command`, `{source}` is replaced by the path of the generated source and
+/// `{output}` by the path the assembler must write to, as a binary or Intel HEX file.
+pub fn verify(
+    image: &input::Image,
+    dialect: &asm::Dialect,
+    command: &str,
+) -> anyhow::Result<Verdict> {
+    let directory =
+        std::env::temp_dir().join(format!("{}-{}", env!("CARGO_PKG_NAME"), std::process::id()));
+    std::fs::create_dir_all(&directory).context("creating temporary directory")?;
+    let result = assemble_in(&directory, image, dialect, command);
+    // Leftover files in the temporary directory are harmless.
+    let _ = std::fs::remove_dir_all(&directory);
+    let assembled = result?;
+
+    let differences: Vec<usize> = (0..image.bytes.len().max(assembled.len()))
+        .filter(|&i| image.bytes.get(i) != assembled.get(i))
+        .collect();
+    Ok(match differences.first() {
+        None => Verdict::Identical,
+        Some(&first_offset) => Verdict::Different {
+            first_offset,
+            count: differences.len(),
+            assembled_length: assembled.len(),
+        },
+    })
+}
+
+// The placeholders of the command look like formatting arguments.
+#[allow(clippy::literal_string_with_formatting_args)]
+fn assemble_in(
+    directory: &Path,
+    image: &input::Image,
+    dialect: &asm::Dialect,
+    command: &str,
+) -> anyhow::Result<Vec<u8>> {
+    let source_path = directory.join("source.asm");
+    let output_path = directory.join("output.bin");
+
+    let listing = analysis::analyze(image, &[0]);
+    std::fs::write(
+        &source_path,
+        asm::render(&listing, dialect, &Layout::SOURCE),
+    )
+    .context("writing generated source")?;
+
+    let command = command
+        .replace("{source}", &source_path.to_string_lossy())
+        .replace("{output}", &output_path.to_string_lossy());
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .current_dir(directory)
+        .status()
+        .with_context(|| format!("running \"{command}\""))?;
+    if !status.success() {
+        bail!("assembler failed with {status}");
+    }
+
+    let output = std::fs::read(&output_path).context("reading assembler output")?;
+    if output.first() == Some(&b':') {
+        input::parse_intel_hex(&String::from_utf8_lossy(&output))
+            .context("parsing assembler output")
+    } else {
+        Ok(output)
+    }
+}
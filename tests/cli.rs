@@ -0,0 +1,286 @@
+//! The command line reads and writes the files it is given as they are.
+
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Write a file of the test to the temporary directory.
+fn write_file(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "{}-{}-{name}",
+        env!("CARGO_PKG_NAME"),
+        std::process::id()
+    ));
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+/// Run the command line, returning what it printed.
+fn run(args: &[&str]) -> Vec<u8> {
+    let output = Command::new(env!("CARGO_BIN_EXE_intel-8080-disassembler"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output.stdout
+}
+
+#[test]
+fn patching_a_rom_loaded_elsewhere_changes_the_file_at_its_offset() {
+    let rom = [0x3E, 0x01, 0xD3, 0x10, 0xC3, 0x00, 0x01, 0x00];
+    let path = write_file("patch.bin", &rom);
+    let path = path.to_str().unwrap();
+
+    let patched = run(&["patch", "--org", "0x100", "--at", "0x102", "NOP; NOP", path]);
+    assert_eq!(patched, [0x3E, 0x01, 0x00, 0x00, 0xC3, 0x00, 0x01, 0x00]);
+
+    let ips = run(&[
+        "patch",
+        "--org",
+        "0x100",
+        "--at",
+        "0x102",
+        "--emit-patch",
+        "ips",
+        "NOP; NOP",
+        path,
+    ]);
+    assert_eq!(ips, b"PATCH\x00\x00\x02\x00\x02\x00\x00EOF");
+
+    let text = run(&[
+        "patch",
+        "--base-file-offset",
+        "2",
+        "--org",
+        "0x100",
+        "--at",
+        "0x100",
+        "--emit-patch",
+        "text",
+        "NOP",
+        path,
+    ]);
+    assert_eq!(String::from_utf8(text).unwrap(), "0002: d3 -> 00\n");
+}
+
+#[test]
+fn a_rom_loaded_elsewhere_is_listed_from_its_origin() {
+    let path = write_file("org.bin", &[0x3E, 0x01, 0xD3, 0x10, 0xC3, 0x00, 0x01, 0x76]);
+    let path = path.to_str().unwrap();
+
+    let source = String::from_utf8(run(&["--org", "0x100", "--format", "asm", path])).unwrap();
+    let statements: Vec<&str> = source.lines().map(str::trim).collect();
+    assert!(statements.contains(&"ORG\t0100H"), "{source}");
+    assert!(!source.contains("0FFH"), "{source}");
+
+    let listing = String::from_utf8(run(&["--org", "0x100", "--labels", path])).unwrap();
+    let first = listing
+        .lines()
+        .find(|line| line.starts_with(|c: char| c.is_ascii_hexdigit()));
+    assert!(
+        first.is_some_and(|line| line.starts_with("0100")),
+        "{listing}"
+    );
+}
+
+#[test]
+fn the_checksums_of_a_rom_loaded_elsewhere_are_those_of_the_file() {
+    let rom = [0x3E, 0x01, 0xD3, 0x10, 0xC3, 0x00, 0x01, 0x00, 0x76];
+    let path = write_file("provenance.bin", &rom);
+    let name = path.file_name().unwrap().to_str().unwrap();
+    let sha256 = intel_8080_disassembler::hash::sha256(&rom);
+
+    let listing = run(&[
+        "--deterministic",
+        "--provenance",
+        "--org",
+        "0x100",
+        path.to_str().unwrap(),
+    ]);
+    let listing = String::from_utf8(listing).unwrap();
+    let input = format!("; input: {name}, 0x9 bytes, sha256 {sha256}\n");
+    assert!(listing.contains(&input), "{listing}");
+    assert!(listing.contains("; image: 0x109 bytes"), "{listing}");
+    assert!(
+        listing.contains(&format!(
+            "; 0100-0108  crc32 {:08x}  sha256 {sha256}  {name}\n",
+            intel_8080_disassembler::hash::crc32(&rom)
+        )),
+        "{listing}"
+    );
+}
+
+#[test]
+fn the_checksums_of_a_rom_after_a_header_are_those_of_the_file() {
+    let rom = [0x00, 0x09, 0x3E, 0x01, 0xD3, 0x10, 0xC3, 0x00, 0x01];
+    let path = write_file("header.bin", &rom);
+    let name = path.file_name().unwrap().to_str().unwrap();
+    let sha256 = intel_8080_disassembler::hash::sha256(&rom);
+
+    let listing = run(&[
+        "--deterministic",
+        "--provenance",
+        "--base-file-offset",
+        "2",
+        "--org",
+        "0x100",
+        path.to_str().unwrap(),
+    ]);
+    let listing = String::from_utf8(listing).unwrap();
+    let input = format!("; input: {name}, 0x9 bytes, sha256 {sha256}\n");
+    assert!(listing.contains(&input), "{listing}");
+    let load = "; load: file offset 0x2 at address 0x0100, after a header of 0x2 bytes\n";
+    assert!(listing.contains(load), "{listing}");
+    assert!(
+        listing.contains(&format!(
+            "; file       crc32 {:08x}  sha256 {sha256}  {name}\n",
+            intel_8080_disassembler::hash::crc32(&rom)
+        )),
+        "{listing}"
+    );
+}
+
+#[test]
+fn the_listing_is_spelled_like_the_library() {
+    let rom: Vec<u8> = (0..=u8::MAX)
+        .flat_map(|opcode| {
+            let length = intel_8080_disassembler::opcodes::lookup(opcode).length();
+            [opcode, 0x12, 0xBC].into_iter().take(length)
+        })
+        .collect();
+    let path = write_file("syntax.bin", &rom);
+
+    let listing = String::from_utf8(run(&["--deterministic", path.to_str().unwrap()])).unwrap();
+    let lines: Vec<&str> = listing
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with(';'))
+        .collect();
+    let mut expected = String::new();
+    intel_8080_disassembler::decode::decode_into(&rom, &mut expected).unwrap();
+    assert_eq!(lines, expected.lines().collect::<Vec<_>>());
+}
+
+#[test]
+fn the_tokens_of_the_listing_are_the_listing() {
+    // Code calling a function and reading data, with padding after it.
+    let mut rom = vec![0xCD, 0x08, 0x00, 0x3A, 0x0D, 0x00, 0x76, 0x00];
+    rom.extend([0x3E, 0x41, 0xD3, 0x10, 0xC9, 0x12, 0x34, 0x56]);
+    rom.resize(0x40, 0xFF);
+    let path = write_file("tokens.bin", &rom);
+    let path = path.to_str().unwrap();
+
+    let listing = String::from_utf8(run(&["--deterministic", "--labels", path])).unwrap();
+    let tokens = String::from_utf8(run(&["--format", "tokens", path])).unwrap();
+    let lines: Vec<&str> = listing
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with(';'))
+        .collect();
+    let texts: Vec<String> = tokens
+        .lines()
+        .map(|line| {
+            let text = &line[line.find("\"text\":\"").unwrap() + 8..];
+            text[..text.find("\",\"tokens\"").unwrap()].to_owned()
+        })
+        .collect();
+    assert_eq!(lines, texts);
+}
+
+#[test]
+fn printing_to_a_closed_pipe_stops_quietly() {
+    for args in [&["table"][..], &["info", "MOV"], &["--list-passes"]] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_intel-8080-disassembler"))
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // Close the reading end before anything is printed, like "| head" once it is done.
+        drop(child.stdout.take());
+        let output = child.wait_with_output().unwrap();
+        assert!(
+            output.status.success(),
+            "{args:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
+#[test]
+fn roms_of_the_same_name_are_written_to_different_files() {
+    let root = write_file("batch", &[]);
+    std::fs::remove_file(&root).unwrap();
+    for (directory, rom) in [("a", [0x3E, 0x01, 0x76]), ("b", [0x06, 0x02, 0x76])] {
+        std::fs::create_dir_all(root.join(directory)).unwrap();
+        std::fs::write(root.join(directory).join("x.bin"), rom).unwrap();
+    }
+    std::fs::write(root.join("b").join("x.rom"), [0x0E, 0x03, 0x76]).unwrap();
+    let output = root.join("out");
+
+    run(&[
+        "batch",
+        "--output-dir",
+        output.to_str().unwrap(),
+        "--format",
+        "asm",
+        root.join("a").to_str().unwrap(),
+        root.join("b").to_str().unwrap(),
+    ]);
+    let source = |name: &str| std::fs::read_to_string(output.join(name)).unwrap();
+    assert!(source("x.asm").contains("MVI\tA"));
+    assert!(source("x-2.asm").contains("MVI\tB"));
+    assert!(source("x-3.asm").contains("MVI\tC"));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn a_rom_that_cannot_be_parsed_is_a_data_error() {
+    let path = write_file("damaged.hex", b":0\xc3\xa90\n");
+    let status = |path: &str| {
+        Command::new(env!("CARGO_BIN_EXE_intel-8080-disassembler"))
+            .args(["--input-format", "ihex", path])
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+            .code()
+    };
+
+    // EX_DATAERR, and EX_IOERR for a file that cannot be read.
+    assert_eq!(status(path.to_str().unwrap()), Some(65));
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(status(path.to_str().unwrap()), Some(74));
+}
+
+#[test]
+fn an_instruction_cut_off_by_the_end_fails_only_when_asked() {
+    let path = write_file("truncated.bin", &[0x00, 0xC3, 0x00]);
+    let path = path.to_str().unwrap();
+
+    let listing = String::from_utf8(run(&["--deterministic", path])).unwrap();
+    assert!(listing.contains("; truncated instruction"), "{listing}");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_intel-8080-disassembler"))
+        .args(["--deterministic", "--fail-on-truncated", path])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.ends_with(b"0001  c3 00 \n"));
+}
+
+#[test]
+fn the_parameters_of_calls_are_not_checked_as_code() {
+    // CALL 0x0005 is followed by a byte of parameter.
+    let path = write_file("parameters.bin", &[0xCD, 0x05, 0x00, 0x01, 0x76, 0xC9]);
+
+    let listing = run(&[
+        "--deterministic",
+        "--strict",
+        "--call-params",
+        "5:1",
+        path.to_str().unwrap(),
+    ]);
+    let listing = String::from_utf8(listing).unwrap();
+    assert!(listing.contains("; parameter of CALL 0x0005"), "{listing}");
+}
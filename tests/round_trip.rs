@@ -0,0 +1,264 @@
+//! Encoding and decoding are inverse of each other for every instruction.
+
+use intel_8080_disassembler::decode::{
+    self, decode, decode_instruction, write_instruction, DecodeError, Disassembler,
+};
+use intel_8080_disassembler::effects::State;
+use intel_8080_disassembler::encode::encode;
+use intel_8080_disassembler::instruction::Instruction;
+use intel_8080_disassembler::opcodes::{self, canonical_opcode};
+use intel_8080_disassembler::tokens::{tokenize_instruction, Kind};
+use intel_8080_disassembler::DisasmError;
+
+/// Bytes of every instruction, with a few immediates for those that have one.
+fn instructions() -> Vec<Vec<u8>> {
+    let mut instructions = Vec::new();
+    for opcode in 0..=u8::MAX {
+        match opcodes::lookup(opcode).length() {
+            1 => instructions.push(vec![opcode]),
+            2 => {
+                for value in [0x00, 0x01, 0x7F, 0x80, 0xFF] {
+                    instructions.push(vec![opcode, value]);
+                }
+            }
+            _ => {
+                for value in [0x0000_u16, 0x0005, 0x1234, 0x8000, 0xFFFF] {
+                    let [low, high] = value.to_le_bytes();
+                    instructions.push(vec![opcode, low, high]);
+                }
+            }
+        }
+    }
+    instructions
+}
+
+fn text(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    write_instruction(bytes, &mut text).unwrap();
+    text
+}
+
+#[test]
+fn encoding_decoded_bytes_gives_them_back() {
+    for bytes in instructions() {
+        let mut expected = bytes.clone();
+        expected[0] = canonical_opcode(bytes[0]);
+        assert_eq!(encode(&text(&bytes)).unwrap(), expected, "{}", text(&bytes));
+    }
+}
+
+#[test]
+fn decoding_encoded_instructions_gives_them_back() {
+    for bytes in instructions() {
+        let instruction = text(&bytes);
+        let encoded = encode(&instruction).unwrap();
+        let decoded = decode(&encoded).next().unwrap().unwrap();
+        assert_eq!(text(decoded.bytes), instruction);
+        assert_eq!(decoded.bytes.len(), encoded.len());
+    }
+}
+
+#[test]
+fn only_undocumented_opcodes_have_another_canonical_opcode() {
+    for opcode in 0..=u8::MAX {
+        let canonical = canonical_opcode(opcode);
+        assert_eq!(canonical != opcode, opcodes::is_undocumented(opcode));
+        assert!(!opcodes::is_undocumented(canonical));
+        assert_eq!(opcodes::lookup(canonical), opcodes::lookup(opcode));
+    }
+}
+
+#[test]
+fn encoding_typed_instructions_gives_their_bytes_back() {
+    for bytes in instructions() {
+        let mut expected = bytes.clone();
+        expected[0] = canonical_opcode(bytes[0]);
+        let instruction = Instruction::decode(&bytes).unwrap();
+        assert_eq!(*instruction.encode().unwrap(), expected, "{}", text(&bytes));
+        assert_eq!(instruction.cycles(), Some(opcodes::lookup(bytes[0]).cycles));
+        assert_eq!(
+            instruction.flags_affected(),
+            Some(opcodes::lookup(bytes[0]).flags)
+        );
+        assert_eq!(Instruction::decode(&bytes[..bytes.len() - 1]), None);
+    }
+}
+
+#[test]
+fn decoded_operands_are_the_registers_of_the_opcode_table() {
+    use intel_8080_disassembler::instruction::Operand;
+
+    for bytes in instructions() {
+        let opcode = opcodes::lookup(bytes[0]);
+        let instruction = Instruction::decode(&bytes).unwrap();
+        let registers: Vec<String> = instruction
+            .operands()
+            .iter()
+            .filter(|operand| {
+                matches!(
+                    operand,
+                    Operand::Register(_) | Operand::RegisterPair(_) | Operand::RstVector(_)
+                )
+            })
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(registers.join(","), opcode.registers, "{}", text(&bytes));
+        assert!(text(&bytes).starts_with(opcode.mnemonic), "{instruction:?}");
+    }
+}
+
+#[test]
+fn encoding_tells_what_is_wrong_with_the_instruction() {
+    let message = |text: &str| encode(text).unwrap_err().to_string();
+
+    assert_eq!(message("FOO A"), "unknown mnemonic \"FOO\"");
+    assert_eq!(message("MOV M, M"), "invalid operands for MOV: \"M,M\"");
+    assert_eq!(message("PUSH SP"), "invalid operands for PUSH: \"SP\"");
+    assert_eq!(message("MVI 5, A"), "invalid operands for MVI: \"5,A\"");
+    assert_eq!(message("RST 8"), "invalid operands for RST: \"8\"");
+    assert_eq!(message("JNZ"), "invalid operands for JNZ: \"\"");
+    assert_eq!(
+        message("ADI 0x100"),
+        "immediate 0x100 does not fit in a byte"
+    );
+    assert_eq!(encode("jnz 0x1234").unwrap(), [0xC2, 0x34, 0x12]);
+    assert_eq!(encode("mov m, a").unwrap(), [0x77]);
+}
+
+#[test]
+fn visiting_sees_the_instructions_of_the_disassembler() {
+    // Ending with a jump cut off by the end of the rom.
+    let rom = [instructions().concat(), vec![0xC3, 0x00]].concat();
+    let mut visited = Vec::new();
+    let truncated = decode::visit(
+        &rom,
+        &mut |address, bytes: &[u8], instruction: &Instruction| {
+            assert_eq!(Instruction::decode(bytes), Some(*instruction));
+            visited.push((address, *instruction));
+        },
+    );
+    let mut disassembler = Disassembler::new(&rom);
+    assert_eq!(visited, disassembler.by_ref().collect::<Vec<_>>());
+    assert_eq!(truncated, Some([0xC3, 0x00].as_slice()));
+    assert_eq!(truncated, disassembler.truncated());
+}
+
+#[test]
+fn instructions_without_an_opcode_do_not_encode() {
+    use intel_8080_disassembler::instruction::{Register, RegisterPair};
+
+    for instruction in [
+        Instruction::Mov(Register::M, Register::M),
+        Instruction::Rst(8),
+        Instruction::Lxi(RegisterPair::Psw, 0x1234),
+        Instruction::Stax(RegisterPair::Hl),
+        Instruction::Ldax(RegisterPair::Sp),
+        Instruction::Push(RegisterPair::Sp),
+        Instruction::Pop(RegisterPair::Sp),
+    ] {
+        assert_eq!(instruction.encode(), None, "{instruction:?}");
+        assert_eq!(instruction.cycles(), None, "{instruction:?}");
+    }
+}
+
+#[test]
+fn disassembling_all_gives_the_lines_of_the_decoder_at_the_base() {
+    let rom = [instructions().concat(), vec![0xC3, 0x00]].concat();
+    let lines = decode::disassemble_all(&rom, 0x0100);
+    let decoded: Vec<_> = decode(&rom).map_while(Result::ok).collect();
+    assert_eq!(lines.len(), decoded.len());
+    for (line, decoded) in lines.iter().zip(&decoded) {
+        assert_eq!(line.address, 0x0100 + decoded.address);
+        assert_eq!(&*line.bytes, decoded.bytes);
+        assert_eq!(line.instruction, decoded.instruction());
+    }
+}
+
+#[test]
+fn disassembling_in_parallel_gives_the_lines_of_one_pass() {
+    // Pseudo-random bytes, so that instructions span the boundaries of the chunks.
+    let mut seed = 1_u32;
+    let rom: Vec<u8> = (0..0x48001)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            seed.to_be_bytes()[1]
+        })
+        .collect();
+    let decoded: Vec<_> = decode(&rom).map_while(Result::ok).collect();
+    for threads in [1, 3, 8] {
+        let lines =
+            decode::disassemble_parallel(&rom, std::num::NonZeroUsize::new(threads).unwrap());
+        assert_eq!(lines.len(), decoded.len());
+        for (line, decoded) in lines.iter().zip(&decoded) {
+            assert_eq!(line.address, decoded.address);
+            assert_eq!(&*line.bytes, decoded.bytes);
+        }
+    }
+}
+
+#[test]
+fn tokens_of_an_instruction_cover_its_listing_text() {
+    for bytes in instructions() {
+        let line = tokenize_instruction(0x0100, &bytes);
+        let mut text = String::new();
+        write_instruction(&bytes, &mut text).unwrap();
+        let kinds: Vec<Kind> = line.tokens.iter().map(|token| token.kind).collect();
+        assert_eq!(kinds[..3], [Kind::Address, Kind::Bytes, Kind::Mnemonic]);
+        let mnemonic = line.tokens[2].range.start;
+        assert_eq!(line.text[mnemonic..], text, "{bytes:02x?}");
+        // Only spaces and commas are left between the tokens.
+        let mut end = 0;
+        for token in &line.tokens {
+            assert!(!token.range.is_empty());
+            assert!(line.text[end..token.range.start]
+                .chars()
+                .all(|c| c == ' ' || c == ','));
+            end = token.range.end;
+        }
+        assert_eq!(end, line.text.len());
+    }
+}
+
+#[test]
+fn invalid_instructions_fail_with_an_error_to_match_on() {
+    assert!(matches!(encode("FOO"), Err(DisasmError::Encode(_))));
+    assert!(matches!(
+        encode("MVI A, 0x1zz"),
+        Err(DisasmError::Number { text, .. }) if text == "0x1zz"
+    ));
+    let mut state = State::random(0);
+    assert!(matches!(
+        state.execute(&[0xC3, 0x00, 0x00]),
+        Err(DisasmError::Unsupported(_))
+    ));
+    assert!(matches!(
+        state.execute(&[0x00, 0x3E]),
+        Err(DisasmError::Decode(DecodeError::Truncated { address: 1, bytes })) if bytes == [0x3E]
+    ));
+}
+
+#[test]
+fn decoding_one_instruction_tells_why_it_fails() {
+    for bytes in instructions() {
+        assert_eq!(
+            decode_instruction(&bytes),
+            Ok(Instruction::decode(&bytes).unwrap())
+        );
+        let cut = &bytes[..bytes.len() - 1];
+        if let Some(&opcode) = cut.first() {
+            let error = decode_instruction(cut).unwrap_err();
+            assert_eq!(
+                error,
+                DecodeError::Truncated {
+                    address: 0,
+                    bytes: cut.to_vec()
+                }
+            );
+            assert_eq!(error.bytes()[0], opcode);
+        }
+    }
+    assert_eq!(
+        decode_instruction(&[]),
+        Err(DecodeError::Empty { address: 0 })
+    );
+}